@@ -0,0 +1,38 @@
+//! Compares the heap-allocated `OutputLayer` with the array-based `StaticOutputLayer` on a 10-input, 5-output layer.
+//! Run with `cargo +nightly bench -p libnn`.
+
+#![feature(test)]
+
+extern crate test;
+
+use libnn::{OutputLayer, StaticOutputLayer, Weight, MEAN_SQUARED_ERROR, SIGMOID};
+use test::{black_box, Bencher};
+
+const INPUTS: [Weight; 10] = [0.1, -0.4, 0.9, 0.3, -0.7, 0.5, 0.0, -0.2, 0.8, -0.6];
+const EXPECTED: [Weight; 5] = [0., 1., 0., 1., 1.];
+
+fn init_weights(i: usize, j: usize) -> Weight { ((i * 10 + j) as Weight * 0.37).sin() }
+
+#[bench]
+fn dynamic_output_layer_train_step(b: &mut Bencher) {
+    let mut layer = OutputLayer::new(&SIGMOID, &MEAN_SQUARED_ERROR, &mut init_weights, 10, 5);
+    b.iter(|| {
+        layer.compute(black_box(&INPUTS));
+        layer.compute_costs(&EXPECTED);
+        layer.compute_gradients();
+        layer.update_weights(&INPUTS, 0.01);
+        black_box(&layer.outputs);
+    });
+}
+
+#[bench]
+fn static_output_layer_train_step(b: &mut Bencher) {
+    let mut layer = StaticOutputLayer::<10, 5>::new(&SIGMOID, &MEAN_SQUARED_ERROR, &mut init_weights);
+    b.iter(|| {
+        layer.compute(black_box(&INPUTS));
+        layer.compute_costs(&EXPECTED);
+        layer.compute_gradients();
+        layer.update_weights(&INPUTS, 0.01);
+        black_box(&layer.outputs);
+    });
+}
@@ -0,0 +1,53 @@
+use crate::{DenseLayer, Weight};
+
+/// Below this activation derivative a neuron passes back so little gradient that it is considered saturated
+const SATURATION_THRESHOLD: Weight = 0.01;
+
+/// Summary of a layer's activations from its most recent forward pass, for spotting dead or saturated layers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActivationStats {
+    /// Mean of the outputs after activation
+    pub mean: Weight,
+    /// Standard deviation of the outputs after activation
+    pub std: Weight,
+    /// Fraction of neurons that output exactly 0, as dead ReLUs do
+    pub fraction_dead: f32,
+    /// Fraction of neurons where the activation's derivative is below 0.01.  Dead ReLUs count as saturated too.
+    pub fraction_saturated: f32,
+}
+
+impl DenseLayer {
+    /// Computes statistics over the values from the most recent call to `forward_propagate`.
+    pub fn compute_activation_stats(&self) -> ActivationStats {
+        let count = self.outputs.len();
+        if count == 0 {
+            return ActivationStats {
+                mean: 0.,
+                std: 0.,
+                fraction_dead: 0.,
+                fraction_saturated: 0.,
+            };
+        }
+
+        let mean = self.outputs.iter().sum::<Weight>() / count as Weight;
+        let variance = self
+            .outputs
+            .iter()
+            .map(|output| (output - mean).powi(2))
+            .sum::<Weight>()
+            / count as Weight;
+        let dead_count = self.outputs.iter().filter(|&&output| output == 0.).count();
+        let saturated_count = self
+            .outputs_before_activation
+            .iter()
+            .filter(|&&x| self.activation_fn.derivative(x).abs() < SATURATION_THRESHOLD)
+            .count();
+
+        ActivationStats {
+            mean,
+            std: variance.sqrt(),
+            fraction_dead: dead_count as f32 / count as f32,
+            fraction_saturated: saturated_count as f32 / count as f32,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{Network, Weight};
+
+/// Attributes a network's output to each of its inputs
+pub struct InputAttribution;
+
+impl InputAttribution {
+    /// Integrated gradients (Sundararajan et al., 2017): the gradient of output `output_ix` with respect to each
+    /// input, averaged along the straight line from `baseline` to `inputs` and scaled by how far that input moved.
+    ///
+    /// The path is sampled at the midpoints of `num_samples` equal steps.  The attributions add up to the change in
+    /// the output between the baseline and the inputs, up to the error of that approximation.
+    pub fn shap_values(
+        network: &mut Network,
+        inputs: &[Weight],
+        baseline: &[Weight],
+        output_ix: usize,
+        num_samples: usize,
+    ) -> Vec<Weight> {
+        debug_assert_eq!(inputs.len(), baseline.len());
+        assert!(num_samples > 0, "Integrated gradients needs at least one sample");
+        let mut output_gradients = vec![0.; network.outputs.outputs.len()];
+        output_gradients[output_ix] = 1.;
+
+        let mut total_gradients = vec![0.; inputs.len()];
+        let mut input_gradients = vec![0.; inputs.len()];
+        for sample_ix in 0..num_samples {
+            let alpha = (sample_ix as Weight + 0.5) / num_samples as Weight;
+            let interpolated: Vec<Weight> = baseline
+                .iter()
+                .zip(inputs.iter())
+                .map(|(&baseline, &input)| baseline + alpha * (input - baseline))
+                .collect();
+            network.forward_propagate(&interpolated);
+            network.backpropagate(&output_gradients);
+            network.compute_input_gradients(&mut input_gradients);
+            for (total, &gradient) in total_gradients.iter_mut().zip(input_gradients.iter()) {
+                *total += gradient;
+            }
+        }
+
+        total_gradients
+            .iter()
+            .zip(inputs.iter().zip(baseline.iter()))
+            .map(|(&total, (&input, &baseline))| (input - baseline) * total / num_samples as Weight)
+            .collect()
+    }
+}
@@ -0,0 +1,122 @@
+use rand::Rng;
+
+use crate::Weight;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SequenceTransform {
+    /// Adds Gaussian noise with the given standard deviation to every element
+    Jitter { std_dev: Weight },
+    /// Multiplies the whole sequence by a single factor drawn uniformly from [min, max)
+    Scale { min: Weight, max: Weight },
+    /// Stretches or compresses the sequence by a factor drawn uniformly from [1 - max_warp, 1 + max_warp), linearly
+    /// interpolating between the original elements
+    TimeWarp { max_warp: Weight },
+    /// Extracts a random contiguous subsequence between `min_len` and `max_len` elements long
+    Crop { min_len: usize, max_len: usize },
+}
+
+/// Applies a chain of random transforms to training sequences to make it harder for a network to overfit them.
+///
+/// Transforms are applied in the order they were added, with each one seeing the output of the last.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceAugmenter {
+    pub transforms: Vec<SequenceTransform>,
+}
+
+impl SequenceAugmenter {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn jitter(mut self, std_dev: Weight) -> Self {
+        self.transforms.push(SequenceTransform::Jitter { std_dev });
+        self
+    }
+
+    pub fn scale(mut self, min: Weight, max: Weight) -> Self {
+        assert!(min < max, "Scale range must not be empty");
+        self.transforms.push(SequenceTransform::Scale { min, max });
+        self
+    }
+
+    pub fn time_warp(mut self, max_warp: Weight) -> Self {
+        assert!(
+            (0. ..1.).contains(&max_warp),
+            "Time warp must be at least 0 and less than 1"
+        );
+        self.transforms.push(SequenceTransform::TimeWarp { max_warp });
+        self
+    }
+
+    pub fn crop(mut self, min_len: usize, max_len: usize) -> Self {
+        assert!(
+            min_len > 0 && min_len <= max_len,
+            "Crop lengths must satisfy 0 < min_len <= max_len"
+        );
+        self.transforms.push(SequenceTransform::Crop { min_len, max_len });
+        self
+    }
+
+    pub fn augment(&self, sequence: &[Weight], rng: &mut impl Rng) -> Vec<Weight> {
+        let mut sequence = sequence.to_owned();
+        for transform in &self.transforms {
+            if sequence.is_empty() {
+                break;
+            }
+            sequence = transform.apply(&sequence, rng);
+        }
+        sequence
+    }
+}
+
+impl SequenceTransform {
+    pub fn apply(&self, sequence: &[Weight], rng: &mut impl Rng) -> Vec<Weight> {
+        match *self {
+            SequenceTransform::Jitter { std_dev } =>
+                sequence.iter().map(|&x| x + std_dev * standard_normal(rng)).collect(),
+            SequenceTransform::Scale { min, max } => {
+                let factor = rng.gen_range(min, max);
+                sequence.iter().map(|&x| x * factor).collect()
+            },
+            SequenceTransform::TimeWarp { max_warp } => {
+                let factor = if max_warp > 0. {
+                    rng.gen_range(1. - max_warp, 1. + max_warp)
+                } else {
+                    1.
+                };
+                let new_len = ((sequence.len() as Weight * factor).round() as usize).max(1);
+                resample(sequence, new_len)
+            },
+            SequenceTransform::Crop { min_len, max_len } => {
+                // Sequences shorter than `min_len` are left whole rather than padded
+                let max_len = max_len.min(sequence.len());
+                let min_len = min_len.min(max_len);
+                let len = rng.gen_range(min_len, max_len + 1);
+                let start = rng.gen_range(0, sequence.len() - len + 1);
+                sequence[start..start + len].to_owned()
+            },
+        }
+    }
+}
+
+/// Box-Muller transform, since `rand` 0.7 only ships normal distributions in a separate crate
+pub(crate) fn standard_normal(rng: &mut impl Rng) -> Weight {
+    let u1: Weight = 1. - rng.gen::<Weight>();
+    let u2: Weight = rng.gen();
+    (-2. * u1.ln()).sqrt() * (2. * std::f32::consts::PI * u2).cos()
+}
+
+/// Linearly interpolates `sequence` onto `new_len` evenly spaced points, keeping the first and last elements fixed.
+fn resample(sequence: &[Weight], new_len: usize) -> Vec<Weight> {
+    if sequence.len() == 1 || new_len == 1 {
+        return vec![sequence[0]; new_len];
+    }
+
+    let step = (sequence.len() - 1) as Weight / (new_len - 1) as Weight;
+    (0..new_len)
+        .map(|ix| {
+            let pos = ix as Weight * step;
+            let lower = (pos.floor() as usize).min(sequence.len() - 2);
+            let t = pos - lower as Weight;
+            sequence[lower] * (1. - t) + sequence[lower + 1] * t
+        })
+        .collect()
+}
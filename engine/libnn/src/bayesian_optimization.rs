@@ -0,0 +1,262 @@
+use rand::Rng;
+
+use crate::{
+    erf,
+    hyperparameter_search::{best_trial, run_trial},
+    HyperParamConfig, LabeledExamples, Network, ParamSpace, TrialResult, Weight,
+};
+
+/// Gaussian process regression with an RBF kernel, `k(a, b) = signal_variance * exp(-|a - b|^2 / (2 *
+/// length_scale^2))`, and Gaussian observation noise.
+///
+/// Targets are standardized before fitting so that the kernel hyperparameters don't depend on the scale of the losses
+/// being modelled, which means `signal_variance` is relative to the variance of the targets.
+pub struct GaussianProcess {
+    pub length_scale: Weight,
+    pub signal_variance: Weight,
+    pub noise_variance: Weight,
+    inputs: Vec<Vec<Weight>>,
+    target_mean: f64,
+    target_std: f64,
+    /// Lower triangular Cholesky factor of the kernel matrix plus noise
+    cholesky: Vec<Vec<f64>>,
+    /// The standardized targets multiplied by the inverse of the kernel matrix plus noise
+    alpha: Vec<f64>,
+}
+
+impl GaussianProcess {
+    pub fn new(length_scale: Weight, signal_variance: Weight, noise_variance: Weight) -> Self {
+        GaussianProcess {
+            length_scale,
+            signal_variance,
+            noise_variance,
+            inputs: Vec::new(),
+            target_mean: 0.,
+            target_std: 1.,
+            cholesky: Vec::new(),
+            alpha: Vec::new(),
+        }
+    }
+
+    pub fn kernel(&self, a: &[Weight], b: &[Weight]) -> Weight {
+        let squared_distance: Weight = a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum();
+        self.signal_variance * (-squared_distance / (2. * self.length_scale * self.length_scale)).exp()
+    }
+
+    pub fn observation_count(&self) -> usize { self.inputs.len() }
+
+    /// Conditions the process on `targets` observed at `inputs`, replacing any earlier observations.
+    pub fn fit(&mut self, inputs: &[Vec<Weight>], targets: &[Weight]) {
+        assert_eq!(inputs.len(), targets.len());
+        let count = targets.len() as f64;
+        self.target_mean = targets.iter().map(|&y| y as f64).sum::<f64>() / count.max(1.);
+        let variance = targets
+            .iter()
+            .map(|&y| (y as f64 - self.target_mean).powi(2))
+            .sum::<f64>()
+            / count.max(1.);
+        self.target_std = if variance > 0. { variance.sqrt() } else { 1. };
+
+        let mut covariance: Vec<Vec<f64>> = inputs
+            .iter()
+            .map(|a| inputs.iter().map(|b| self.kernel(a, b) as f64).collect())
+            .collect();
+        for (ix, row) in covariance.iter_mut().enumerate() {
+            row[ix] += self.noise_variance as f64;
+        }
+        self.cholesky = cholesky(&covariance);
+
+        let standardized: Vec<f64> = targets
+            .iter()
+            .map(|&y| (y as f64 - self.target_mean) / self.target_std)
+            .collect();
+        self.alpha = solve_lower_transposed(&self.cholesky, &solve_lower(&self.cholesky, &standardized));
+        self.inputs = inputs.to_vec();
+    }
+
+    /// Returns the posterior mean and variance at `x`.  The variance doesn't include observation noise.
+    pub fn predict(&self, x: &[Weight]) -> (Weight, Weight) {
+        let prior_variance = self.signal_variance as f64 * self.target_std * self.target_std;
+        if self.inputs.is_empty() {
+            return (self.target_mean as Weight, prior_variance as Weight);
+        }
+
+        let covariances: Vec<f64> = self.inputs.iter().map(|input| self.kernel(input, x) as f64).collect();
+        let mean = covariances
+            .iter()
+            .zip(self.alpha.iter())
+            .map(|(k, a)| k * a)
+            .sum::<f64>();
+        let v = solve_lower(&self.cholesky, &covariances);
+        let variance = (self.signal_variance as f64 - v.iter().map(|v| v * v).sum::<f64>()).max(0.);
+        (
+            (self.target_mean + mean * self.target_std) as Weight,
+            (variance * self.target_std * self.target_std) as Weight,
+        )
+    }
+}
+
+/// Cholesky decomposition of a symmetric positive definite matrix.  Pivots that come out non-positive through
+/// rounding are clamped to a tiny value rather than failing.
+fn cholesky(matrix: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let size = matrix.len();
+    let mut lower = vec![vec![0.; size]; size];
+    for row in 0..size {
+        for col in 0..=row {
+            let sum: f64 = (0..col).map(|k| lower[row][k] * lower[col][k]).sum();
+            if row == col {
+                lower[row][col] = (matrix[row][row] - sum).max(1e-12).sqrt();
+            } else {
+                lower[row][col] = (matrix[row][col] - sum) / lower[col][col];
+            }
+        }
+    }
+    lower
+}
+
+/// Solves `lower * x = b` by forward substitution.
+fn solve_lower(lower: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let mut x = vec![0.; b.len()];
+    for row in 0..b.len() {
+        let sum: f64 = (0..row).map(|col| lower[row][col] * x[col]).sum();
+        x[row] = (b[row] - sum) / lower[row][row];
+    }
+    x
+}
+
+/// Solves `lower^T * x = b` by back substitution.
+fn solve_lower_transposed(lower: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let mut x = vec![0.; b.len()];
+    for row in (0..b.len()).rev() {
+        let sum: f64 = ((row + 1)..b.len()).map(|col| lower[col][row] * x[col]).sum();
+        x[row] = (b[row] - sum) / lower[row][row];
+    }
+    x
+}
+
+/// Scores how worthwhile it would be to try a point next, given the surrogate's posterior there.  Losses are being
+/// minimized, so both favour a low mean and a high variance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AcquisitionFunction {
+    /// Expected amount by which the loss will beat the best so far by more than `xi`
+    ExpectedImprovement { xi: Weight },
+    /// `kappa` standard deviations below the mean, negated so that higher is better
+    UpperConfidenceBound { kappa: Weight },
+}
+
+impl AcquisitionFunction {
+    pub fn evaluate(&self, mean: Weight, variance: Weight, best_loss: Weight) -> Weight {
+        let std_dev = variance.max(0.).sqrt();
+        match *self {
+            AcquisitionFunction::ExpectedImprovement { xi } => {
+                let improvement = best_loss - mean - xi;
+                if std_dev == 0. {
+                    return improvement.max(0.);
+                }
+                let z = improvement / std_dev;
+                let cdf = 0.5 * (1. + erf(z * std::f32::consts::FRAC_1_SQRT_2));
+                let pdf = (-0.5 * z * z).exp() / (2. * std::f32::consts::PI).sqrt();
+                improvement * cdf + std_dev * pdf
+            },
+            AcquisitionFunction::UpperConfidenceBound { kappa } => kappa * std_dev - mean,
+        }
+    }
+}
+
+/// Searches `param_space` by fitting a Gaussian process to the validation losses seen so far and trying whichever
+/// point maximizes `acquisition` next, so that later trials concentrate where the losses have been low or nothing
+/// is known yet.  The first `n_initial_points` trials are sampled at random to give the process something to fit.
+///
+/// The acquisition function is maximized over `n_candidates` random points rather than by gradient ascent, which is
+/// plenty for a three dimensional space.
+pub struct BayesianOptimizer {
+    pub gp: GaussianProcess,
+    pub param_space: ParamSpace,
+    pub acquisition: AcquisitionFunction,
+    pub n_trials: usize,
+    pub n_initial_points: usize,
+    pub n_candidates: usize,
+    /// Epochs each trial's network is trained for
+    pub epochs: usize,
+    pub rng: pcg::Pcg,
+    /// Every trial observed so far, in order
+    pub trials: Vec<TrialResult>,
+}
+
+impl BayesianOptimizer {
+    pub fn new(
+        param_space: ParamSpace,
+        acquisition: AcquisitionFunction,
+        n_trials: usize,
+        epochs: usize,
+        seed: u64,
+    ) -> Self {
+        BayesianOptimizer {
+            gp: GaussianProcess::new(0.25, 1., 1e-4),
+            param_space,
+            acquisition,
+            n_trials,
+            n_initial_points: 5,
+            n_candidates: 1000,
+            epochs,
+            rng: pcg::Pcg::new(seed, 0),
+            trials: Vec::new(),
+        }
+    }
+
+    /// The configuration to try next
+    pub fn suggest(&mut self) -> HyperParamConfig {
+        if self.trials.len() < self.n_initial_points || self.gp.observation_count() == 0 {
+            return self.param_space.sample(&mut self.rng);
+        }
+
+        let best_loss = best_trial(&self.trials).validation_loss;
+        let dimensions = self.param_space.to_unit_cube(&self.trials[0].config).len();
+        let mut best_point = Vec::new();
+        let mut best_score = Weight::NEG_INFINITY;
+        for _ in 0..self.n_candidates {
+            let point: Vec<Weight> = (0..dimensions).map(|_| self.rng.gen_range(0., 1.)).collect();
+            let (mean, variance) = self.gp.predict(&point);
+            let score = self.acquisition.evaluate(mean, variance, best_loss);
+            if score > best_score {
+                best_score = score;
+                best_point = point;
+            }
+        }
+        self.param_space.from_unit_cube(&best_point)
+    }
+
+    /// Records the result of a trial and refits the Gaussian process.  Trials that diverged are kept in `trials` but
+    /// left out of the fit.
+    pub fn observe(&mut self, config: HyperParamConfig, validation_loss: Weight) {
+        self.trials.push(TrialResult {
+            config,
+            validation_loss,
+        });
+
+        let (inputs, targets): (Vec<Vec<Weight>>, Vec<Weight>) = self
+            .trials
+            .iter()
+            .filter(|trial| trial.validation_loss.is_finite())
+            .map(|trial| (self.param_space.to_unit_cube(&trial.config), trial.validation_loss))
+            .unzip();
+        self.gp.fit(&inputs, &targets);
+    }
+
+    /// Runs `n_trials` trials, building each network with `build`, and returns the configuration with the lowest
+    /// validation loss.
+    pub fn run(
+        &mut self,
+        build: impl Fn(&HyperParamConfig) -> Network,
+        training: LabeledExamples,
+        validation: LabeledExamples,
+    ) -> HyperParamConfig {
+        assert!(self.n_trials > 0, "Need at least one trial");
+        for _ in 0..self.n_trials {
+            let config = self.suggest();
+            let validation_loss = run_trial(&build, &config, training, validation, self.epochs);
+            self.observe(config, validation_loss);
+        }
+        best_trial(&self.trials).config
+    }
+}
@@ -0,0 +1,81 @@
+use crate::Weight;
+
+/// A decoded token sequence and the sum of the log probabilities of its tokens
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hypothesis {
+    pub tokens: Vec<usize>,
+    pub score: Weight,
+}
+
+/// Searches for the most probable token sequence by keeping the `beam_width` best partial sequences at every step.
+/// A beam width of 1 is greedy decoding.
+///
+/// The model is any function from the tokens decoded so far to the log probability of each possible next token, so
+/// it can wrap a `Network` fed a window of previous tokens or any other decoder.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeamSearchDecoder {
+    pub beam_width: usize,
+    pub max_length: usize,
+    pub eos_token: usize,
+}
+
+impl BeamSearchDecoder {
+    pub fn new(beam_width: usize, max_length: usize, eos_token: usize) -> Self {
+        assert!(beam_width > 0, "Beam width must be at least 1");
+        BeamSearchDecoder {
+            beam_width,
+            max_length,
+            eos_token,
+        }
+    }
+
+    /// Returns the highest scoring hypothesis that ended with `eos_token`, or the best unfinished one if none did
+    /// within `max_length` tokens.
+    pub fn decode(&self, mut next_token_log_probs: impl FnMut(&[usize]) -> Vec<Weight>) -> Hypothesis {
+        let mut beam = vec![Hypothesis {
+            tokens: Vec::new(),
+            score: 0.,
+        }];
+        let mut finished: Vec<Hypothesis> = Vec::new();
+
+        for _ in 0..self.max_length {
+            let mut candidates = Vec::new();
+            for hypothesis in &beam {
+                for (token, log_prob) in next_token_log_probs(&hypothesis.tokens).into_iter().enumerate() {
+                    let mut tokens = hypothesis.tokens.clone();
+                    tokens.push(token);
+                    candidates.push(Hypothesis {
+                        tokens,
+                        score: hypothesis.score + log_prob,
+                    });
+                }
+            }
+            candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+            candidates.truncate(self.beam_width);
+
+            beam.clear();
+            for candidate in candidates {
+                if candidate.tokens.last() == Some(&self.eos_token) {
+                    finished.push(candidate);
+                } else {
+                    beam.push(candidate);
+                }
+            }
+            // Scores only decrease as tokens are added, so nothing left in the beam can beat a finished hypothesis
+            // that's already better than all of it
+            let best_finished = finished.iter().map(|h| h.score).fold(Weight::NEG_INFINITY, Weight::max);
+            if beam.iter().all(|h| h.score <= best_finished) {
+                break;
+            }
+        }
+
+        let candidates = if finished.is_empty() { beam } else { finished };
+        candidates
+            .into_iter()
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or(Hypothesis {
+                tokens: Vec::new(),
+                score: 0.,
+            })
+    }
+}
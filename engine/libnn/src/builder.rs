@@ -0,0 +1,265 @@
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{ActivationFunction, CostFunction, DenseLayer, Network, OutputLayer, Weight, IDENTITY, MEAN_SQUARED_ERROR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    ZeroOutputCount,
+    ZeroInputCount,
+    NoHiddenLayers,
+    ZeroNeuronCount {
+        hidden_layer_ix: usize,
+    },
+    /// `WeightInit::Uniform` needs `min` to be below `max`
+    EmptyWeightRange,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::ZeroOutputCount => write!(f, "layer must have at least one output"),
+            BuildError::ZeroInputCount => write!(f, "layer must have at least one input"),
+            BuildError::NoHiddenLayers => write!(f, "network must have at least one hidden layer"),
+            BuildError::ZeroNeuronCount { hidden_layer_ix } =>
+                write!(f, "hidden layer {} must have at least one neuron", hidden_layer_ix),
+            BuildError::EmptyWeightRange => write!(f, "uniform weight initialization needs min to be below max"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds a `DenseLayer` without having to pass every initializer up front.
+///
+/// Unless overridden, layers use the identity activation function, weights drawn uniformly from [-1, 1], and biases of
+/// 0.
+pub struct DenseLayerBuilder {
+    output_count: usize,
+    input_count: usize,
+    activation_fn: &'static dyn ActivationFunction,
+    init_weights: Option<Box<dyn FnMut(usize, usize) -> Weight>>,
+    init_biases: Option<Box<dyn FnMut(usize) -> Weight>>,
+    use_bias: bool,
+}
+
+impl Default for DenseLayerBuilder {
+    fn default() -> Self {
+        DenseLayerBuilder {
+            output_count: 0,
+            input_count: 0,
+            activation_fn: &IDENTITY,
+            init_weights: None,
+            init_biases: None,
+            use_bias: true,
+        }
+    }
+}
+
+impl DenseLayerBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn output_count(mut self, output_count: usize) -> Self {
+        self.output_count = output_count;
+        self
+    }
+
+    pub fn input_count(mut self, input_count: usize) -> Self {
+        self.input_count = input_count;
+        self
+    }
+
+    pub fn activation(mut self, activation_fn: &'static dyn ActivationFunction) -> Self {
+        self.activation_fn = activation_fn;
+        self
+    }
+
+    pub fn weight_init(mut self, init_weights: impl FnMut(usize, usize) -> Weight + 'static) -> Self {
+        self.init_weights = Some(Box::new(init_weights));
+        self
+    }
+
+    pub fn bias_init(mut self, init_biases: impl FnMut(usize) -> Weight + 'static) -> Self {
+        self.init_biases = Some(Box::new(init_biases));
+        self
+    }
+
+    /// Layers built with `use_bias(false)` have no biases, and any `bias_init` is ignored.
+    pub fn use_bias(mut self, use_bias: bool) -> Self {
+        self.use_bias = use_bias;
+        self
+    }
+
+    pub fn build(self) -> Result<DenseLayer, BuildError> {
+        if self.output_count == 0 {
+            return Err(BuildError::ZeroOutputCount);
+        }
+        if self.input_count == 0 {
+            return Err(BuildError::ZeroInputCount);
+        }
+
+        let mut init_weights = self.init_weights.unwrap_or_else(|| {
+            let mut rng = pcg::Pcg::default();
+            Box::new(move |_, _| rng.gen_range(-1., 1.))
+        });
+        if !self.use_bias {
+            return Ok(DenseLayer::new_without_bias(
+                self.output_count,
+                self.input_count,
+                &mut init_weights,
+                self.activation_fn,
+            ));
+        }
+
+        let mut init_biases = self.init_biases.unwrap_or_else(|| Box::new(|_| 0.));
+        Ok(DenseLayer::new(
+            self.output_count,
+            self.input_count,
+            &mut init_weights,
+            &mut init_biases,
+            self.activation_fn,
+        ))
+    }
+}
+
+impl DenseLayer {
+    pub fn builder() -> DenseLayerBuilder { DenseLayerBuilder::new() }
+}
+
+/// How `NetworkBuilder` initializes the weights of every layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeightInit {
+    Constant(Weight),
+    Uniform { min: Weight, max: Weight },
+}
+
+/// Builds a `Network` layer by layer, wiring up the input counts of each layer automatically.
+///
+/// Unless overridden, the output layer uses the identity activation function with mean squared error as its cost
+/// function, all weights are drawn uniformly from [-1, 1], and biases start at 0.
+pub struct NetworkBuilder {
+    input_size: usize,
+    hidden_layers: Vec<(usize, &'static dyn ActivationFunction)>,
+    output_size: usize,
+    output_activation_fn: &'static dyn ActivationFunction,
+    cost_fn: &'static dyn CostFunction,
+    weight_init: WeightInit,
+    learning_rate: Weight,
+}
+
+impl Default for NetworkBuilder {
+    fn default() -> Self {
+        NetworkBuilder {
+            input_size: 0,
+            hidden_layers: Vec::new(),
+            output_size: 0,
+            output_activation_fn: &IDENTITY,
+            cost_fn: &MEAN_SQUARED_ERROR,
+            weight_init: WeightInit::Uniform { min: -1., max: 1. },
+            learning_rate: 0.01,
+        }
+    }
+}
+
+impl NetworkBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn input_size(mut self, input_size: usize) -> Self {
+        self.input_size = input_size;
+        self
+    }
+
+    /// Appends a hidden layer after any that have already been added.
+    pub fn hidden_layer(mut self, neuron_count: usize, activation_fn: &'static dyn ActivationFunction) -> Self {
+        self.hidden_layers.push((neuron_count, activation_fn));
+        self
+    }
+
+    pub fn output_size(mut self, output_size: usize) -> Self {
+        self.output_size = output_size;
+        self
+    }
+
+    pub fn output_activation(mut self, activation_fn: &'static dyn ActivationFunction) -> Self {
+        self.output_activation_fn = activation_fn;
+        self
+    }
+
+    pub fn cost_function(mut self, cost_fn: &'static dyn CostFunction) -> Self {
+        self.cost_fn = cost_fn;
+        self
+    }
+
+    pub fn weight_init(mut self, weight_init: WeightInit) -> Self {
+        self.weight_init = weight_init;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: Weight) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn build(self) -> Result<Network, BuildError> {
+        if self.input_size == 0 {
+            return Err(BuildError::ZeroInputCount);
+        }
+        if self.output_size == 0 {
+            return Err(BuildError::ZeroOutputCount);
+        }
+        if self.hidden_layers.is_empty() {
+            return Err(BuildError::NoHiddenLayers);
+        }
+        if let Some(hidden_layer_ix) = self
+            .hidden_layers
+            .iter()
+            .position(|&(neuron_count, _)| neuron_count == 0)
+        {
+            return Err(BuildError::ZeroNeuronCount { hidden_layer_ix });
+        }
+        if let WeightInit::Uniform { min, max } = self.weight_init {
+            if min.is_nan() || max.is_nan() || min >= max {
+                return Err(BuildError::EmptyWeightRange);
+            }
+        }
+
+        let mut rng = pcg::Pcg::default();
+        let weight_init = self.weight_init;
+        let mut init_weights = move |_: usize, _: usize| match weight_init {
+            WeightInit::Constant(weight) => weight,
+            WeightInit::Uniform { min, max } => rng.gen_range(min, max),
+        };
+
+        let mut hidden_layers = Vec::with_capacity(self.hidden_layers.len());
+        let mut input_count = self.input_size;
+        for &(neuron_count, activation_fn) in &self.hidden_layers {
+            hidden_layers.push(DenseLayer::new(
+                neuron_count,
+                input_count,
+                &mut init_weights,
+                &mut |_| 0.,
+                activation_fn,
+            ));
+            input_count = neuron_count;
+        }
+
+        let outputs = OutputLayer::new(
+            self.output_activation_fn,
+            self.cost_fn,
+            &mut init_weights,
+            input_count,
+            self.output_size,
+        );
+
+        Ok(Network {
+            hidden_layers,
+            outputs: Box::new(outputs),
+            learning_rate: self.learning_rate,
+        })
+    }
+}
+
+impl Network {
+    pub fn builder() -> NetworkBuilder { NetworkBuilder::new() }
+}
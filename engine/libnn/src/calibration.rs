@@ -0,0 +1,84 @@
+use crate::{log_sum_exp, predicted_class, Network, Weight};
+
+/// The range of temperatures `calibrate_temperature` searches
+const MIN_TEMPERATURE: f32 = 0.05;
+const MAX_TEMPERATURE: f32 = 20.;
+const CALIBRATION_ITERATIONS: usize = 50;
+
+/// `softmax(logits / temperature)`.  Temperatures above 1 flatten the distribution and temperatures below 1 sharpen
+/// it.
+pub fn softmax_with_temperature(logits: &[Weight], temperature: f32) -> Vec<Weight> {
+    let scaled: Vec<Weight> = logits.iter().map(|logit| logit / temperature).collect();
+    let log_total = log_sum_exp(&scaled);
+    scaled.iter().map(|logit| (logit - log_total).exp()).collect()
+}
+
+/// The average gap between confidence and accuracy, weighting each of `num_bins` equal-width confidence bins by how
+/// many predictions fall in it.  A perfectly calibrated model that is 80% confident is right 80% of the time.
+pub fn expected_calibration_error(probabilities: &[Vec<Weight>], labels: &[usize], num_bins: usize) -> Weight {
+    debug_assert_eq!(probabilities.len(), labels.len());
+    let mut bin_counts = vec![0usize; num_bins];
+    let mut bin_confidence = vec![0.; num_bins];
+    let mut bin_correct = vec![0.; num_bins];
+    for (probabilities, &label) in probabilities.iter().zip(labels.iter()) {
+        let predicted = predicted_class(probabilities);
+        let confidence = probabilities[predicted];
+        let bin_ix = ((confidence * num_bins as Weight) as usize).min(num_bins - 1);
+        bin_counts[bin_ix] += 1;
+        bin_confidence[bin_ix] += confidence;
+        if predicted == label {
+            bin_correct[bin_ix] += 1.;
+        }
+    }
+
+    let total = probabilities.len() as Weight;
+    (0..num_bins)
+        .filter(|&bin_ix| bin_counts[bin_ix] > 0)
+        .map(|bin_ix| (bin_confidence[bin_ix] - bin_correct[bin_ix]).abs() / total)
+        .sum()
+}
+
+impl Network {
+    /// Runs the network on `inputs` and returns the softmax of its outputs divided by `temperature`.  The outputs are
+    /// treated as logits, so the output layer should use the identity activation.
+    pub fn predict_with_temperature(&mut self, inputs: &[Weight], temperature: f32) -> Vec<Weight> {
+        softmax_with_temperature(self.compute(inputs), temperature)
+    }
+
+    /// Finds the temperature that maximizes the likelihood of the validation labels, which are the index of the
+    /// largest value in each of `expected`.  Weights are left unchanged.
+    ///
+    /// The negative log likelihood is convex in `1 / temperature`, so this bisects on the sign of its derivative.
+    pub fn calibrate_temperature(&mut self, examples: &[Vec<Weight>], expected: &[Vec<Weight>]) -> f32 {
+        debug_assert_eq!(examples.len(), expected.len());
+        let logits: Vec<Vec<Weight>> = examples
+            .iter()
+            .map(|example| self.compute(example).to_owned())
+            .collect();
+        let labels: Vec<usize> = expected.iter().map(|expected| predicted_class(expected)).collect();
+
+        // Derivative of the total negative log likelihood with respect to `1 / temperature`
+        let nll_derivative = |inverse_temperature: f32| -> Weight {
+            logits
+                .iter()
+                .zip(labels.iter())
+                .map(|(logits, &label)| {
+                    let probabilities = softmax_with_temperature(logits, 1. / inverse_temperature);
+                    let expected_logit: Weight = probabilities.iter().zip(logits.iter()).map(|(p, z)| p * z).sum();
+                    expected_logit - logits[label]
+                })
+                .sum()
+        };
+
+        let (mut low, mut high) = (1. / MAX_TEMPERATURE, 1. / MIN_TEMPERATURE);
+        for _ in 0..CALIBRATION_ITERATIONS {
+            let mid = (low + high) / 2.;
+            if nll_derivative(mid) < 0. {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        2. / (low + high)
+    }
+}
@@ -0,0 +1,104 @@
+use std::{fs::File, io, io::BufWriter, path::PathBuf};
+
+use crate::{LrScheduler, Network, Weight};
+
+/// Hooks into `Trainer::fit`.  Every method has a no-op default so callbacks only need to implement the events they
+/// care about.
+pub trait Callback {
+    /// Called before each epoch.  The network can be modified here, for example to change its learning rate.
+    fn on_epoch_start(&mut self, _epoch: usize, _network: &mut Network) {}
+
+    /// Called after each epoch with the mean training loss and, if a validation set was given, the mean validation
+    /// loss.
+    fn on_epoch_end(&mut self, _epoch: usize, _train_loss: Weight, _val_loss: Option<Weight>, _network: &Network) {}
+
+    /// Called after each weight update with the mean loss of the examples that went into it.
+    fn on_batch_end(&mut self, _batch: usize, _loss: Weight) {}
+
+    fn on_training_end(&mut self, _network: &Network) {}
+}
+
+/// Saves the network's weights to `path` every time the validation loss reaches a new low.  Without a validation set,
+/// the training loss is used instead.
+pub struct ModelCheckpointCallback {
+    pub path: PathBuf,
+    pub best_loss: Option<Weight>,
+    /// The first error hit while writing a checkpoint, if any.  No further checkpoints are attempted after an error.
+    pub error: Option<io::Error>,
+}
+
+impl ModelCheckpointCallback {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ModelCheckpointCallback {
+            path: path.into(),
+            best_loss: None,
+            error: None,
+        }
+    }
+
+    fn save(&self, network: &Network) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        network.save_weights(&mut writer)?;
+        io::Write::flush(&mut writer)
+    }
+}
+
+impl Callback for ModelCheckpointCallback {
+    fn on_epoch_end(&mut self, _epoch: usize, train_loss: Weight, val_loss: Option<Weight>, network: &Network) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let loss = val_loss.unwrap_or(train_loss);
+        if matches!(self.best_loss, Some(best_loss) if loss >= best_loss) {
+            return;
+        }
+        self.best_loss = Some(loss);
+        if let Err(err) = self.save(network) {
+            self.error = Some(err);
+        }
+    }
+}
+
+/// Sets the network's learning rate from `scheduler` at the start of every epoch, treating each epoch as one step.
+pub struct LrScheduleCallback {
+    pub scheduler: Box<dyn LrScheduler>,
+}
+
+impl LrScheduleCallback {
+    pub fn new(scheduler: Box<dyn LrScheduler>) -> Self { LrScheduleCallback { scheduler } }
+}
+
+impl Callback for LrScheduleCallback {
+    fn on_epoch_start(&mut self, epoch: usize, network: &mut Network) {
+        network.learning_rate = self.scheduler.get_learning_rate(epoch);
+    }
+}
+
+/// Prints the losses at the end of every epoch.
+pub struct ProgressCallback {
+    pub epochs: usize,
+}
+
+impl ProgressCallback {
+    pub fn new(epochs: usize) -> Self { ProgressCallback { epochs } }
+}
+
+impl Callback for ProgressCallback {
+    fn on_epoch_end(&mut self, epoch: usize, train_loss: Weight, val_loss: Option<Weight>, _network: &Network) {
+        match val_loss {
+            Some(val_loss) => println!(
+                "Epoch {}/{}: train loss {}, val loss {}",
+                epoch + 1,
+                self.epochs,
+                train_loss,
+                val_loss
+            ),
+            None => println!("Epoch {}/{}: train loss {}", epoch + 1, self.epochs, train_loss),
+        }
+    }
+
+    fn on_training_end(&mut self, _network: &Network) {
+        println!("Training finished");
+    }
+}
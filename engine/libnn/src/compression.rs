@@ -0,0 +1,294 @@
+use rand::Rng;
+
+use crate::{ActivationFunction, DenseLayer, Weight};
+
+/// Jacobi sweeps stop once every pair of columns is orthogonal to within this, relative to their norms
+const JACOBI_TOLERANCE: f64 = 1e-12;
+const MAX_JACOBI_SWEEPS: usize = 60;
+
+/// The factors of a singular value decomposition: `U` with one row per row of the matrix, the singular values in
+/// descending order, and `V^T` with one column per column of the matrix
+pub type SvdFactors = (Vec<Vec<Weight>>, Vec<Weight>, Vec<Vec<Weight>>);
+
+fn transpose(matrix: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let col_count = matrix.first().map_or(0, |row| row.len());
+    (0..col_count)
+        .map(|col_ix| matrix.iter().map(|row| row[col_ix]).collect())
+        .collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 { a.iter().zip(b.iter()).map(|(a, b)| a * b).sum() }
+
+/// One-sided Jacobi SVD of a matrix with at least as many rows as columns, given as its columns.  Returns the left
+/// singular vectors and the right singular vectors as columns, along with the singular values, all unsorted.
+fn jacobi_svd(mut columns: Vec<Vec<f64>>) -> (Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>) {
+    let col_count = columns.len();
+    let mut right: Vec<Vec<f64>> = (0..col_count)
+        .map(|ix| (0..col_count).map(|jx| if ix == jx { 1. } else { 0. }).collect())
+        .collect();
+
+    // Rotate pairs of columns until they're all orthogonal.  The rotations are collected in `right`.
+    let rotate = |vectors: &mut [Vec<f64>], p: usize, q: usize, c: f64, s: f64| {
+        for row_ix in 0..vectors[p].len() {
+            let (x, y) = (vectors[p][row_ix], vectors[q][row_ix]);
+            vectors[p][row_ix] = c * x - s * y;
+            vectors[q][row_ix] = s * x + c * y;
+        }
+    };
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let mut rotated = false;
+        for p in 0..col_count {
+            for q in (p + 1)..col_count {
+                let alpha = dot(&columns[p], &columns[p]);
+                let beta = dot(&columns[q], &columns[q]);
+                let gamma = dot(&columns[p], &columns[q]);
+                if gamma.abs() <= JACOBI_TOLERANCE * (alpha * beta).sqrt() || gamma == 0. {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2. * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1. + zeta * zeta).sqrt());
+                let c = 1. / (1. + t * t).sqrt();
+                let s = c * t;
+                rotate(&mut columns, p, q, c, s);
+                rotate(&mut right, p, q, c, s);
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let singular_values: Vec<f64> = columns.iter().map(|column| dot(column, column).sqrt()).collect();
+    let left = columns
+        .into_iter()
+        .zip(singular_values.iter())
+        .map(|(column, &sigma)| {
+            if sigma > 0. {
+                column.iter().map(|val| val / sigma).collect()
+            } else {
+                vec![0.; column.len()]
+            }
+        })
+        .collect();
+    (left, singular_values, right)
+}
+
+/// Thin singular value decomposition, `matrix = U * diag(S) * V^T`, computed exactly with one-sided Jacobi rotations
+/// in double precision.  There are `min(rows, columns)` singular values.
+pub fn svd(matrix: &[Vec<Weight>]) -> SvdFactors {
+    let matrix: Vec<Vec<f64>> = matrix
+        .iter()
+        .map(|row| row.iter().map(|&val| val as f64).collect())
+        .collect();
+    let row_count = matrix.len();
+    let col_count = matrix.first().map_or(0, |row| row.len());
+
+    // Jacobi needs a tall matrix; for a wide one, decompose its transpose and swap the roles of U and V
+    let (left, singular_values, right) = if row_count >= col_count {
+        jacobi_svd(transpose(&matrix))
+    } else {
+        let (left, singular_values, right) = jacobi_svd(matrix);
+        (right, singular_values, left)
+    };
+
+    let mut order: Vec<usize> = (0..singular_values.len()).collect();
+    order.sort_by(|&a, &b| singular_values[b].total_cmp(&singular_values[a]));
+    let u = (0..row_count)
+        .map(|row_ix| order.iter().map(|&ix| left[ix][row_ix] as Weight).collect())
+        .collect();
+    let s = order.iter().map(|&ix| singular_values[ix] as Weight).collect();
+    let vt = order
+        .iter()
+        .map(|&ix| right[ix].iter().map(|&val| val as Weight).collect())
+        .collect();
+    (u, s, vt)
+}
+
+/// Replaces `vectors` with an orthonormal basis for their span using modified Gram-Schmidt.  Vectors that are
+/// (numerically) dependent on the earlier ones are dropped.
+fn orthonormalize(vectors: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let mut basis: Vec<Vec<f64>> = Vec::with_capacity(vectors.len());
+    for mut vector in vectors {
+        let original_norm = dot(&vector, &vector).sqrt();
+        for basis_vector in &basis {
+            let projection = dot(&vector, basis_vector);
+            for (val, &basis_val) in vector.iter_mut().zip(basis_vector.iter()) {
+                *val -= projection * basis_val;
+            }
+        }
+        let norm = dot(&vector, &vector).sqrt();
+        if norm > 1e-10 * original_norm && norm > 0. {
+            basis.push(vector.iter().map(|val| val / norm).collect());
+        }
+    }
+    basis
+}
+
+/// `matrix * columns[i]` for each of `columns`, where `matrix` has one row per output
+fn mul_columns(matrix: &[Vec<f64>], columns: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    columns
+        .iter()
+        .map(|column| matrix.iter().map(|row| dot(row, column)).collect())
+        .collect()
+}
+
+/// Approximates the top `rank` singular values and vectors of `matrix` without decomposing the whole thing (Halko,
+/// Martinsson & Tropp, 2011), returning the same factors as `svd` truncated to `rank`.
+///
+/// The matrix is multiplied by `rank + n_oversampling` random vectors to sample its range, and each of the
+/// `n_power_iter` power iterations multiplies by `matrix * matrix^T` again to sharpen the sample towards the leading
+/// singular vectors, which matters when the singular values decay slowly.  Only the small matrix projected onto that
+/// range is decomposed exactly.  The random vectors come from a fixed seed, so results are reproducible.
+pub fn randomized_svd(matrix: &[Vec<Weight>], rank: usize, n_oversampling: usize, n_power_iter: usize) -> SvdFactors {
+    let matrix: Vec<Vec<f64>> = matrix
+        .iter()
+        .map(|row| row.iter().map(|&val| val as f64).collect())
+        .collect();
+    let col_count = matrix.first().map_or(0, |row| row.len());
+    let matrix_t = transpose(&matrix);
+    let sample_count = (rank + n_oversampling).min(matrix.len()).min(col_count);
+
+    let mut rng = pcg::Pcg::default();
+    let test_vectors: Vec<Vec<f64>> = (0..sample_count)
+        .map(|_| (0..col_count).map(|_| rng.gen_range(-1., 1.)).collect())
+        .collect();
+    let mut range = orthonormalize(mul_columns(&matrix, &test_vectors));
+    for _ in 0..n_power_iter {
+        let co_range = orthonormalize(mul_columns(&matrix_t, &range));
+        range = orthonormalize(mul_columns(&matrix, &co_range));
+    }
+
+    // `range` holds the columns of Q, so `Q^T * matrix` has one row per column of Q
+    let projected: Vec<Vec<Weight>> = mul_columns(&matrix_t, &range)
+        .iter()
+        .map(|row| row.iter().map(|&val| val as Weight).collect())
+        .collect();
+    let (small_u, s, vt) = svd(&projected);
+
+    let rank = rank.min(s.len());
+    let u = (0..matrix.len())
+        .map(|row_ix| {
+            (0..rank)
+                .map(|ix| {
+                    range
+                        .iter()
+                        .zip(small_u.iter())
+                        .map(|(q, small_row)| q[row_ix] * small_row[ix] as f64)
+                        .sum::<f64>() as Weight
+                })
+                .collect()
+        })
+        .collect();
+    (u, s[..rank].to_vec(), vt[..rank].to_vec())
+}
+
+/// A dense layer whose weight matrix is stored as the product of two thinner ones, `second * first`, so that an
+/// `n x m` layer of rank `r` holds `r * (n + m)` weights instead of `n * m`.  Built with
+/// `DenseLayer::compress_weights`.
+pub struct FactoredDenseLayer {
+    /// `sqrt(S) * V^T`, with one row per retained singular value
+    pub first: Vec<Vec<Weight>>,
+    /// `U * sqrt(S)`, with one row per neuron
+    pub second: Vec<Vec<Weight>>,
+    pub biases: Vec<Weight>,
+    pub activation_fn: &'static dyn ActivationFunction,
+    /// Output of multiplying the inputs by `first`
+    pub hidden: Vec<Weight>,
+    pub outputs_before_activation: Vec<Weight>,
+    pub outputs: Vec<Weight>,
+}
+
+impl FactoredDenseLayer {
+    pub fn rank(&self) -> usize { self.first.len() }
+
+    pub fn parameter_count(&self) -> usize {
+        self.first
+            .iter()
+            .chain(self.second.iter())
+            .map(|row| row.len())
+            .sum::<usize>()
+            + self.biases.len()
+    }
+
+    /// Multiplies the factors back out into a full weight matrix with one row per neuron.
+    pub fn weights(&self) -> Vec<Vec<Weight>> {
+        let input_count = self.first.first().map_or(0, |row| row.len());
+        self.second
+            .iter()
+            .map(|second_row| {
+                (0..input_count)
+                    .map(|input_ix| {
+                        second_row
+                            .iter()
+                            .zip(self.first.iter())
+                            .map(|(&factor, first_row)| factor * first_row[input_ix])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn forward_propagate(&mut self, inputs: &[Weight]) {
+        for (hidden, row) in self.hidden.iter_mut().zip(self.first.iter()) {
+            *hidden = row
+                .iter()
+                .zip(inputs.iter())
+                .map(|(weight, input)| weight * input)
+                .sum();
+        }
+        for (neuron_ix, (output, row)) in self
+            .outputs_before_activation
+            .iter_mut()
+            .zip(self.second.iter())
+            .enumerate()
+        {
+            let bias = self.biases.get(neuron_ix).copied().unwrap_or(0.);
+            *output = row
+                .iter()
+                .zip(self.hidden.iter())
+                .map(|(weight, hidden)| weight * hidden)
+                .sum::<Weight>()
+                + bias;
+        }
+        self.activation_fn
+            .apply_batch(&mut self.outputs, &self.outputs_before_activation);
+    }
+}
+
+impl DenseLayer {
+    /// Approximates the weights with their top `rank` singular values, which by the Eckart-Young theorem is the best
+    /// approximation of that rank in the Frobenius norm.  A rank of `min(neurons, inputs)` or more is lossless.
+    pub fn compress_weights(&self, rank: usize) -> FactoredDenseLayer {
+        let (u, s, vt) = svd(&self.weights);
+        let rank = rank.min(s.len());
+        let scale = self.spectral_norm_scale();
+        let roots: Vec<Weight> = s[..rank].iter().map(|sigma| (sigma * scale).sqrt()).collect();
+        let first = vt[..rank]
+            .iter()
+            .zip(roots.iter())
+            .map(|(row, root)| row.iter().map(|val| val * root).collect())
+            .collect();
+        let second = u
+            .iter()
+            .map(|row| {
+                row[..rank]
+                    .iter()
+                    .zip(roots.iter())
+                    .map(|(val, root)| val * root)
+                    .collect()
+            })
+            .collect();
+
+        FactoredDenseLayer {
+            first,
+            second,
+            biases: if self.use_bias { self.biases.clone() } else { Vec::new() },
+            activation_fn: self.activation_fn,
+            hidden: vec![0.; rank],
+            outputs_before_activation: vec![0.; self.weights.len()],
+            outputs: vec![0.; self.weights.len()],
+        }
+    }
+}
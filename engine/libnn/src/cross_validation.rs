@@ -0,0 +1,75 @@
+use std::ops::Range;
+
+use crate::{Network, Weight};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidationResult {
+    /// Mean validation loss per example of the network trained for each fold
+    pub fold_losses: Vec<Weight>,
+    pub mean_loss: Weight,
+    /// Population standard deviation of `fold_losses`
+    pub std_loss: Weight,
+}
+
+/// Splits `example_count` examples into `k` contiguous folds whose sizes differ by at most one.
+pub fn k_fold_splits(example_count: usize, k: usize) -> Vec<Range<usize>> {
+    assert!(k >= 2, "Cross validation needs at least 2 folds");
+    assert!(example_count >= k, "Need at least one example per fold");
+
+    let base_size = example_count / k;
+    let remainder = example_count % k;
+    let mut start = 0;
+    (0..k)
+        .map(|fold_ix| {
+            let size = base_size + if fold_ix < remainder { 1 } else { 0 };
+            let fold = start..start + size;
+            start += size;
+            fold
+        })
+        .collect()
+}
+
+/// Trains a fresh network from `builder` on every fold but one for each of `k` folds, evaluating it on the fold that
+/// was held out.
+///
+/// Folds are contiguous, so examples should be shuffled beforehand if they're ordered.
+pub fn k_fold_cross_validate(
+    builder: impl Fn() -> Network,
+    examples: &[Vec<Weight>],
+    expected: &[Vec<Weight>],
+    k: usize,
+    epochs: usize,
+    learning_rate: Weight,
+) -> CrossValidationResult {
+    assert_eq!(examples.len(), expected.len());
+
+    let fold_losses: Vec<Weight> = k_fold_splits(examples.len(), k)
+        .into_iter()
+        .map(|fold| {
+            let mut network = builder();
+            for _ in 0..epochs {
+                for (ix, (example, expected)) in examples.iter().zip(expected.iter()).enumerate() {
+                    if !fold.contains(&ix) {
+                        network.train_one_example(example, expected, learning_rate);
+                    }
+                }
+            }
+
+            network
+                .evaluate(&examples[fold.clone()], &expected[fold])
+                .mean_loss_per_example
+        })
+        .collect();
+
+    let mean_loss = fold_losses.iter().sum::<Weight>() / k as Weight;
+    let variance = fold_losses
+        .iter()
+        .map(|loss| (loss - mean_loss).powi(2))
+        .sum::<Weight>()
+        / k as Weight;
+    CrossValidationResult {
+        fold_losses,
+        mean_loss,
+        std_loss: variance.sqrt(),
+    }
+}
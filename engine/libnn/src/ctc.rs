@@ -0,0 +1,110 @@
+use crate::{log_sum_exp, Weight};
+
+/// Connectionist temporal classification loss, for training sequence labelers when the alignment between the inputs
+/// and the shorter sequence of target labels is unknown.
+///
+/// The loss is the negative log of the total probability of every alignment that collapses to the targets once
+/// repeated labels are merged and blanks are removed.
+pub struct CtcLoss;
+
+impl CtcLoss {
+    /// `log_probs` holds the log probability of every label, including `blank_label`, at each time step.  Returns the
+    /// loss along with its gradient with respect to each of `log_probs`.
+    ///
+    /// If no alignment is possible, for example because there are fewer time steps than targets, the loss is infinite
+    /// and the gradients are all 0.
+    pub fn compute(log_probs: &[Vec<Weight>], targets: &[usize], blank_label: usize) -> (Weight, Vec<Vec<Weight>>) {
+        let step_count = log_probs.len();
+        let label_count = log_probs.first().map(Vec::len).unwrap_or(0);
+        let mut gradients = vec![vec![0.; label_count]; step_count];
+        if step_count == 0 {
+            let loss = if targets.is_empty() { 0. } else { Weight::INFINITY };
+            return (loss, gradients);
+        }
+        debug_assert!(targets
+            .iter()
+            .all(|&target| target != blank_label && target < label_count));
+
+        // Targets with a blank before, between, and after every label
+        let mut extended = Vec::with_capacity(2 * targets.len() + 1);
+        extended.push(blank_label);
+        for &target in targets {
+            extended.push(target);
+            extended.push(blank_label);
+        }
+        let state_count = extended.len();
+        // The transition skipping over a blank is only allowed between two different labels
+        let can_skip = |state_ix: usize| {
+            state_ix >= 2 && extended[state_ix] != blank_label && extended[state_ix] != extended[state_ix - 2]
+        };
+
+        let mut alphas = vec![vec![Weight::NEG_INFINITY; state_count]; step_count];
+        alphas[0][0] = log_probs[0][blank_label];
+        if state_count > 1 {
+            alphas[0][1] = log_probs[0][extended[1]];
+        }
+        for t in 1..step_count {
+            for state_ix in 0..state_count {
+                let prev = &alphas[t - 1];
+                let mut incoming = vec![prev[state_ix]];
+                if state_ix >= 1 {
+                    incoming.push(prev[state_ix - 1]);
+                }
+                if can_skip(state_ix) {
+                    incoming.push(prev[state_ix - 2]);
+                }
+                alphas[t][state_ix] = log_sum_exp(&incoming) + log_probs[t][extended[state_ix]];
+            }
+        }
+
+        let mut betas = vec![vec![Weight::NEG_INFINITY; state_count]; step_count];
+        let last_step = step_count - 1;
+        betas[last_step][state_count - 1] = log_probs[last_step][blank_label];
+        if state_count > 1 {
+            betas[last_step][state_count - 2] = log_probs[last_step][extended[state_count - 2]];
+        }
+        for t in (0..last_step).rev() {
+            for state_ix in 0..state_count {
+                let next = &betas[t + 1];
+                let mut outgoing = vec![next[state_ix]];
+                if state_ix + 1 < state_count {
+                    outgoing.push(next[state_ix + 1]);
+                }
+                if state_ix + 2 < state_count && can_skip(state_ix + 2) {
+                    outgoing.push(next[state_ix + 2]);
+                }
+                betas[t][state_ix] = log_sum_exp(&outgoing) + log_probs[t][extended[state_ix]];
+            }
+        }
+
+        let final_states = if state_count > 1 {
+            vec![alphas[last_step][state_count - 1], alphas[last_step][state_count - 2]]
+        } else {
+            vec![alphas[last_step][0]]
+        };
+        let log_likelihood = log_sum_exp(&final_states);
+        if log_likelihood == Weight::NEG_INFINITY {
+            return (Weight::INFINITY, gradients);
+        }
+
+        // alpha * beta counts the log probability at step t twice, once from each direction
+        let mut state_log_probs = Vec::with_capacity(state_count);
+        for t in 0..step_count {
+            for (label_ix, gradient) in gradients[t].iter_mut().enumerate() {
+                state_log_probs.clear();
+                for state_ix in 0..state_count {
+                    if extended[state_ix] == label_ix {
+                        state_log_probs.push(alphas[t][state_ix] + betas[t][state_ix]);
+                    }
+                }
+                let log_occupancy = log_sum_exp(&state_log_probs);
+                if log_occupancy == Weight::NEG_INFINITY {
+                    continue;
+                }
+                *gradient = -(log_occupancy - log_probs[t][label_ix] - log_likelihood).exp();
+            }
+        }
+
+        (-log_likelihood, gradients)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{softmax_with_temperature, ActivationFunction, Weight};
+
+/// Differentiable architecture search (Liu et al., 2019) over activation functions.  The output for each input is the
+/// average of every candidate's output weighted by `softmax(alphas)`, which makes the choice of activation
+/// continuous so that `alphas` can be trained by gradient descent along with the rest of the network.  Once trained,
+/// `discretize` picks the candidate with the highest weight.
+pub struct DartsActivationLayer {
+    pub alphas: Vec<Weight>,
+    pub candidates: Vec<&'static dyn ActivationFunction>,
+}
+
+impl DartsActivationLayer {
+    /// Starts with every candidate weighted equally.
+    pub fn new(candidates: Vec<&'static dyn ActivationFunction>) -> Self {
+        assert!(
+            !candidates.is_empty(),
+            "Need at least one candidate activation function"
+        );
+        DartsActivationLayer {
+            alphas: vec![0.; candidates.len()],
+            candidates,
+        }
+    }
+
+    /// `softmax(alphas)`, the weight given to each candidate
+    pub fn mixing_weights(&self) -> Vec<Weight> { softmax_with_temperature(&self.alphas, 1.) }
+
+    pub fn forward_propagate(&self, inputs: &[Weight]) -> Vec<Weight> {
+        let mixing_weights = self.mixing_weights();
+        inputs
+            .iter()
+            .map(|&x| {
+                self.candidates
+                    .iter()
+                    .zip(mixing_weights.iter())
+                    .map(|(candidate, weight)| weight * candidate.get_output(x))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Fills `dst` with the gradients with respect to `inputs` given `output_gradients`, which like the neuron
+    /// gradients of other layers are negated gradients of the cost.  Returns the gradients for `alphas`, in the same
+    /// form, to be passed to `update_alphas`.
+    pub fn compute_gradients(&self, inputs: &[Weight], output_gradients: &[Weight], dst: &mut [Weight]) -> Vec<Weight> {
+        debug_assert_eq!(inputs.len(), output_gradients.len());
+        debug_assert_eq!(inputs.len(), dst.len());
+
+        let mixing_weights = self.mixing_weights();
+        let mut alpha_gradients = vec![0.; self.alphas.len()];
+        let mut candidate_outputs = vec![0.; self.candidates.len()];
+        for ((input_gradient, &x), &output_gradient) in dst.iter_mut().zip(inputs.iter()).zip(output_gradients.iter()) {
+            let mut output = 0.;
+            let mut derivative = 0.;
+            for ((candidate_output, candidate), &weight) in candidate_outputs
+                .iter_mut()
+                .zip(self.candidates.iter())
+                .zip(mixing_weights.iter())
+            {
+                *candidate_output = candidate.get_output(x);
+                output += weight * *candidate_output;
+                derivative += weight * candidate.derivative(x);
+            }
+            *input_gradient = output_gradient * derivative;
+
+            // The derivative of softmax means each alpha pulls the output towards its own candidate's output
+            for ((alpha_gradient, &weight), &candidate_output) in alpha_gradients
+                .iter_mut()
+                .zip(mixing_weights.iter())
+                .zip(candidate_outputs.iter())
+            {
+                *alpha_gradient += output_gradient * weight * (candidate_output - output);
+            }
+        }
+        alpha_gradients
+    }
+
+    pub fn update_alphas(&mut self, learning_rate: Weight, gradients: &[Weight]) {
+        for (alpha, &gradient) in self.alphas.iter_mut().zip(gradients.iter()) {
+            *alpha += learning_rate * gradient;
+        }
+    }
+
+    /// The candidate with the highest alpha, to use in place of this layer after the search
+    pub fn discretize(&self) -> &'static dyn ActivationFunction {
+        let best_ix = (0..self.alphas.len())
+            .max_by(|&a, &b| self.alphas[a].total_cmp(&self.alphas[b]))
+            .unwrap();
+        self.candidates[best_ix]
+    }
+}
@@ -0,0 +1,184 @@
+use crate::Weight;
+
+/// A training sequence together with its expected output
+pub type LabeledSequence = (Vec<Weight>, Vec<Weight>);
+
+/// Groups sequences by length so that every mini-batch is made of sequences of similar length, minimizing how much
+/// padding is needed to batch them together.
+///
+/// `bucket_edges` must be strictly increasing.  Bucket 0 holds sequences shorter than `bucket_edges[0]`, bucket `i`
+/// holds lengths in `bucket_edges[i - 1]..bucket_edges[i]`, and the last bucket holds everything at least as long as
+/// the last edge.
+pub struct BucketedDataLoader {
+    pub bucket_edges: Vec<usize>,
+    pub buckets: Vec<Vec<LabeledSequence>>,
+    /// How many sequences have been yielded from each bucket this epoch
+    pub bucket_positions: Vec<usize>,
+    /// The bucket the next batch will be drawn from
+    pub next_bucket_ix: usize,
+}
+
+impl BucketedDataLoader {
+    pub fn new(bucket_edges: Vec<usize>, data: Vec<LabeledSequence>) -> Self {
+        assert!(
+            bucket_edges.windows(2).all(|pair| pair[0] < pair[1]),
+            "Bucket edges must be strictly increasing"
+        );
+
+        let mut buckets = vec![Vec::new(); bucket_edges.len() + 1];
+        for item in data {
+            let bucket_ix = bucket_ix_for_len(&bucket_edges, item.0.len());
+            buckets[bucket_ix].push(item);
+        }
+
+        BucketedDataLoader {
+            bucket_positions: vec![0; buckets.len()],
+            bucket_edges,
+            buckets,
+            next_bucket_ix: 0,
+        }
+    }
+
+    pub fn bucket_ix_for_len(&self, len: usize) -> usize { bucket_ix_for_len(&self.bucket_edges, len) }
+
+    /// Returns up to `batch_size` sequences, all from the same bucket.  Buckets take turns providing batches, and
+    /// the last batch from each bucket may be smaller than `batch_size`.  Once every sequence has been yielded this
+    /// returns an empty batch until `reset` is called.
+    pub fn next_batch(&mut self, batch_size: usize) -> Vec<LabeledSequence> {
+        assert!(batch_size > 0, "Batch size must be at least 1");
+
+        for _ in 0..self.buckets.len() {
+            let bucket_ix = self.next_bucket_ix;
+            self.next_bucket_ix = (self.next_bucket_ix + 1) % self.buckets.len();
+
+            let bucket = &self.buckets[bucket_ix];
+            let start = self.bucket_positions[bucket_ix];
+            if start < bucket.len() {
+                let end = (start + batch_size).min(bucket.len());
+                self.bucket_positions[bucket_ix] = end;
+                return bucket[start..end].to_owned();
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Starts a new epoch in which every sequence will be yielded again.
+    pub fn reset(&mut self) {
+        self.bucket_positions.fill(0);
+        self.next_bucket_ix = 0;
+    }
+
+    pub fn len(&self) -> usize { self.buckets.iter().map(Vec::len).sum() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+fn bucket_ix_for_len(bucket_edges: &[usize], len: usize) -> usize {
+    bucket_edges
+        .iter()
+        .position(|&edge| len < edge)
+        .unwrap_or(bucket_edges.len())
+}
+
+/// How much of the data `CurriculumDataLoader` makes available as training progresses.  Both schedules start with the
+/// easiest `initial_fraction` of the data and include all of it from `full_at_epoch` on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CurriculumSchedule {
+    /// The fraction grows by the same amount every epoch
+    Linear {
+        initial_fraction: f32,
+        full_at_epoch: usize,
+    },
+    /// The fraction grows with the square root of the epoch, adding harder examples quickly at first and more slowly
+    /// once most of the data is included
+    Root {
+        initial_fraction: f32,
+        full_at_epoch: usize,
+    },
+}
+
+impl CurriculumSchedule {
+    /// Returns the fraction of the data, from easiest to hardest, to train on during `epoch`.
+    pub fn fraction(&self, epoch: usize) -> f32 {
+        let (initial_fraction, full_at_epoch, progress_fn): (f32, usize, fn(f32) -> f32) = match *self {
+            CurriculumSchedule::Linear {
+                initial_fraction,
+                full_at_epoch,
+            } => (initial_fraction, full_at_epoch, |progress| progress),
+            CurriculumSchedule::Root {
+                initial_fraction,
+                full_at_epoch,
+            } => (initial_fraction, full_at_epoch, f32::sqrt),
+        };
+        if epoch >= full_at_epoch {
+            return 1.;
+        }
+        let progress = progress_fn(epoch as f32 / full_at_epoch as f32);
+        initial_fraction + (1. - initial_fraction) * progress
+    }
+}
+
+/// Scores how hard a sequence is to learn; lower scores are trained on first
+pub type DifficultyFn = Box<dyn Fn(&[Weight]) -> f32>;
+
+/// Yields the easiest sequences first, as scored by `difficulty_fn`, and includes harder ones as `set_epoch` moves
+/// through `schedule`.
+pub struct CurriculumDataLoader {
+    /// Sorted from easiest to hardest
+    pub data: Vec<LabeledSequence>,
+    pub difficulty_fn: DifficultyFn,
+    pub schedule: CurriculumSchedule,
+    /// How many of the easiest sequences are included in the current epoch
+    pub available_count: usize,
+    /// How many sequences have been yielded this epoch
+    pub position: usize,
+}
+
+impl CurriculumDataLoader {
+    /// Sorts `data` by the difficulty of each input sequence and starts at epoch 0.
+    pub fn new(data: Vec<LabeledSequence>, difficulty_fn: DifficultyFn, schedule: CurriculumSchedule) -> Self {
+        let mut scored: Vec<(f32, LabeledSequence)> =
+            data.into_iter().map(|item| (difficulty_fn(&item.0), item)).collect();
+        scored.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut loader = CurriculumDataLoader {
+            data: scored.into_iter().map(|(_, item)| item).collect(),
+            difficulty_fn,
+            schedule,
+            available_count: 0,
+            position: 0,
+        };
+        loader.set_epoch(0);
+        loader
+    }
+
+    /// Moves the curriculum to `epoch` and starts yielding sequences from the beginning again.  At least one sequence
+    /// is always available.
+    pub fn set_epoch(&mut self, epoch: usize) {
+        let fraction = self.schedule.fraction(epoch).clamp(0., 1.);
+        let count = (fraction * self.data.len() as f32).ceil() as usize;
+        self.available_count = count.max(1).min(self.data.len());
+        self.position = 0;
+    }
+
+    /// The sequences included in the current epoch
+    pub fn available(&self) -> &[LabeledSequence] { &self.data[..self.available_count] }
+
+    /// Returns up to `batch_size` of the sequences available this epoch, or an empty batch once they have all been
+    /// yielded.
+    pub fn next_batch(&mut self, batch_size: usize) -> Vec<LabeledSequence> {
+        assert!(batch_size > 0, "Batch size must be at least 1");
+        let start = self.position;
+        let end = (start + batch_size).min(self.available_count);
+        self.position = end;
+        self.data[start..end].to_owned()
+    }
+
+    /// Returns the difficulty score of `sequence`.
+    pub fn difficulty(&self, sequence: &[Weight]) -> f32 { (self.difficulty_fn)(sequence) }
+
+    pub fn len(&self) -> usize { self.data.len() }
+
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+}
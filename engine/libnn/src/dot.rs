@@ -0,0 +1,47 @@
+use std::fmt::Write;
+
+use crate::Network;
+
+impl Network {
+    /// Renders the network as a Graphviz DOT graph with a node for the inputs and for each layer.  Each edge is
+    /// labeled with the length of the vector passed along it.
+    pub fn to_dot(&self) -> String {
+        let input_count = match self.hidden_layers.first() {
+            Some(layer) => layer.weights.first().map(Vec::len).unwrap_or(0),
+            None => self.outputs.weights.first().map(Vec::len).unwrap_or(0),
+        };
+
+        let mut dot = String::from("digraph network {\n    rankdir=LR;\n    node [shape=box];\n");
+        writeln!(dot, "    input [label=\"Input\\n{}\", shape=ellipse];", input_count).unwrap();
+        let mut prev_node = "input".to_owned();
+        let mut prev_size = input_count;
+        for (layer_ix, layer) in self.hidden_layers.iter().enumerate() {
+            let node = format!("hidden_{}", layer_ix);
+            let bias = if layer.use_bias { "" } else { ", no bias" };
+            writeln!(
+                dot,
+                "    {} [label=\"Dense {}\\n{} units{}\"];",
+                node,
+                layer.activation_fn.name(),
+                layer.weights.len(),
+                bias
+            )
+            .unwrap();
+            writeln!(dot, "    {} -> {} [label=\"[{}]\"];", prev_node, node, prev_size).unwrap();
+            prev_node = node;
+            prev_size = layer.weights.len();
+        }
+
+        writeln!(
+            dot,
+            "    output [label=\"Output {}\\n{} units\\n{}\"];",
+            self.outputs.activation_fn.name(),
+            self.outputs.weights.len(),
+            self.outputs.cost_fn.name()
+        )
+        .unwrap();
+        writeln!(dot, "    {} -> output [label=\"[{}]\"];", prev_node, prev_size).unwrap();
+        dot.push_str("}\n");
+        dot
+    }
+}
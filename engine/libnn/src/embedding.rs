@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+
+use crate::{Network, Weight};
+
+/// Maps discrete tokens to trainable dense vectors, avoiding the need to one-hot encode them.
+#[derive(Clone)]
+pub struct EmbeddingLayer {
+    /// One row of `embedding_dim` weights for each token in the vocabulary
+    pub embeddings: Vec<Vec<Weight>>,
+    pub vocab_size: usize,
+    pub embedding_dim: usize,
+    /// Gradients accumulated for each row since the last call to `update_weights`
+    pub gradients: Vec<Vec<Weight>>,
+    /// Rows that have had gradients accumulated since the last call to `update_weights`.  Every other row's gradients
+    /// are all zero, so only these rows need updating.
+    pub dirty_rows: HashSet<usize>,
+}
+
+impl EmbeddingLayer {
+    pub fn new(vocab_size: usize, embedding_dim: usize, init_weights: &mut impl FnMut(usize, usize) -> Weight) -> Self {
+        let mut embeddings = vec![vec![0.; embedding_dim]; vocab_size];
+        for (token_ix, embedding) in embeddings.iter_mut().enumerate() {
+            for (dim_ix, weight) in embedding.iter_mut().enumerate() {
+                *weight = init_weights(token_ix, dim_ix);
+            }
+        }
+
+        EmbeddingLayer {
+            embeddings,
+            vocab_size,
+            embedding_dim,
+            gradients: vec![vec![0.; embedding_dim]; vocab_size],
+            dirty_rows: HashSet::new(),
+        }
+    }
+
+    pub fn lookup(&self, token_ix: usize) -> &[Weight] { &self.embeddings[token_ix] }
+
+    /// Adds `gradients`, the gradient of the cost with respect to the embedding for `token_ix` as it was fed into the
+    /// next layer, to that token's row.
+    pub fn accumulate_gradients(&mut self, token_ix: usize, gradients: &[Weight]) {
+        debug_assert_eq!(gradients.len(), self.embedding_dim);
+        self.dirty_rows.insert(token_ix);
+        for (accumulated, &gradient) in self.gradients[token_ix].iter_mut().zip(gradients.iter()) {
+            *accumulated += gradient;
+        }
+    }
+
+    /// Applies all accumulated gradients and clears them.  Only the rows in `dirty_rows` are visited, so the cost
+    /// depends on how many distinct tokens were seen rather than on the size of the vocabulary.
+    pub fn update_weights(&mut self, learning_rate: Weight) {
+        for token_ix in self.dirty_rows.drain() {
+            let embedding = &mut self.embeddings[token_ix];
+            let gradients = &mut self.gradients[token_ix];
+            for (weight, gradient) in embedding.iter_mut().zip(gradients.iter_mut()) {
+                *weight += learning_rate * *gradient;
+                *gradient = 0.;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BagOfWordsReduction {
+    Sum,
+    Mean,
+}
+
+/// Combines the embeddings of any number of tokens into a single fixed-size vector, ignoring their order.  Makes for a
+/// fast baseline to compare sequence models against.
+#[derive(Clone)]
+pub struct BagOfWordsLayer {
+    pub embedding: EmbeddingLayer,
+    pub reduction: BagOfWordsReduction,
+}
+
+impl BagOfWordsLayer {
+    pub fn new(embedding: EmbeddingLayer, reduction: BagOfWordsReduction) -> Self {
+        BagOfWordsLayer { embedding, reduction }
+    }
+
+    fn scale(&self, token_count: usize) -> Weight {
+        match self.reduction {
+            BagOfWordsReduction::Sum => 1.,
+            BagOfWordsReduction::Mean => 1. / token_count.max(1) as Weight,
+        }
+    }
+
+    /// Returns the sum or mean of the embeddings for `tokens`, or all zeros if `tokens` is empty.
+    pub fn forward_propagate(&self, tokens: &[usize]) -> Vec<Weight> {
+        let scale = self.scale(tokens.len());
+        let mut outputs = vec![0.; self.embedding.embedding_dim];
+        for &token_ix in tokens {
+            for (output, &weight) in outputs.iter_mut().zip(self.embedding.lookup(token_ix).iter()) {
+                *output += scale * weight;
+            }
+        }
+        outputs
+    }
+
+    /// Given the gradient of the cost with respect to the output for `tokens`, accumulates gradients for the embedding
+    /// of every token involved.  Tokens that appear more than once receive a share for each appearance.
+    pub fn accumulate_gradients(&mut self, tokens: &[usize], output_gradients: &[Weight]) {
+        let scale = self.scale(tokens.len());
+        let token_gradients: Vec<Weight> = output_gradients.iter().map(|gradient| scale * gradient).collect();
+        for &token_ix in tokens {
+            self.embedding.accumulate_gradients(token_ix, &token_gradients);
+        }
+    }
+
+    pub fn update_weights(&mut self, learning_rate: Weight) { self.embedding.update_weights(learning_rate); }
+}
+
+/// A `Network` whose output layer reuses the embedding matrix that feeds its inputs, as is common in language models.
+///
+/// `OutputLayer` stores a row of weights per output neuron just like `EmbeddingLayer` stores a row per token, so the
+/// output weights are exactly the embedding rows.  Gradients from both the output projection and the embedding lookup
+/// are summed into `embedding` and then copied into the output layer.
+pub struct TiedEmbeddingNetwork {
+    pub embedding: EmbeddingLayer,
+    pub network: Network,
+}
+
+impl Network {
+    pub fn with_tied_output_weights(mut self, embedding: &EmbeddingLayer) -> TiedEmbeddingNetwork {
+        let hidden_output_count = match self.hidden_layers.last() {
+            Some(layer) => layer.outputs.len(),
+            None => embedding.embedding_dim,
+        };
+        assert_eq!(
+            hidden_output_count, embedding.embedding_dim,
+            "Output layer inputs must match the embedding dimension"
+        );
+        assert_eq!(
+            self.outputs.outputs.len(),
+            embedding.vocab_size,
+            "Output layer must have one neuron per token"
+        );
+
+        self.outputs.weights = embedding.embeddings.clone();
+        TiedEmbeddingNetwork {
+            embedding: embedding.clone(),
+            network: self,
+        }
+    }
+}
+
+impl TiedEmbeddingNetwork {
+    /// Trains the network to produce `expected` given the embedding for `token_ix`, returning the average cost before
+    /// updating weights.
+    pub fn train_one_token(&mut self, token_ix: usize, expected: &[Weight], learning_rate: Weight) -> Weight {
+        let example = self.embedding.lookup(token_ix).to_owned();
+        self.network.compute_gradients(&example, expected);
+
+        let mut input_gradients = vec![0.; example.len()];
+        self.network.compute_input_gradients(&mut input_gradients);
+        self.embedding.accumulate_gradients(token_ix, &input_gradients);
+
+        let output_inputs = match self.network.hidden_layers.last() {
+            Some(layer) => layer.outputs.clone(),
+            None => example.clone(),
+        };
+        let mut output_weight_gradients = vec![0.; output_inputs.len()];
+        for (output_ix, &neuron_gradient) in self.network.outputs.neuron_gradients.iter().enumerate() {
+            for (gradient, &input) in output_weight_gradients.iter_mut().zip(output_inputs.iter()) {
+                *gradient = neuron_gradient * input;
+            }
+            self.embedding.accumulate_gradients(output_ix, &output_weight_gradients);
+        }
+
+        // The output layer's own update is discarded in favor of the combined one applied to the embedding
+        self.network.update_weights(&example, learning_rate);
+        self.embedding.update_weights(learning_rate);
+        self.network.outputs.weights.clone_from(&self.embedding.embeddings);
+
+        self.network.mean_cost()
+    }
+}
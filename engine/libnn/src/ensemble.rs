@@ -0,0 +1,60 @@
+use crate::{fit_model, LabeledExamples, Model, Network, Weight};
+
+/// Averages the outputs of several networks trained on the same task, which usually predicts better than any one of
+/// them alone.  Members can be any `Model`, though they all have to be the same kind.
+#[derive(Clone)]
+pub struct Ensemble<N: Model = Network> {
+    pub networks: Vec<N>,
+}
+
+impl<N: Model> Ensemble<N> {
+    pub fn new(networks: Vec<N>) -> Self {
+        assert!(!networks.is_empty(), "Ensemble must have at least one network");
+        Ensemble { networks }
+    }
+
+    /// Builds one member for each seed, for ensembles of identical architectures that differ only in their random
+    /// initialization.
+    pub fn from_seeds(builder: impl Fn(u64) -> N, seeds: &[u64]) -> Self {
+        Ensemble::new(seeds.iter().map(|&seed| builder(seed)).collect())
+    }
+
+    /// Runs every network on `inputs` and returns the elementwise mean of their outputs.
+    pub fn compute(&mut self, inputs: &[Weight]) -> Vec<Weight> {
+        let mut sum = self.networks[0].predict(inputs);
+        for network in &mut self.networks[1..] {
+            for (sum, &output) in sum.iter_mut().zip(network.forward_propagate(inputs)) {
+                *sum += output;
+            }
+        }
+        let member_count = self.networks.len() as Weight;
+        for val in &mut sum {
+            *val /= member_count;
+        }
+        sum
+    }
+
+    /// Trains every member on `training` with `fit_model` and returns the mean training loss of each epoch, averaged
+    /// over the members.  Each member is trained separately, so they only differ if they started out differently.
+    pub fn fit(&mut self, training: LabeledExamples, epochs: usize, learning_rate: Weight) -> Vec<Weight> {
+        let mut losses = vec![0.; epochs];
+        for network in &mut self.networks {
+            for (loss, member_loss) in losses
+                .iter_mut()
+                .zip(fit_model(network, training, epochs, learning_rate))
+            {
+                *loss += member_loss;
+            }
+        }
+        let member_count = self.networks.len() as Weight;
+        for loss in &mut losses {
+            *loss /= member_count;
+        }
+        losses
+    }
+
+    /// Returns the averaged outputs for each of `examples`.
+    pub fn predict(&mut self, examples: &[Vec<Weight>]) -> Vec<Vec<Weight>> {
+        examples.iter().map(|inputs| self.compute(inputs)).collect()
+    }
+}
@@ -0,0 +1,39 @@
+use crate::{Model, Weight};
+
+/// A non-finite neuron gradient found after a backward pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientEvent {
+    /// Index into `Model::layer_gradients`.  For a `Network` that's the index into `hidden_layers`, or
+    /// `hidden_layers.len()` for the output layer.
+    pub layer_ix: usize,
+    pub neuron_ix: usize,
+    /// The offending gradient, either NaN or infinite
+    pub gradient: Weight,
+}
+
+/// Checks a model's gradients for NaN or infinite values, calling `hook` for every neuron affected.
+pub struct GradientMonitor {
+    pub hook: Box<dyn Fn(GradientEvent)>,
+}
+
+impl GradientMonitor {
+    pub fn new(hook: impl Fn(GradientEvent) + 'static) -> Self { GradientMonitor { hook: Box::new(hook) } }
+
+    /// Returns true if every gradient was finite.
+    pub fn check(&self, model: &impl Model) -> bool {
+        let mut all_finite = true;
+        for (layer_ix, neuron_gradients) in model.layer_gradients().into_iter().enumerate() {
+            for (neuron_ix, &gradient) in neuron_gradients.iter().enumerate() {
+                if !gradient.is_finite() {
+                    all_finite = false;
+                    (self.hook)(GradientEvent {
+                        layer_ix,
+                        neuron_ix,
+                        gradient,
+                    });
+                }
+            }
+        }
+        all_finite
+    }
+}
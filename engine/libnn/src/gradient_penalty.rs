@@ -0,0 +1,275 @@
+use std::borrow::Cow;
+
+use crate::{ActivationFunction, Network, Weight};
+
+/// Which norm of the input gradient `GradientPenalty` penalizes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PenaltyType {
+    /// Sum of absolute values
+    L1,
+    /// Sum of squares, which is smooth everywhere unlike the Euclidean norm itself
+    L2,
+    /// Largest absolute value
+    LInf,
+}
+
+/// Penalizes `lambda` times the norm of the gradient of a network's single output with respect to its inputs, which
+/// keeps the network close to Lipschitz continuous, as needed for the critic in Wasserstein GAN training
+/// (Gulrajani et al., 2017).
+///
+/// Computing the input gradient takes one backward pass, and differentiating the penalty with respect to the weights
+/// takes a second pass back through that one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientPenalty {
+    pub lambda: Weight,
+    pub penalty_type: PenaltyType,
+}
+
+/// The parts of a layer that the penalty's gradient depends on
+struct LayerView<'a> {
+    /// The weights the layer's outputs are computed with, which are scaled down from the raw weights under spectral
+    /// normalization
+    weights: Cow<'a, [Vec<Weight>]>,
+    /// What the raw weights are multiplied by to get `weights`
+    scale: Weight,
+    outputs_before_activation: &'a [Weight],
+    activation_fn: &'a dyn ActivationFunction,
+}
+
+/// The penalty, the gradient of the penalty with respect to each layer's weights, and then with respect to each
+/// layer's biases
+type PenaltyGradients = (Weight, Vec<Vec<Vec<Weight>>>, Vec<Vec<Weight>>);
+
+/// `weights^T * vector`, where `weights` has one row per neuron
+fn transpose_mul(weights: &[Vec<Weight>], vector: &[Weight]) -> Vec<Weight> {
+    let mut result = vec![0.; weights.first().map_or(0, |row| row.len())];
+    for (row, &val) in weights.iter().zip(vector.iter()) {
+        for (dst, &weight) in result.iter_mut().zip(row.iter()) {
+            *dst += weight * val;
+        }
+    }
+    result
+}
+
+impl GradientPenalty {
+    pub fn new(lambda: Weight, penalty_type: PenaltyType) -> Self { GradientPenalty { lambda, penalty_type } }
+
+    fn norm(&self, gradients: &[Weight]) -> Weight {
+        match self.penalty_type {
+            PenaltyType::L1 => gradients.iter().map(|gradient| gradient.abs()).sum(),
+            PenaltyType::L2 => gradients.iter().map(|gradient| gradient * gradient).sum(),
+            PenaltyType::LInf => gradients.iter().fold(0., |max, gradient| max.max(gradient.abs())),
+        }
+    }
+
+    /// The derivative of `norm` with respect to each gradient
+    fn norm_derivatives(&self, gradients: &[Weight]) -> Vec<Weight> {
+        match self.penalty_type {
+            PenaltyType::L1 => gradients.iter().map(|gradient| gradient.signum()).collect(),
+            PenaltyType::L2 => gradients.iter().map(|gradient| 2. * gradient).collect(),
+            PenaltyType::LInf => {
+                let mut derivatives = vec![0.; gradients.len()];
+                let max_ix = (0..gradients.len()).max_by(|&a, &b| gradients[a].abs().total_cmp(&gradients[b].abs()));
+                if let Some(max_ix) = max_ix {
+                    derivatives[max_ix] = gradients[max_ix].signum();
+                }
+                derivatives
+            },
+        }
+    }
+
+    fn check_network(network: &Network) {
+        assert_eq!(
+            network.outputs.outputs.len(),
+            1,
+            "Gradient penalties need a network with a single output"
+        );
+    }
+
+    /// Runs the network on `inputs` and returns the gradient of its output with respect to each input.
+    pub fn input_gradients(network: &mut Network, inputs: &[Weight]) -> Vec<Weight> {
+        Self::check_network(network);
+        network.forward_propagate(inputs);
+        network.backpropagate(&[1.]);
+        let mut gradients = vec![0.; inputs.len()];
+        network.compute_input_gradients(&mut gradients);
+        gradients
+    }
+
+    /// Returns the penalty at `inputs`.
+    pub fn compute(&self, network: &mut Network, inputs: &[Weight]) -> Weight {
+        self.lambda * self.norm(&Self::input_gradients(network, inputs))
+    }
+
+    /// Takes a gradient descent step on the penalty at `inputs`, returning the penalty from before the step.  Call
+    /// this alongside the regular training step so that the penalty is effectively part of the total loss.
+    pub fn apply(&self, network: &mut Network, inputs: &[Weight], learning_rate: Weight) -> Weight {
+        Self::check_network(network);
+        network.forward_propagate(inputs);
+        let (penalty, weight_gradients, bias_gradients) = self.penalty_gradients(network, inputs);
+
+        let layer_count = network.hidden_layers.len();
+        for (layer_ix, layer) in network.hidden_layers.iter_mut().enumerate() {
+            if layer.frozen {
+                continue;
+            }
+            for (row, gradient_row) in layer.weights.iter_mut().zip(weight_gradients[layer_ix].iter()) {
+                for (weight, gradient) in row.iter_mut().zip(gradient_row.iter()) {
+                    *weight -= learning_rate * gradient;
+                }
+            }
+            layer.after_weight_update();
+            if layer.use_bias {
+                for (bias, gradient) in layer.biases.iter_mut().zip(bias_gradients[layer_ix].iter()) {
+                    *bias -= learning_rate * gradient;
+                }
+            }
+        }
+        for (row, gradient_row) in network
+            .outputs
+            .weights
+            .iter_mut()
+            .zip(weight_gradients[layer_count].iter())
+        {
+            for (weight, gradient) in row.iter_mut().zip(gradient_row.iter()) {
+                *weight -= learning_rate * gradient;
+            }
+        }
+        penalty
+    }
+
+    /// Once `forward_propagate()` has been called, returns the penalty along with its gradient with respect to the
+    /// weights and biases of every layer, the output layer last.
+    pub(crate) fn penalty_gradients(&self, network: &Network, inputs: &[Weight]) -> PenaltyGradients {
+        let layers: Vec<LayerView> = network
+            .hidden_layers
+            .iter()
+            .map(|layer| {
+                let scale = layer.spectral_norm_scale();
+                LayerView {
+                    weights: if scale == 1. {
+                        Cow::Borrowed(layer.weights.as_slice())
+                    } else {
+                        Cow::Owned(
+                            layer
+                                .weights
+                                .iter()
+                                .map(|row| row.iter().map(|weight| weight * scale).collect())
+                                .collect(),
+                        )
+                    },
+                    scale,
+                    outputs_before_activation: &layer.outputs_before_activation,
+                    activation_fn: layer.activation_fn,
+                }
+            })
+            .chain(std::iter::once(LayerView {
+                weights: Cow::Borrowed(network.outputs.weights.as_slice()),
+                scale: 1.,
+                outputs_before_activation: &network.outputs.outputs_before_activation,
+                activation_fn: network.outputs.activation_fn,
+            }))
+            .collect();
+        let layer_inputs: Vec<&[Weight]> = std::iter::once(inputs)
+            .chain(network.hidden_layers.iter().map(|layer| layer.outputs.as_slice()))
+            .collect();
+        let layer_count = layers.len();
+
+        // First pass: the usual backward pass for the output itself.  `backward_inputs[l]` is what flows into layer
+        // `l` from above, so that `deltas[l] = activation'(z[l]) * backward_inputs[l]`.
+        let mut backward_inputs = vec![Vec::new(); layer_count];
+        let mut deltas = vec![Vec::new(); layer_count];
+        backward_inputs[layer_count - 1] = vec![1.];
+        for layer_ix in (0..layer_count).rev() {
+            if layer_ix + 1 < layer_count {
+                backward_inputs[layer_ix] = transpose_mul(&layers[layer_ix + 1].weights, &deltas[layer_ix + 1]);
+            }
+            let layer = &layers[layer_ix];
+            deltas[layer_ix] = layer
+                .outputs_before_activation
+                .iter()
+                .zip(backward_inputs[layer_ix].iter())
+                .map(|(&z, &backward_input)| layer.activation_fn.derivative(z) * backward_input)
+                .collect();
+        }
+        let input_gradients = transpose_mul(&layers[0].weights, &deltas[0]);
+        let penalty = self.lambda * self.norm(&input_gradients);
+
+        // Second pass: back through the first pass, which runs from the inputs towards the output.  Wherever the
+        // first pass read a weight directly it contributes to `weight_gradients` here; wherever it read a
+        // pre-activation value, the contribution is collected in `direct` to be sent back through the forward pass.
+        let mut weight_gradients: Vec<Vec<Vec<Weight>>> = layers
+            .iter()
+            .map(|layer| vec![vec![0.; layer.weights[0].len()]; layer.weights.len()])
+            .collect();
+        let mut direct = Vec::with_capacity(layer_count);
+        let mut upstream: Vec<Weight> = self
+            .norm_derivatives(&input_gradients)
+            .iter()
+            .map(|derivative| self.lambda * derivative)
+            .collect();
+        for (layer_ix, layer) in layers.iter().enumerate() {
+            for (row, &delta) in weight_gradients[layer_ix].iter_mut().zip(deltas[layer_ix].iter()) {
+                for (gradient, &val) in row.iter_mut().zip(upstream.iter()) {
+                    *gradient += delta * val;
+                }
+            }
+            let delta_adjoints: Vec<Weight> = layer
+                .weights
+                .iter()
+                .map(|row| row.iter().zip(upstream.iter()).map(|(weight, val)| weight * val).sum())
+                .collect();
+            direct.push(
+                delta_adjoints
+                    .iter()
+                    .zip(layer.outputs_before_activation.iter())
+                    .zip(backward_inputs[layer_ix].iter())
+                    .map(|((&adjoint, &z), &backward_input)| {
+                        adjoint * layer.activation_fn.second_derivative(z) * backward_input
+                    })
+                    .collect::<Vec<Weight>>(),
+            );
+            upstream = delta_adjoints
+                .iter()
+                .zip(layer.outputs_before_activation.iter())
+                .map(|(&adjoint, &z)| adjoint * layer.activation_fn.derivative(z))
+                .collect();
+        }
+
+        // Finally, the pre-activation contributions go back through the forward pass like an ordinary cost
+        let mut bias_gradients = vec![Vec::new(); layer_count];
+        let mut pre_activation_gradients: Vec<Weight> = Vec::new();
+        for layer_ix in (0..layer_count).rev() {
+            let layer = &layers[layer_ix];
+            let mut gradients = direct[layer_ix].clone();
+            if layer_ix + 1 < layer_count {
+                let from_above = transpose_mul(&layers[layer_ix + 1].weights, &pre_activation_gradients);
+                for ((gradient, &val), &z) in gradients
+                    .iter_mut()
+                    .zip(from_above.iter())
+                    .zip(layer.outputs_before_activation.iter())
+                {
+                    *gradient += val * layer.activation_fn.derivative(z);
+                }
+            }
+            for (row, &gradient) in weight_gradients[layer_ix].iter_mut().zip(gradients.iter()) {
+                for (weight_gradient, &input) in row.iter_mut().zip(layer_inputs[layer_ix].iter()) {
+                    *weight_gradient += gradient * input;
+                }
+            }
+            bias_gradients[layer_ix] = gradients.clone();
+            pre_activation_gradients = gradients;
+        }
+
+        // The gradients so far are with respect to the scaled weights, and the raw weights move them `scale` times as
+        // much, treating the spectral norm as a constant like the rest of the crate does
+        for (layer, layer_gradients) in layers.iter().zip(weight_gradients.iter_mut()) {
+            if layer.scale != 1. {
+                for gradient in layer_gradients.iter_mut().flatten() {
+                    *gradient *= layer.scale;
+                }
+            }
+        }
+        (penalty, weight_gradients, bias_gradients)
+    }
+}
@@ -0,0 +1,25 @@
+use crate::Weight;
+
+/// Passes its inputs through unchanged but negates and scales the gradients flowing back through it.
+///
+/// Placed between a feature extractor and a classifier that tries to predict which domain an example came from, the
+/// classifier still learns to tell domains apart while the feature extractor learns to make that impossible, leaving
+/// features that work the same for every domain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientReversalLayer {
+    pub lambda: Weight,
+}
+
+impl GradientReversalLayer {
+    pub fn new(lambda: Weight) -> Self { GradientReversalLayer { lambda } }
+
+    pub fn forward_propagate(&self, inputs: &[Weight]) -> Vec<Weight> { inputs.to_owned() }
+
+    /// Fills `dst` with `output_gradients` multiplied by `-lambda`.
+    pub fn compute_gradients(&self, output_gradients: &[Weight], dst: &mut [Weight]) {
+        debug_assert_eq!(output_gradients.len(), dst.len());
+        for (input_gradient, &output_gradient) in dst.iter_mut().zip(output_gradients.iter()) {
+            *input_gradient = -self.lambda * output_gradient;
+        }
+    }
+}
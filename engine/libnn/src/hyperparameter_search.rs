@@ -0,0 +1,154 @@
+use rand::Rng;
+
+use crate::{LabeledExamples, Network, Weight};
+
+/// One point in a `ParamSpace`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HyperParamConfig {
+    pub learning_rate: Weight,
+    pub hidden_size: usize,
+    pub hidden_layer_count: usize,
+}
+
+/// The hyperparameters to search over
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamSpace {
+    /// Smallest and largest learning rate, sampled log-uniformly in between
+    pub learning_rate: (Weight, Weight),
+    /// Neurons per hidden layer, sampled uniformly from these
+    pub hidden_sizes: Vec<usize>,
+    /// Number of hidden layers, sampled uniformly from these
+    pub hidden_layer_counts: Vec<usize>,
+}
+
+impl ParamSpace {
+    pub fn sample(&self, rng: &mut impl Rng) -> HyperParamConfig {
+        let (min_lr, max_lr) = self.learning_rate;
+        HyperParamConfig {
+            learning_rate: rng.gen_range(min_lr.ln(), max_lr.ln()).exp().clamp(min_lr, max_lr),
+            hidden_size: self.hidden_sizes[rng.gen_range(0, self.hidden_sizes.len())],
+            hidden_layer_count: self.hidden_layer_counts[rng.gen_range(0, self.hidden_layer_counts.len())],
+        }
+    }
+
+    /// Maps `config` to a point in `[0, 1]^3`, with the learning rate on a log scale and each categorical choice at
+    /// the centre of its own equal slice of the interval, so that a model over the cube can compare configurations.
+    pub fn to_unit_cube(&self, config: &HyperParamConfig) -> Vec<Weight> {
+        let (min_lr, max_lr) = self.learning_rate;
+        let categorical = |choices: &[usize], value: usize| {
+            let ix = choices.iter().position(|&choice| choice == value).unwrap_or(0);
+            (ix as Weight + 0.5) / choices.len() as Weight
+        };
+        vec![
+            (config.learning_rate.ln() - min_lr.ln()) / (max_lr.ln() - min_lr.ln()),
+            categorical(&self.hidden_sizes, config.hidden_size),
+            categorical(&self.hidden_layer_counts, config.hidden_layer_count),
+        ]
+    }
+
+    /// The inverse of `to_unit_cube`, picking whichever categorical choice's slice `point` falls in
+    pub fn from_unit_cube(&self, point: &[Weight]) -> HyperParamConfig {
+        let (min_lr, max_lr) = self.learning_rate;
+        let categorical = |choices: &[usize], position: Weight| {
+            let ix = (position * choices.len() as Weight) as usize;
+            choices[ix.min(choices.len() - 1)]
+        };
+        HyperParamConfig {
+            learning_rate: (min_lr.ln() + point[0].clamp(0., 1.) * (max_lr.ln() - min_lr.ln()))
+                .exp()
+                .clamp(min_lr, max_lr),
+            hidden_size: categorical(&self.hidden_sizes, point[1]),
+            hidden_layer_count: categorical(&self.hidden_layer_counts, point[2]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrialResult {
+    pub config: HyperParamConfig,
+    /// Mean validation loss per example after training
+    pub validation_loss: Weight,
+}
+
+/// Trains a fresh network from `build` with `config`'s learning rate for `epochs` epochs and returns its mean
+/// validation loss per example.
+pub(crate) fn run_trial(
+    build: &impl Fn(&HyperParamConfig) -> Network,
+    config: &HyperParamConfig,
+    training: LabeledExamples,
+    validation: LabeledExamples,
+    epochs: usize,
+) -> Weight {
+    let (examples, expected) = training;
+    assert_eq!(examples.len(), expected.len());
+
+    let mut network = build(config);
+    network.learning_rate = config.learning_rate;
+    for _ in 0..epochs {
+        for (example, expected) in examples.iter().zip(expected.iter()) {
+            network.train_one_example(example, expected, config.learning_rate);
+        }
+    }
+    network.evaluate(validation.0, validation.1).mean_loss_per_example
+}
+
+/// Tries `n_trials` configurations sampled independently from `param_space`, which for the same budget usually beats a
+/// grid search because no trials are wasted on hyperparameters that don't matter (Bergstra & Bengio, 2012).
+pub struct RandomSearch {
+    pub param_space: ParamSpace,
+    pub n_trials: usize,
+    /// Epochs each trial's network is trained for
+    pub epochs: usize,
+    pub rng: pcg::Pcg,
+    /// Every trial run so far, in order
+    pub trials: Vec<TrialResult>,
+}
+
+impl RandomSearch {
+    pub fn new(param_space: ParamSpace, n_trials: usize, epochs: usize, seed: u64) -> Self {
+        RandomSearch {
+            param_space,
+            n_trials,
+            epochs,
+            rng: pcg::Pcg::new(seed, 0),
+            trials: Vec::new(),
+        }
+    }
+
+    /// Runs every trial, building each network with `build`, and returns the configuration with the lowest
+    /// validation loss.  Networks that diverge have a NaN loss and are never chosen.
+    pub fn run(
+        &mut self,
+        build: impl Fn(&HyperParamConfig) -> Network,
+        training: LabeledExamples,
+        validation: LabeledExamples,
+    ) -> HyperParamConfig {
+        assert!(self.n_trials > 0, "Need at least one trial");
+        for _ in 0..self.n_trials {
+            let config = self.param_space.sample(&mut self.rng);
+            let validation_loss = run_trial(&build, &config, training, validation, self.epochs);
+            self.trials.push(TrialResult {
+                config,
+                validation_loss,
+            });
+        }
+        best_trial(&self.trials).config
+    }
+}
+
+/// The trial with the lowest validation loss, ignoring any that diverged
+pub(crate) fn best_trial(trials: &[TrialResult]) -> TrialResult {
+    *trials
+        .iter()
+        .min_by(|a, b| {
+            let loss = |trial: &TrialResult| {
+                if trial.validation_loss.is_nan() {
+                    Weight::INFINITY
+                } else {
+                    trial.validation_loss
+                }
+            };
+            loss(a).total_cmp(&loss(b))
+        })
+        .expect("No trials have been run")
+}
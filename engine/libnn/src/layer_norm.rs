@@ -0,0 +1,94 @@
+use crate::Weight;
+
+/// Normalizes each sample to zero mean and unit variance over its features, then applies a learned per-feature scale
+/// (`gamma`) and shift (`beta`).
+///
+/// Statistics come from the sample alone rather than from a batch, so it works the same during training and inference
+/// and with any batch size.
+#[derive(Clone)]
+pub struct LayerNorm {
+    pub gamma: Vec<Weight>,
+    pub beta: Vec<Weight>,
+    /// Added to the variance to keep the division stable for nearly constant inputs
+    pub epsilon: Weight,
+    /// The inputs from the last call to `normalize`, after normalization but before scaling and shifting
+    pub normalized: Vec<Weight>,
+    /// `1 / sqrt(variance + epsilon)` from the last call to `normalize`
+    pub inv_std: Weight,
+    /// Gradients accumulated since the last call to `update_weights`
+    pub gamma_gradients: Vec<Weight>,
+    pub beta_gradients: Vec<Weight>,
+}
+
+impl LayerNorm {
+    pub fn new(feature_count: usize, epsilon: Weight) -> Self {
+        LayerNorm {
+            gamma: vec![1.; feature_count],
+            beta: vec![0.; feature_count],
+            epsilon,
+            normalized: vec![0.; feature_count],
+            inv_std: 0.,
+            gamma_gradients: vec![0.; feature_count],
+            beta_gradients: vec![0.; feature_count],
+        }
+    }
+
+    pub fn normalize(&mut self, x: &[Weight]) -> Vec<Weight> {
+        debug_assert_eq!(x.len(), self.gamma.len());
+        let count = x.len() as Weight;
+        let mean = x.iter().sum::<Weight>() / count;
+        let variance = x.iter().map(|&x| (x - mean).powi(2)).sum::<Weight>() / count;
+        self.inv_std = 1. / (variance + self.epsilon).sqrt();
+
+        for (normalized, &x) in self.normalized.iter_mut().zip(x.iter()) {
+            *normalized = (x - mean) * self.inv_std;
+        }
+        self.normalized
+            .iter()
+            .zip(self.gamma.iter().zip(self.beta.iter()))
+            .map(|(&normalized, (&gamma, &beta))| gamma * normalized + beta)
+            .collect()
+    }
+
+    /// Once `normalize()` has been called, accumulates the gradients for `gamma` and `beta` and fills `dst` with the
+    /// gradients with respect to each input.  `output_gradients` and `dst` are negated gradients of the cost, like the
+    /// neuron gradients of other layers.
+    pub fn compute_gradients(&mut self, output_gradients: &[Weight], dst: &mut [Weight]) {
+        debug_assert_eq!(output_gradients.len(), self.gamma.len());
+        debug_assert_eq!(dst.len(), self.gamma.len());
+
+        let mut normalized_gradient_sum = 0.;
+        let mut normalized_gradient_dot = 0.;
+        for (feature_ix, &output_gradient) in output_gradients.iter().enumerate() {
+            let normalized = self.normalized[feature_ix];
+            self.gamma_gradients[feature_ix] += output_gradient * normalized;
+            self.beta_gradients[feature_ix] += output_gradient;
+
+            let normalized_gradient = output_gradient * self.gamma[feature_ix];
+            normalized_gradient_sum += normalized_gradient;
+            normalized_gradient_dot += normalized_gradient * normalized;
+        }
+
+        // Every input affects the mean and variance, and so every output, which is where the two sums come from
+        let count = dst.len() as Weight;
+        for (feature_ix, input_gradient) in dst.iter_mut().enumerate() {
+            let normalized_gradient = output_gradients[feature_ix] * self.gamma[feature_ix];
+            *input_gradient = self.inv_std / count
+                * (count * normalized_gradient
+                    - normalized_gradient_sum
+                    - self.normalized[feature_ix] * normalized_gradient_dot);
+        }
+    }
+
+    /// Applies all accumulated gradients to `gamma` and `beta` and clears them.
+    pub fn update_weights(&mut self, learning_rate: Weight) {
+        for (gamma, gradient) in self.gamma.iter_mut().zip(self.gamma_gradients.iter_mut()) {
+            *gamma += learning_rate * *gradient;
+            *gradient = 0.;
+        }
+        for (beta, gradient) in self.beta.iter_mut().zip(self.beta_gradients.iter_mut()) {
+            *beta += learning_rate * *gradient;
+            *gradient = 0.;
+        }
+    }
+}
@@ -2,20 +2,135 @@
 
 #[cfg(target_arch = "wasm32")]
 use core::arch::wasm32::*;
+use std::borrow::Cow;
 
 use fast_math::sigmoid_approx;
 
+mod activation_stats;
+mod attribution;
+mod augmentation;
+mod bayesian_optimization;
+mod beam_search;
+mod builder;
+mod calibration;
+mod callbacks;
+mod compression;
+mod cross_validation;
+mod ctc;
+mod darts;
+mod data_loader;
+mod dot;
+mod embedding;
+mod ensemble;
 mod fast_math;
+mod gradient_monitor;
+mod gradient_penalty;
+mod gradient_reversal;
+mod hyperparameter_search;
+mod layer_norm;
+mod losses;
+mod lr_finder;
+mod lr_schedulers;
+mod maxout;
+mod metric_learning;
+mod metrics;
+mod mixture_density;
+mod model;
+mod mutual_information;
+mod nce;
+mod noise;
+mod npy;
+mod optimizers;
+mod orthogonal;
+mod pbt;
+mod positional_encoding;
+mod prelu;
+mod profiling;
+mod pruning;
+mod quantization;
+mod registry;
+mod reservoir;
+mod sensitivity;
+mod serialization;
+mod spectral_norm;
+mod static_output;
+mod swa;
+mod tape;
+mod tensorboard;
 #[cfg(test)]
 mod tests;
+mod trainer;
+mod typed;
+
+pub use activation_stats::*;
+pub use attribution::*;
+pub use augmentation::*;
+pub use bayesian_optimization::*;
+pub use beam_search::*;
+pub use builder::*;
+pub use calibration::*;
+pub use callbacks::*;
+pub use compression::*;
+pub use cross_validation::*;
+pub use ctc::*;
+pub use darts::*;
+pub use data_loader::*;
+pub use embedding::*;
+pub use ensemble::*;
+pub use gradient_monitor::*;
+pub use gradient_penalty::*;
+pub use gradient_reversal::*;
+pub use hyperparameter_search::*;
+pub use layer_norm::*;
+pub use losses::*;
+pub use lr_finder::*;
+pub use lr_schedulers::*;
+pub use maxout::*;
+pub use metric_learning::*;
+pub use metrics::*;
+pub use mixture_density::*;
+pub use model::*;
+pub use mutual_information::*;
+pub use nce::*;
+pub use noise::*;
+pub use npy::*;
+pub use optimizers::*;
+pub use orthogonal::*;
+pub use pbt::*;
+pub use positional_encoding::*;
+pub use prelu::*;
+pub use profiling::*;
+pub use pruning::*;
+pub use quantization::*;
+pub use registry::*;
+pub use reservoir::*;
+pub use sensitivity::*;
+pub use spectral_norm::*;
+pub use static_output::*;
+pub use swa::*;
+pub use tape::*;
+pub use tensorboard::*;
+pub use trainer::*;
+pub use typed::*;
 
 pub type Weight = f32;
 
 pub trait ActivationFunction {
+    /// Identifies the function in saved networks.  Override this with the name passed to
+    /// `register_activation_fn` so that networks using a custom function can be loaded again.
+    fn name(&self) -> &'static str { std::any::type_name::<Self>() }
+
     fn get_output(&self, x: Weight) -> Weight;
 
     fn derivative(&self, x: Weight) -> Weight;
 
+    /// Used by penalties on input gradients, which have to differentiate through the backward pass.  Defaults to a
+    /// central difference of `derivative`.
+    fn second_derivative(&self, x: Weight) -> Weight {
+        const STEP: Weight = 1e-3;
+        (self.derivative(x + STEP) - self.derivative(x - STEP)) / (2. * STEP)
+    }
+
     fn apply_batch(&self, dst: &mut [Weight], src: &[Weight]) {
         debug_assert_eq!(src.len(), dst.len());
         for i in 0..dst.len() {
@@ -39,6 +154,8 @@ pub struct Sigmoid;
 pub static SIGMOID: Sigmoid = Sigmoid;
 
 impl ActivationFunction for Sigmoid {
+    fn name(&self) -> &'static str { "sigmoid" }
+
     fn get_output(&self, x: Weight) -> Weight {
         // 1. / (1. + std::f32::consts::E.powf(-x))
         sigmoid_approx(x)
@@ -48,30 +165,98 @@ impl ActivationFunction for Sigmoid {
         let y = self.get_output(x);
         y * (1. - y)
     }
+
+    fn second_derivative(&self, x: Weight) -> Weight {
+        let y = self.get_output(x);
+        y * (1. - y) * (1. - 2. * y)
+    }
+}
+
+/// Exact logistic function.  `exp` is only ever evaluated on non-positive numbers so it can't overflow for inputs of
+/// large magnitude.
+pub fn sigmoid(x: Weight) -> Weight {
+    if x >= 0. {
+        1. / (1. + (-x).exp())
+    } else {
+        let e = x.exp();
+        e / (1. + e)
+    }
+}
+
+/// `ln(sigmoid(x))`, computed without going through `sigmoid` so that it stays accurate when `sigmoid(x)` rounds to 0.
+pub fn log_sigmoid(x: Weight) -> Weight {
+    if x >= 0. {
+        -(-x).exp().ln_1p()
+    } else {
+        x - x.exp().ln_1p()
+    }
+}
+
+/// `ln(sum(exp(vals)))`, shifted by the largest value so that it doesn't overflow.  Returns negative infinity for an
+/// empty slice.
+pub fn log_sum_exp(vals: &[Weight]) -> Weight {
+    let max = vals.iter().cloned().fold(Weight::NEG_INFINITY, Weight::max);
+    if max == Weight::NEG_INFINITY {
+        return max;
+    }
+    max + vals.iter().map(|&val| (val - max).exp()).sum::<Weight>().ln()
+}
+
+/// Sigmoid computed exactly rather than with the lookup table used by `Sigmoid`.
+pub struct StableSigmoid;
+pub static STABLE_SIGMOID: StableSigmoid = StableSigmoid;
+
+impl ActivationFunction for StableSigmoid {
+    fn name(&self) -> &'static str { "stable_sigmoid" }
+
+    fn get_output(&self, x: Weight) -> Weight { sigmoid(x) }
+
+    fn derivative(&self, x: Weight) -> Weight {
+        let y = sigmoid(x);
+        y * (1. - y)
+    }
+
+    fn second_derivative(&self, x: Weight) -> Weight {
+        let y = sigmoid(x);
+        y * (1. - y) * (1. - 2. * y)
+    }
 }
 
 pub struct Tanh;
 pub static TANH: Tanh = Tanh;
 
 impl ActivationFunction for Tanh {
+    fn name(&self) -> &'static str { "tanh" }
+
     fn get_output(&self, x: Weight) -> Weight { x.tanh() }
 
     fn derivative(&self, x: Weight) -> Weight { 1. - x.tanh().powi(2) }
+
+    fn second_derivative(&self, x: Weight) -> Weight {
+        let y = x.tanh();
+        -2. * y * (1. - y * y)
+    }
 }
 
 pub struct Identity;
 pub static IDENTITY: Identity = Identity;
 
 impl ActivationFunction for Identity {
+    fn name(&self) -> &'static str { "identity" }
+
     fn get_output(&self, x: Weight) -> Weight { x }
 
     fn derivative(&self, _x: Weight) -> Weight { 1. }
+
+    fn second_derivative(&self, _x: Weight) -> Weight { 0. }
 }
 
 pub struct ReLU;
 pub static RELU: ReLU = ReLU;
 
 impl ActivationFunction for ReLU {
+    fn name(&self) -> &'static str { "relu" }
+
     fn get_output(&self, x: Weight) -> Weight {
         if x > 0. {
             x
@@ -88,6 +273,8 @@ impl ActivationFunction for ReLU {
         }
     }
 
+    fn second_derivative(&self, _x: Weight) -> Weight { 0. }
+
     #[cfg(target_arch = "wasm32")]
     fn apply_batch(&self, dst: &mut [Weight], src: &[Weight]) {
         debug_assert_eq!(src.len(), dst.len());
@@ -142,6 +329,8 @@ pub struct LeakyReLU;
 pub static LEAKY_RELU: LeakyReLU = LeakyReLU;
 
 impl ActivationFunction for LeakyReLU {
+    fn name(&self) -> &'static str { "leaky_relu" }
+
     fn get_output(&self, x: Weight) -> Weight {
         if x < 0. {
             0.01 * x
@@ -219,6 +408,8 @@ pub struct GrowingCosineUnit;
 pub static GCU: GrowingCosineUnit = GrowingCosineUnit;
 
 impl ActivationFunction for GrowingCosineUnit {
+    fn name(&self) -> &'static str { "gcu" }
+
     fn get_output(&self, x: Weight) -> Weight {
         if x >= -std::f32::consts::PI && x <= std::f32::consts::PI {
             return x * fastapprox::fast::cos(x);
@@ -240,6 +431,8 @@ pub struct Gaussian;
 pub static GAUSSIAN: Gaussian = Gaussian;
 
 impl ActivationFunction for Gaussian {
+    fn name(&self) -> &'static str { "gaussian" }
+
     // TODO: Fastmath
     fn get_output(&self, x: Weight) -> Weight { std::f32::consts::E.powf(-x * x) }
 
@@ -252,19 +445,131 @@ pub struct Swish;
 pub static SWISH: Swish = Swish;
 
 impl ActivationFunction for Swish {
+    fn name(&self) -> &'static str { "swish" }
+
     // TODO: Fastmath
-    fn get_output(&self, x: Weight) -> Weight { x / (1. + std::f32::consts::E.powf(-x)) }
+    fn get_output(&self, x: Weight) -> Weight { x * sigmoid(x) }
+
+    /// Written in terms of `sigmoid` so that it stays finite when `e^-x` overflows
+    fn derivative(&self, x: Weight) -> Weight {
+        let s = sigmoid(x);
+        s + x * s * (1. - s)
+    }
+}
+
+/// `sqrt(2 / pi)`, used by the tanh approximation of GELU
+const GELU_TANH_SCALE: Weight = 0.797_884_6;
+const GELU_TANH_CUBIC: Weight = 0.044_715;
+
+/// Gaussian Error Linear Unit, `x * Phi(x)` where `Phi` is the standard normal CDF.  With `approximate` set, `Phi`
+/// is replaced by the tanh approximation from the original paper, which is cheaper and what many pretrained models
+/// were trained with.
+pub struct Gelu {
+    pub approximate: bool,
+}
+pub static GELU: Gelu = Gelu { approximate: false };
+pub static GELU_TANH: Gelu = Gelu { approximate: true };
+
+/// Abramowitz and Stegun formula 7.1.26, accurate to about 1.5e-7
+pub(crate) fn erf(x: Weight) -> Weight {
+    let t = 1. / (1. + 0.327_591_1 * x.abs());
+    let polynomial = t * (0.254_829_6 + t * (-0.284_496_7 + t * (1.421_413_7 + t * (-1.453_152 + t * 1.061_405_4))));
+    let y = 1. - polynomial * (-x * x).exp();
+    if x >= 0. {
+        y
+    } else {
+        -y
+    }
+}
+
+impl ActivationFunction for Gelu {
+    fn name(&self) -> &'static str {
+        if self.approximate {
+            "gelu_tanh"
+        } else {
+            "gelu"
+        }
+    }
+
+    fn get_output(&self, x: Weight) -> Weight {
+        if self.approximate {
+            0.5 * x * (1. + (GELU_TANH_SCALE * (x + GELU_TANH_CUBIC * x * x * x)).tanh())
+        } else {
+            0.5 * x * (1. + erf(x * std::f32::consts::FRAC_1_SQRT_2))
+        }
+    }
 
     fn derivative(&self, x: Weight) -> Weight {
-        (1. + std::f32::consts::E.powf(-x) + (x * std::f32::consts::E.powf(-x)))
-            / (1. + std::f32::consts::E.powf(-x)).powi(2)
+        if self.approximate {
+            let t = (GELU_TANH_SCALE * (x + GELU_TANH_CUBIC * x * x * x)).tanh();
+            0.5 * (1. + t) + 0.5 * x * (1. - t * t) * GELU_TANH_SCALE * (1. + 3. * GELU_TANH_CUBIC * x * x)
+        } else {
+            let cdf = 0.5 * (1. + erf(x * std::f32::consts::FRAC_1_SQRT_2));
+            let pdf = (-0.5 * x * x).exp() / (2. * std::f32::consts::PI).sqrt();
+            cdf + x * pdf
+        }
     }
 }
 
+/// Exponential Linear Unit: `x` for positive inputs and `alpha * (e^x - 1)` otherwise, which saturates at `-alpha`.
+/// Layers take a `&'static` activation function, so an `Elu` with a custom `alpha` needs to be stored in a `static`.
+///
+/// `ELU` is saved as `elu`, and any other `alpha` as `elu(alpha)`, which loading turns back into an `Elu` with the same
+/// `alpha`.
+pub struct Elu {
+    pub alpha: Weight,
+}
+pub static ELU: Elu = Elu { alpha: 1. };
+
+impl ActivationFunction for Elu {
+    fn name(&self) -> &'static str {
+        if self.alpha == 1. {
+            "elu"
+        } else {
+            parameterized_name("elu", &[self.alpha])
+        }
+    }
+
+    fn get_output(&self, x: Weight) -> Weight {
+        if x > 0. {
+            x
+        } else {
+            self.alpha * x.exp_m1()
+        }
+    }
+
+    fn derivative(&self, x: Weight) -> Weight {
+        if x > 0. {
+            1.
+        } else {
+            self.alpha * x.exp()
+        }
+    }
+}
+
+/// The constants from "Self-Normalizing Neural Networks" (Klambauer et al., 2017) which keep activations at zero mean
+/// and unit variance from layer to layer
+pub const SELU_ALPHA: Weight = 1.673_263_2;
+pub const SELU_SCALE: Weight = 1.050_701;
+
+/// Scaled ELU, `SELU_SCALE * ELU(x)` with `alpha = SELU_ALPHA`
+pub struct Selu;
+pub static SELU: Selu = Selu;
+
+impl ActivationFunction for Selu {
+    fn name(&self) -> &'static str { "selu" }
+
+    fn get_output(&self, x: Weight) -> Weight { SELU_SCALE * Elu { alpha: SELU_ALPHA }.get_output(x) }
+
+    fn derivative(&self, x: Weight) -> Weight { SELU_SCALE * Elu { alpha: SELU_ALPHA }.derivative(x) }
+}
+
 pub struct Ameo;
 pub static AMEO: Ameo = Ameo;
 
 impl ActivationFunction for Ameo {
+    fn name(&self) -> &'static str { "ameo" }
+
     fn get_output(&self, x: Weight) -> Weight {
         if x >= 0. {
             GCU.get_output(x)
@@ -283,20 +588,68 @@ impl ActivationFunction for Ameo {
 }
 
 pub trait CostFunction {
+    /// Identifies the function in saved networks, like `ActivationFunction::name`.
+    fn name(&self) -> &'static str { std::any::type_name::<Self>() }
+
+    /// The cost of a single output given its error of `expected - output`.  Costs that depend on more than the error
+    /// override the slice-based methods as well, and give the cost of a single output of 0 here.
     fn get_cost(&self, error: Weight) -> Weight;
 
+    /// The negated derivative of `get_cost` with respect to the output
     fn derivative(&self, error: Weight) -> Weight;
+
+    /// Fills `costs` with the cost attributed to each output, given `errors` of `expected - output`.  The default
+    /// applies `get_cost` to each error on its own; costs that depend on several outputs at once, like those for
+    /// classification, override this and `compute_error_gradients` instead.
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        debug_assert_eq!(outputs.len(), costs.len());
+        for (cost, &error) in costs.iter_mut().zip(errors.iter()) {
+            *cost = self.get_cost(error);
+        }
+    }
+
+    /// Fills `dst` with the negated gradient of the total cost with respect to each output, which the output layer
+    /// multiplies by the derivative of its activation function.  The default applies `derivative` to each error.
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        debug_assert_eq!(outputs.len(), dst.len());
+        for (gradient, &error) in dst.iter_mut().zip(errors.iter()) {
+            *gradient = self.derivative(error);
+        }
+    }
 }
 
 pub struct MeanSquaredError;
 pub static MEAN_SQUARED_ERROR: MeanSquaredError = MeanSquaredError;
 
 impl CostFunction for MeanSquaredError {
+    fn name(&self) -> &'static str { "mean_squared_error" }
+
     fn get_cost(&self, error: Weight) -> Weight { error * error }
 
     fn derivative(&self, error: Weight) -> Weight { error * 2. }
 }
 
+/// L1 loss.  It grows linearly rather than quadratically with the error, so a few outliers don't dominate the fit.
+pub struct MeanAbsoluteError;
+pub static MEAN_ABSOLUTE_ERROR: MeanAbsoluteError = MeanAbsoluteError;
+
+impl CostFunction for MeanAbsoluteError {
+    fn name(&self) -> &'static str { "mean_absolute_error" }
+
+    fn get_cost(&self, error: Weight) -> Weight { error.abs() }
+
+    /// The sign of the error, taking 0 as the subgradient where the cost isn't differentiable
+    fn derivative(&self, error: Weight) -> Weight {
+        if error > 0. {
+            1.
+        } else if error < 0. {
+            -1.
+        } else {
+            0.
+        }
+    }
+}
+
 pub struct MeanSquaredErrorMultiplied(pub f32);
 
 impl CostFunction for MeanSquaredErrorMultiplied {
@@ -305,6 +658,7 @@ impl CostFunction for MeanSquaredErrorMultiplied {
     fn derivative(&self, error: Weight) -> Weight { error * self.0 }
 }
 
+#[derive(Clone)]
 pub struct DenseLayer {
     pub weights: Vec<Vec<Weight>>,
     pub biases: Vec<Weight>,
@@ -313,6 +667,17 @@ pub struct DenseLayer {
     pub errors_scratch: Vec<Weight>,
     pub outputs_before_activation: Vec<Weight>,
     pub outputs: Vec<Weight>,
+    /// Frozen layers skip all weight and bias updates, allowing the rest of a network to be fine-tuned around them
+    pub frozen: bool,
+    /// Layers without biases have an empty `biases` vector and compute just the weighted sum of their inputs
+    pub use_bias: bool,
+    /// The power iteration state, if spectral normalization is enabled
+    pub spectral_norm: Option<SpectralNorm>,
+    /// Weights marked `false` have been pruned by `prune_by_magnitude`
+    pub prune_mask: Option<Vec<Vec<bool>>>,
+    /// Keeps pruned weights at zero through every update.  Without it, pruned weights start out at zero but can grow
+    /// back.
+    pub apply_mask_during_update: bool,
 }
 
 impl DenseLayer {
@@ -341,6 +706,40 @@ impl DenseLayer {
             errors_scratch: vec![0.; neuron_count],
             outputs_before_activation: vec![0.; neuron_count],
             outputs: vec![0.; neuron_count],
+            frozen: false,
+            use_bias: true,
+            spectral_norm: None,
+            prune_mask: None,
+            apply_mask_during_update: true,
+        }
+    }
+
+    /// Like `new`, but the layer has no biases.
+    pub fn new_without_bias(
+        neuron_count: usize,
+        input_count: usize,
+        init_weights: &mut impl FnMut(usize, usize) -> Weight,
+        activation_fn: &'static dyn ActivationFunction,
+    ) -> Self {
+        let mut layer = DenseLayer::new(neuron_count, input_count, init_weights, &mut |_| 0., activation_fn);
+        layer.biases = Vec::new();
+        layer.use_bias = false;
+        layer
+    }
+
+    pub fn freeze(&mut self) { self.frozen = true; }
+
+    pub fn unfreeze(&mut self) { self.frozen = false; }
+
+    /// Clamps every weight to `[min, max]`, as Wasserstein GAN critics do after each update to stay Lipschitz.
+    /// Biases are left alone, as are the weights of frozen layers.
+    pub fn clip_weights(&mut self, min: Weight, max: Weight) {
+        debug_assert!(min <= max);
+        if self.frozen {
+            return;
+        }
+        for weight in self.weights.iter_mut().flatten() {
+            *weight = weight.clamp(min, max);
         }
     }
 
@@ -431,15 +830,24 @@ impl DenseLayer {
 
     #[cfg(not(target_arch = "wasm32"))]
     pub fn update_weights(&mut self, inputs: &[Weight], learning_rate: Weight) {
+        if self.frozen {
+            return;
+        }
+        let learning_rate = learning_rate * self.spectral_norm_scale();
         for (neuron_ix, &neuron_gradient) in self.neuron_gradients.iter().enumerate() {
             for (weight_ix, weight) in self.weights[neuron_ix].iter_mut().enumerate() {
                 *weight += learning_rate * neuron_gradient * inputs[weight_ix];
             }
         }
+        self.after_weight_update();
     }
 
     #[cfg(target_arch = "wasm32")]
     pub fn update_weights(&mut self, inputs: &[Weight], learning_rate: Weight) {
+        if self.frozen {
+            return;
+        }
+        let learning_rate = learning_rate * self.spectral_norm_scale();
         let input_count = inputs.len();
         let remainder = input_count % 4;
         let chunk_count = (input_count - remainder) / 4;
@@ -481,10 +889,64 @@ impl DenseLayer {
                 *weight += learning_rate * neuron_gradient * input;
             }
         }
+        self.after_weight_update();
+    }
+
+    /// Once `compute_gradients()` has been called, fills `dst` with the gradient of the cost with respect to each of
+    /// this layer's inputs.
+    pub fn compute_input_gradients(&self, dst: &mut [Weight]) {
+        dst.fill(0.);
+        for (neuron_weights, &neuron_gradient) in self.weights.iter().zip(self.neuron_gradients.iter()) {
+            for (input_gradient, &weight) in dst.iter_mut().zip(neuron_weights.iter()) {
+                *input_gradient += weight * neuron_gradient;
+            }
+        }
+        let scale = self.spectral_norm_scale();
+        if scale != 1. {
+            for input_gradient in dst.iter_mut() {
+                *input_gradient *= scale;
+            }
+        }
+    }
+
+    /// Like `update_weights`, but lets `optimizer` decide how far each weight moves.
+    pub fn update_weights_with_optimizer(
+        &mut self,
+        inputs: &[Weight],
+        learning_rate: Weight,
+        optimizer: &mut dyn Optimizer,
+    ) {
+        if self.frozen {
+            return;
+        }
+        let learning_rate = learning_rate * self.spectral_norm_scale();
+        optimizer.update_weights(&mut self.weights, &self.neuron_gradients, inputs, learning_rate);
+        self.after_weight_update();
+    }
+
+    /// Keeps the state derived from the weights in step with them: reapplies the prune mask if
+    /// `apply_mask_during_update` is set and takes a power iteration step for spectral normalization.  Every update
+    /// made through this crate calls this, and anything else that changes `weights` should too.
+    pub fn after_weight_update(&mut self) {
+        if self.apply_mask_during_update {
+            self.apply_prune_mask();
+        }
+        self.apply_spectral_normalization();
+    }
+
+    /// Like `update_biases`, but lets `optimizer` decide how far each bias moves.
+    pub fn update_biases_with_optimizer(&mut self, learning_rate: Weight, optimizer: &mut dyn Optimizer) {
+        if self.frozen || !self.use_bias {
+            return;
+        }
+        optimizer.update_biases(&mut self.biases, &self.neuron_gradients, learning_rate);
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     pub fn update_biases(&mut self, learning_rate: Weight) {
+        if self.frozen || !self.use_bias {
+            return;
+        }
         for neuron_ix in 0..self.biases.len() {
             // Each of these biases is added directly to what is fed into our activation function.
             // The impact that it will have on the output of this neuron is equal to
@@ -496,6 +958,9 @@ impl DenseLayer {
 
     #[cfg(target_arch = "wasm32")]
     pub fn update_biases(&mut self, learning_rate: Weight) {
+        if self.frozen || !self.use_bias {
+            return;
+        }
         let remainder = self.biases.len() % 4;
         let chunk_count = (self.biases.len() - remainder) / 4;
         let learning_rate_v = f32x4_splat(learning_rate);
@@ -522,6 +987,7 @@ impl DenseLayer {
     #[cfg(not(target_arch = "wasm32"))]
     pub fn forward_propagate(&mut self, inputs: &[Weight]) {
         debug_assert_eq!(self.weights[0].len(), inputs.len());
+        let scale = self.spectral_norm_scale();
         for neuron_ix in 0..self.weights.len() {
             let mut weight_sum = 0.;
             for (weight_ix, &weight) in unsafe { self.weights.get_unchecked(neuron_ix) }.iter().enumerate() {
@@ -529,9 +995,8 @@ impl DenseLayer {
                 weight_sum += input * weight;
             }
 
-            unsafe {
-                *self.outputs_before_activation.get_unchecked_mut(neuron_ix) = weight_sum + self.biases[neuron_ix]
-            };
+            let bias = if self.use_bias { self.biases[neuron_ix] } else { 0. };
+            unsafe { *self.outputs_before_activation.get_unchecked_mut(neuron_ix) = weight_sum * scale + bias };
         }
 
         (self.activation_fn).apply_batch(&mut self.outputs, &self.outputs_before_activation);
@@ -540,6 +1005,7 @@ impl DenseLayer {
     #[cfg(target_arch = "wasm32")]
     pub fn forward_propagate(&mut self, inputs: &[Weight]) {
         debug_assert_eq!(self.weights[0].len(), inputs.len());
+        let scale = self.spectral_norm_scale();
 
         let input_count = self.weights[0].len();
         let remainder = input_count % 4;
@@ -570,16 +1036,19 @@ impl DenseLayer {
                 weight_sum += input * weight;
             }
 
-            unsafe {
-                *self.outputs_before_activation.get_unchecked_mut(neuron_ix) =
-                    weight_sum + *self.biases.get_unchecked(neuron_ix);
-            }
+            let bias = if self.use_bias {
+                unsafe { *self.biases.get_unchecked(neuron_ix) }
+            } else {
+                0.
+            };
+            unsafe { *self.outputs_before_activation.get_unchecked_mut(neuron_ix) = weight_sum * scale + bias };
         }
 
         (self.activation_fn).apply_batch(&mut self.outputs, &self.outputs_before_activation);
     }
 }
 
+#[derive(Clone)]
 pub struct OutputLayer {
     pub weights: Vec<Vec<Weight>>,
     pub activation_fn: &'static dyn ActivationFunction,
@@ -643,12 +1112,13 @@ impl OutputLayer {
         debug_assert_eq!(expected.len(), self.outputs.len());
         // Assumes that outputs have already been computed.
         for (i, &output) in self.outputs.iter().enumerate() {
-            let error = expected[i] - output;
-            self.errors[i] = error;
-            self.costs[i] = self.cost_fn.get_cost(error);
+            self.errors[i] = expected[i] - output;
         }
+        self.cost_fn.compute_costs(&self.outputs, &self.errors, &mut self.costs);
     }
 
+    /// The gradient of a single neuron for costs that depend on nothing but its error.  `compute_gradients` handles
+    /// every cost, including those that look at the whole layer.
     pub fn compute_neuron_gradient(&self, neuron_output_before_activation: Weight, neuron_error: Weight) -> Weight {
         (self.cost_fn).derivative(neuron_error) * (self.activation_fn).derivative(neuron_output_before_activation)
     }
@@ -657,11 +1127,14 @@ impl OutputLayer {
     /// populates `self.neuron_gradients.
     pub fn compute_gradients(&mut self) {
         // Assumes that costs have already been computed.
-        for (neuron_ix, &neuron_error) in self.errors.iter().enumerate() {
-            let output_before_activation = self.outputs_before_activation[neuron_ix];
-            let gradient = self.compute_neuron_gradient(output_before_activation, neuron_error);
-
-            self.neuron_gradients[neuron_ix] = gradient
+        self.cost_fn
+            .compute_error_gradients(&self.outputs, &self.errors, &mut self.neuron_gradients);
+        for (gradient, &output_before_activation) in self
+            .neuron_gradients
+            .iter_mut()
+            .zip(self.outputs_before_activation.iter())
+        {
+            *gradient *= (self.activation_fn).derivative(output_before_activation);
         }
     }
 
@@ -673,6 +1146,27 @@ impl OutputLayer {
         }
     }
 
+    /// Once `compute_gradients()` has been called, fills `dst` with the gradient of the cost with respect to each of
+    /// this layer's inputs.
+    pub fn compute_input_gradients(&self, dst: &mut [Weight]) {
+        dst.fill(0.);
+        for (neuron_weights, &neuron_gradient) in self.weights.iter().zip(self.neuron_gradients.iter()) {
+            for (input_gradient, &weight) in dst.iter_mut().zip(neuron_weights.iter()) {
+                *input_gradient += weight * neuron_gradient;
+            }
+        }
+    }
+
+    /// Like `update_weights`, but lets `optimizer` decide how far each weight moves.
+    pub fn update_weights_with_optimizer(
+        &mut self,
+        inputs: &[Weight],
+        learning_rate: Weight,
+        optimizer: &mut dyn Optimizer,
+    ) {
+        optimizer.update_weights(&mut self.weights, &self.neuron_gradients, inputs, learning_rate);
+    }
+
     pub fn forward_propagate(&mut self, inputs: &[Weight]) {
         debug_assert_eq!(self.weights[0].len(), inputs.len());
         for neuron_ix in 0..self.weights.len() {
@@ -688,6 +1182,16 @@ impl OutputLayer {
     }
 }
 
+/// How much an example contributes to training.  0 ignores it completely, 1 is the same as unweighted training, and
+/// larger values emphasize it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LossWeight(pub f32);
+
+impl Default for LossWeight {
+    fn default() -> Self { LossWeight(1.) }
+}
+
+#[derive(Clone)]
 pub struct Network {
     pub hidden_layers: Vec<DenseLayer>,
     pub outputs: Box<OutputLayer>,
@@ -705,9 +1209,9 @@ impl Network {
         self.outputs.forward_propagate(inputs);
     }
 
-    /// Returns the average cost of the output before updating weights.  It would be better to compute again after, but
-    /// that would be too expensive
-    pub fn train_one_example(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+    /// Runs `example` through the network and populates costs and neuron gradients for every layer without updating
+    /// any weights.
+    pub fn compute_gradients(&mut self, example: &[Weight], expected: &[Weight]) {
         // Run the example all the way through the network, populating outputs in the output layer.
         self.forward_propagate(example);
 
@@ -715,17 +1219,133 @@ impl Network {
         self.outputs.compute_costs(expected);
         self.outputs.compute_gradients();
 
-        // Then compute gradients for the hidden layers
+        self.compute_hidden_layer_gradients();
+    }
+
+    /// Like `compute_gradients`, but scales every gradient by `loss_weight` so that the example counts for more or less
+    /// than others when training.  Costs aren't scaled.
+    pub fn compute_weighted_gradients(&mut self, example: &[Weight], expected: &[Weight], loss_weight: LossWeight) {
+        self.forward_propagate(example);
+        self.outputs.compute_costs(expected);
+        self.outputs.compute_gradients();
+        for gradient in &mut self.outputs.neuron_gradients {
+            *gradient *= loss_weight.0;
+        }
+
+        self.compute_hidden_layer_gradients();
+    }
+
+    /// Like `train_one_example`, but with the example's gradients scaled by `loss_weight`.
+    pub fn train_one_example_weighted(
+        &mut self,
+        example: &[Weight],
+        expected: &[Weight],
+        loss_weight: LossWeight,
+        learning_rate: Weight,
+    ) -> Weight {
+        self.compute_weighted_gradients(example, expected, loss_weight);
+        self.update_weights(example, learning_rate);
+        self.mean_cost()
+    }
+
+    /// Once `forward_propagate()` has been called, populates neuron gradients for every layer given
+    /// `output_gradients`, the negated derivative of the cost with respect to each of the network's outputs.  This
+    /// is for costs that depend on all of the outputs together and so can't be written as a `CostFunction`.  Output
+    /// layer costs and errors are left untouched.
+    pub fn backpropagate(&mut self, output_gradients: &[Weight]) {
+        debug_assert_eq!(output_gradients.len(), self.outputs.neuron_gradients.len());
+        let outputs = &mut self.outputs;
+        for ((neuron_gradient, &output_gradient), &output_before_activation) in outputs
+            .neuron_gradients
+            .iter_mut()
+            .zip(output_gradients.iter())
+            .zip(outputs.outputs_before_activation.iter())
+        {
+            *neuron_gradient = output_gradient * outputs.activation_fn.derivative(output_before_activation);
+        }
+
+        self.compute_hidden_layer_gradients();
+    }
+
+    /// Populates neuron gradients for the hidden layers by propagating back the output layer's gradients.
+    fn compute_hidden_layer_gradients(&mut self) {
         let mut output_weights = self.outputs.weights.as_slice();
-        let mut gradient_of_output_neurons = self.outputs.neuron_gradients.as_slice();
+        let mut gradient_of_output_neurons = Cow::Borrowed(self.outputs.neuron_gradients.as_slice());
         for hidden_layer in self.hidden_layers.iter_mut().rev() {
-            hidden_layer.compute_gradients(output_weights, gradient_of_output_neurons);
+            hidden_layer.compute_gradients(output_weights, &gradient_of_output_neurons);
             output_weights = hidden_layer.weights.as_slice();
-            gradient_of_output_neurons = &hidden_layer.neuron_gradients.as_slice();
+            // Spectrally normalized layers scale their raw weights down, and the gradients flowing through them with it
+            let scale = hidden_layer.spectral_norm_scale();
+            gradient_of_output_neurons = if scale == 1. {
+                Cow::Borrowed(hidden_layer.neuron_gradients.as_slice())
+            } else {
+                Cow::Owned(
+                    hidden_layer
+                        .neuron_gradients
+                        .iter()
+                        .map(|gradient| gradient * scale)
+                        .collect(),
+                )
+            };
+        }
+    }
+
+    /// Returns the average of the output layer's costs as computed by the last call to `compute_costs`.
+    pub fn mean_cost(&self) -> Weight {
+        let total_cost = self.outputs.costs.iter().fold(0., |acc, cost| acc + *cost);
+        total_cost / self.outputs.costs.len() as Weight
+    }
+
+    /// Returns the average cost of the output before updating weights.  It would be better to compute again after, but
+    /// that would be too expensive
+    pub fn train_one_example(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+        self.compute_gradients(example, expected);
+        self.update_weights(example, learning_rate);
+
+        // That's it, we've successfully "learned"
+        self.mean_cost()
+    }
+
+    /// Like `train_one_example`, but the input is the embedding for `token_ix`.  The embedding is updated along with
+    /// the rest of the network.
+    pub fn train_one_token(
+        &mut self,
+        embedding: &mut EmbeddingLayer,
+        token_ix: usize,
+        expected: &[Weight],
+        learning_rate: Weight,
+    ) -> Weight {
+        let example = embedding.lookup(token_ix).to_owned();
+        self.compute_gradients(&example, expected);
+
+        // Input gradients have to be computed before the weights they flow back through are updated
+        let mut input_gradients = vec![0.; example.len()];
+        self.compute_input_gradients(&mut input_gradients);
+        embedding.accumulate_gradients(token_ix, &input_gradients);
+        embedding.update_weights(learning_rate);
+
+        self.update_weights(&example, learning_rate);
+        self.mean_cost()
+    }
+
+    /// Once `compute_gradients()` has been called, fills `dst` with the gradient of the cost with respect to each of
+    /// the network's inputs.
+    pub fn compute_input_gradients(&self, dst: &mut [Weight]) {
+        match self.hidden_layers.first() {
+            Some(layer) => layer.compute_input_gradients(dst),
+            None => self.outputs.compute_input_gradients(dst),
         }
+    }
 
-        // Using the gradients computed before, update weights on the output layer
-        let inputs = self.hidden_layers.last().unwrap().outputs.as_slice();
+    /// Updates weights and biases for every layer using the gradients populated by `compute_gradients()` or
+    /// `backpropagate()`.
+    pub fn update_weights(&mut self, example: &[Weight], learning_rate: Weight) {
+        // Using the gradients computed before, update weights on the output layer, which is fed by the example itself
+        // if there are no hidden layers
+        let inputs = match self.hidden_layers.last() {
+            Some(layer) => layer.outputs.as_slice(),
+            None => example,
+        };
         self.outputs.update_weights(inputs, self.learning_rate);
 
         // then update weights + biases for all hidden layers
@@ -741,10 +1361,16 @@ impl Network {
             hidden_layer.update_weights(inputs, self.learning_rate);
             hidden_layer.update_biases(learning_rate);
         }
+    }
 
-        // That's it, we've successfully "learned"
-        let total_cost = self.outputs.costs.iter().fold(0., |acc, cost| acc + *cost);
-        total_cost / self.outputs.costs.len() as Weight
+    /// Calls `DenseLayer::clip_weights` on every hidden layer and clamps the output layer's weights the same way.
+    pub fn clip_all_weights(&mut self, min: Weight, max: Weight) {
+        for hidden_layer in &mut self.hidden_layers {
+            hidden_layer.clip_weights(min, max);
+        }
+        for weight in self.outputs.weights.iter_mut().flatten() {
+            *weight = weight.clamp(min, max);
+        }
     }
 
     // pub fn train_batch(
@@ -816,6 +1442,51 @@ impl Network {
     //     total_cost / self.outputs.costs.len() as Weight
     // }
 
+    /// Computes the cost of the network over a set of examples without updating any weights.
+    pub fn evaluate(&mut self, examples: &[Vec<Weight>], expected: &[Vec<Weight>]) -> EvaluationResult {
+        assert_eq!(examples.len(), expected.len());
+
+        let mut total_loss = 0.;
+        for (example, expected) in examples.iter().zip(expected.iter()) {
+            self.forward_propagate(example);
+            self.outputs.compute_costs(expected);
+            total_loss += self.mean_cost();
+        }
+
+        EvaluationResult {
+            total_loss,
+            mean_loss_per_example: if examples.is_empty() {
+                0.
+            } else {
+                total_loss / examples.len() as Weight
+            },
+            example_count: examples.len(),
+            classification: None,
+        }
+    }
+
+    /// Like `evaluate`, but also treats the outputs and expected outputs as class probabilities and compares the most
+    /// likely class of each.
+    pub fn evaluate_classification(&mut self, examples: &[Vec<Weight>], expected: &[Vec<Weight>]) -> EvaluationResult {
+        let mut result = self.evaluate(examples, expected);
+
+        let mut predicted = Vec::with_capacity(examples.len());
+        for example in examples {
+            predicted.push(predicted_class(self.compute(example)));
+        }
+        let actual: Vec<usize> = expected.iter().map(|expected| predicted_class(expected)).collect();
+        let num_classes = match self.outputs.outputs.len() {
+            1 => 2,
+            output_count => output_count,
+        };
+        result.classification = Some(ClassificationMetrics::from_predictions(
+            &predicted,
+            &actual,
+            num_classes,
+        ));
+        result
+    }
+
     pub fn compute<'a>(&'a mut self, inputs: &[Weight]) -> &'a [Weight] {
         self.forward_propagate(inputs);
         &self.outputs.outputs
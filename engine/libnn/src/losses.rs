@@ -0,0 +1,376 @@
+use crate::{log_sum_exp, parameterized_name, rank_discount, relevance_gain, CostFunction, Weight};
+
+/// Recovers the expected values from outputs and the `expected - output` errors passed to cost functions.
+fn expected_values<'a>(outputs: &'a [Weight], errors: &'a [Weight]) -> impl Iterator<Item = Weight> + 'a {
+    outputs.iter().zip(errors.iter()).map(|(output, error)| output + error)
+}
+
+/// `get_cost` for costs defined by the slice-based methods: the cost of a single output of 0, whose expected value is
+/// the error itself
+fn single_output_cost(cost_fn: &impl CostFunction, error: Weight) -> Weight {
+    let mut cost = [0.];
+    cost_fn.compute_costs(&[0.], &[error], &mut cost);
+    cost[0]
+}
+
+/// `derivative` to go with `single_output_cost`
+fn single_output_derivative(cost_fn: &impl CostFunction, error: Weight) -> Weight {
+    let mut gradient = [0.];
+    cost_fn.compute_error_gradients(&[0.], &[error], &mut gradient);
+    gradient[0]
+}
+
+/// `max(0, 1 - y * f(x))` for labels `y` of -1 or 1, which stops pushing on an example once it is on the right side
+/// of the boundary by a margin of at least 1.  Meant for an output layer with the identity activation.
+///
+/// Labels of 0 and 1 work too: any label that isn't positive is treated as -1.  The cost depends on the label as well
+/// as the error, so it is defined by the slice-based methods.
+pub struct HingeLoss;
+pub static HINGE_LOSS: HingeLoss = HingeLoss;
+
+impl HingeLoss {
+    fn sign_label(label: Weight) -> Weight {
+        if label > 0. {
+            1.
+        } else {
+            -1.
+        }
+    }
+}
+
+impl CostFunction for HingeLoss {
+    fn name(&self) -> &'static str { "hinge" }
+
+    fn get_cost(&self, error: Weight) -> Weight { single_output_cost(self, error) }
+
+    fn derivative(&self, error: Weight) -> Weight { single_output_derivative(self, error) }
+
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        for ((cost, &output), label) in costs
+            .iter_mut()
+            .zip(outputs.iter())
+            .zip(expected_values(outputs, errors))
+        {
+            *cost = (1. - Self::sign_label(label) * output).max(0.);
+        }
+    }
+
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        for ((gradient, &output), label) in dst.iter_mut().zip(outputs.iter()).zip(expected_values(outputs, errors)) {
+            let label = Self::sign_label(label);
+            *gradient = if label * output < 1. { label } else { 0. };
+        }
+    }
+}
+
+/// Multiclass hinge loss for one-hot targets: `sum(max(0, f(y) - f(y*) + 1))` over every class `y` other than the
+/// correct class `y*`.  Each wrong class's term is reported as that output's cost, with 0 for the correct class.
+pub struct MulticlassHingeLoss;
+pub static MULTICLASS_HINGE_LOSS: MulticlassHingeLoss = MulticlassHingeLoss;
+
+impl MulticlassHingeLoss {
+    fn correct_class(outputs: &[Weight], errors: &[Weight]) -> usize {
+        expected_values(outputs, errors)
+            .enumerate()
+            .fold((0, Weight::NEG_INFINITY), |(best_ix, best), (ix, val)| {
+                if val > best {
+                    (ix, val)
+                } else {
+                    (best_ix, best)
+                }
+            })
+            .0
+    }
+}
+
+impl CostFunction for MulticlassHingeLoss {
+    fn name(&self) -> &'static str { "multiclass_hinge" }
+
+    fn get_cost(&self, error: Weight) -> Weight { single_output_cost(self, error) }
+
+    fn derivative(&self, error: Weight) -> Weight { single_output_derivative(self, error) }
+
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        let correct_ix = Self::correct_class(outputs, errors);
+        for (class_ix, (cost, &output)) in costs.iter_mut().zip(outputs.iter()).enumerate() {
+            *cost = if class_ix == correct_ix {
+                0.
+            } else {
+                (output - outputs[correct_ix] + 1.).max(0.)
+            };
+        }
+    }
+
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        let correct_ix = Self::correct_class(outputs, errors);
+        dst.fill(0.);
+        for (class_ix, &output) in outputs.iter().enumerate() {
+            if class_ix != correct_ix && output - outputs[correct_ix] + 1. > 0. {
+                dst[class_ix] -= 1.;
+                dst[correct_ix] += 1.;
+            }
+        }
+    }
+}
+
+/// Keeps `ln` finite when a probability rounds to 0
+const MIN_PROBABILITY: Weight = 1e-7;
+
+/// Focal loss, `-alpha * (1 - p_t)^gamma * ln(p_t)`, for outputs that are probabilities of targets of 0 or 1.  `p_t`
+/// is the probability given to the correct answer, so examples that are already classified confidently contribute
+/// almost nothing and training concentrates on the hard ones.  With `gamma = 0` and `alpha = 1` it is binary
+/// cross-entropy.
+///
+/// Output layers hold a `&'static dyn CostFunction`, so instances are usually declared as `static`s.  `FOCAL_LOSS` has
+/// the values recommended by the paper and is saved as `focal`; any other values are saved as `focal(alpha,gamma)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FocalLoss {
+    pub alpha: Weight,
+    pub gamma: Weight,
+}
+pub static FOCAL_LOSS: FocalLoss = FocalLoss { alpha: 0.25, gamma: 2. };
+
+impl FocalLoss {
+    /// `1 - p_t`, which for targets of 0 or 1 is the size of the error
+    fn miss_probability(error: Weight) -> Weight { error.abs().min(1. - MIN_PROBABILITY) }
+}
+
+impl CostFunction for FocalLoss {
+    fn name(&self) -> &'static str {
+        if *self == FOCAL_LOSS {
+            "focal"
+        } else {
+            parameterized_name("focal", &[self.alpha, self.gamma])
+        }
+    }
+
+    fn get_cost(&self, error: Weight) -> Weight {
+        let miss = Self::miss_probability(error);
+        -self.alpha * miss.powf(self.gamma) * (1. - miss).ln()
+    }
+
+    fn derivative(&self, error: Weight) -> Weight {
+        let miss = Self::miss_probability(error);
+        if miss == 0. {
+            return 0.;
+        }
+        // The cost only depends on the error's size; moving the output towards the target shrinks it
+        let cost_gradient = self.alpha
+            * (miss.powf(self.gamma) / (1. - miss) - self.gamma * miss.powf(self.gamma - 1.) * (1. - miss).ln());
+        cost_gradient * error.signum()
+    }
+}
+
+/// `sum(p * ln(p / q))`, how far the output distribution `q` is from the target distribution `p`.  Both should sum to
+/// 1, for example a softmax output trained on soft labels from a teacher network.
+///
+/// Terms with `p = 0` contribute nothing, and `q` is kept above a small minimum so that the cost stays finite.
+pub struct KlDivergenceLoss;
+pub static KL_DIVERGENCE_LOSS: KlDivergenceLoss = KlDivergenceLoss;
+
+impl CostFunction for KlDivergenceLoss {
+    fn name(&self) -> &'static str { "kl_divergence" }
+
+    fn get_cost(&self, error: Weight) -> Weight { single_output_cost(self, error) }
+
+    fn derivative(&self, error: Weight) -> Weight { single_output_derivative(self, error) }
+
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        for ((cost, &q), p) in costs
+            .iter_mut()
+            .zip(outputs.iter())
+            .zip(expected_values(outputs, errors))
+        {
+            *cost = if p > 0. {
+                p * (p / q.max(MIN_PROBABILITY)).ln()
+            } else {
+                0.
+            };
+        }
+    }
+
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        for ((gradient, &q), p) in dst.iter_mut().zip(outputs.iter()).zip(expected_values(outputs, errors)) {
+            *gradient = if p > 0. { p / q.max(MIN_PROBABILITY) } else { 0. };
+        }
+    }
+}
+
+/// Softmax of `vals`, in place
+fn softmax(vals: &mut [Weight]) {
+    let log_total = log_sum_exp(vals);
+    for val in vals.iter_mut() {
+        *val = (*val - log_total).exp();
+    }
+}
+
+/// ListNet (Cao et al., 2007) for learning to rank: the cross-entropy between the top-one probabilities of the
+/// outputs, read as scores for each item in a list, and those of the target relevance grades.  An item's top-one
+/// probability is the chance that it is ranked first, which for a list of scores is their softmax.
+pub struct ListNetLoss;
+pub static LISTNET_LOSS: ListNetLoss = ListNetLoss;
+
+impl CostFunction for ListNetLoss {
+    fn name(&self) -> &'static str { "listnet" }
+
+    fn get_cost(&self, error: Weight) -> Weight { single_output_cost(self, error) }
+
+    fn derivative(&self, error: Weight) -> Weight { single_output_derivative(self, error) }
+
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        let mut target: Vec<Weight> = expected_values(outputs, errors).collect();
+        softmax(&mut target);
+        let log_total = log_sum_exp(outputs);
+        for ((cost, &output), p) in costs.iter_mut().zip(outputs.iter()).zip(target) {
+            *cost = -p * (output - log_total);
+        }
+    }
+
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        let mut target: Vec<Weight> = expected_values(outputs, errors).collect();
+        softmax(&mut target);
+        dst.copy_from_slice(outputs);
+        softmax(dst);
+        for (gradient, p) in dst.iter_mut().zip(target) {
+            *gradient = p - *gradient;
+        }
+    }
+}
+
+/// Like `signum`, but 0 for 0 so that an item compared with itself contributes nothing
+fn sign(x: Weight) -> Weight {
+    if x > 0. {
+        1.
+    } else if x < 0. {
+        -1.
+    } else {
+        0.
+    }
+}
+
+/// `1 - NDCG` for learning to rank, made differentiable with NeuralNDCG (Pobrotyn & Białobrzeski, 2021).  Sorting
+/// the outputs is replaced with NeuralSort's relaxation, in which rank `i` is a softmax over the items rather than a
+/// single item, so every item contributes to the gain at every rank.  Lower temperatures track the true sort more
+/// closely but give sharper gradients.
+///
+/// This skips the Sinkhorn scaling of the relaxed permutation matrix from the paper, so an item's share of the ranks
+/// need not add up to exactly 1.  The cost reported for each output is the DCG that item loses against its place in
+/// the ideal ranking, normalized so that the costs sum to `1 - NDCG`.
+///
+/// Output layers hold a `&'static dyn CostFunction`, so instances are usually declared as `static`s.  `NDCG_LOSS` has
+/// a temperature of 1 and is saved as `ndcg`; any other temperature is saved as `ndcg(temperature)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NdcgLoss {
+    pub temperature: Weight,
+}
+pub static NDCG_LOSS: NdcgLoss = NdcgLoss { temperature: 1. };
+
+impl NdcgLoss {
+    /// NeuralSort's relaxed permutation matrix, with one row per rank and one column per item
+    fn relaxed_permutation(&self, scores: &[Weight]) -> Vec<Vec<Weight>> {
+        let count = scores.len();
+        let total_distances: Vec<Weight> = scores
+            .iter()
+            .map(|&score| scores.iter().map(|&other| (score - other).abs()).sum())
+            .collect();
+        (0..count)
+            .map(|rank| {
+                let scale = (count - 1) as Weight - 2. * rank as Weight;
+                let mut row: Vec<Weight> = scores
+                    .iter()
+                    .zip(total_distances.iter())
+                    .map(|(&score, &total_distance)| (scale * score - total_distance) / self.temperature)
+                    .collect();
+                softmax(&mut row);
+                row
+            })
+            .collect()
+    }
+
+    /// Returns the gain of each item and the DCG of the ideal ranking.
+    fn gains(outputs: &[Weight], errors: &[Weight]) -> (Vec<Weight>, Weight) {
+        let gains: Vec<Weight> = expected_values(outputs, errors).map(relevance_gain).collect();
+        let mut sorted = gains.clone();
+        sorted.sort_by(|a, b| b.total_cmp(a));
+        let ideal_dcg = sorted
+            .iter()
+            .enumerate()
+            .map(|(rank, gain)| rank_discount(rank) * gain)
+            .sum();
+        (gains, ideal_dcg)
+    }
+}
+
+impl CostFunction for NdcgLoss {
+    fn name(&self) -> &'static str {
+        if *self == NDCG_LOSS {
+            "ndcg"
+        } else {
+            parameterized_name("ndcg", &[self.temperature])
+        }
+    }
+
+    fn get_cost(&self, error: Weight) -> Weight { single_output_cost(self, error) }
+
+    fn derivative(&self, error: Weight) -> Weight { single_output_derivative(self, error) }
+
+    fn compute_costs(&self, outputs: &[Weight], errors: &[Weight], costs: &mut [Weight]) {
+        let (gains, ideal_dcg) = Self::gains(outputs, errors);
+        if ideal_dcg <= 0. {
+            costs.fill(0.);
+            return;
+        }
+        let mut ideal_order: Vec<usize> = (0..gains.len()).collect();
+        ideal_order.sort_by(|&a, &b| gains[b].total_cmp(&gains[a]));
+        let mut ideal_discounts = vec![0.; gains.len()];
+        for (rank, &item_ix) in ideal_order.iter().enumerate() {
+            ideal_discounts[item_ix] = rank_discount(rank);
+        }
+
+        let permutation = self.relaxed_permutation(outputs);
+        for (item_ix, cost) in costs.iter_mut().enumerate() {
+            let soft_discount: Weight = permutation
+                .iter()
+                .enumerate()
+                .map(|(rank, row)| rank_discount(rank) * row[item_ix])
+                .sum();
+            *cost = (ideal_discounts[item_ix] - soft_discount) * gains[item_ix] / ideal_dcg;
+        }
+    }
+
+    fn compute_error_gradients(&self, outputs: &[Weight], errors: &[Weight], dst: &mut [Weight]) {
+        let (gains, ideal_dcg) = Self::gains(outputs, errors);
+        dst.fill(0.);
+        if ideal_dcg <= 0. {
+            return;
+        }
+        let count = outputs.len();
+        let permutation = self.relaxed_permutation(outputs);
+
+        // NDCG = sum over ranks of discount * sum(row * gains) / ideal DCG.  `logit_gradients[rank][item]` is its
+        // gradient with respect to the row's softmax inputs.
+        for (rank, row) in permutation.iter().enumerate() {
+            let expected_gain: Weight = row.iter().zip(gains.iter()).map(|(p, gain)| p * gain).sum();
+            let weight = rank_discount(rank) / ideal_dcg / self.temperature;
+            let scale = (count - 1) as Weight - 2. * rank as Weight;
+            let logit_gradients: Vec<Weight> = row
+                .iter()
+                .zip(gains.iter())
+                .map(|(p, gain)| weight * p * (gain - expected_gain))
+                .collect();
+
+            // Each softmax input is `scale * s[j] - sum_k |s[j] - s[k]|`, scaled by the temperature.  Raising NDCG
+            // lowers the cost, so these already point downhill.
+            for (item_ix, gradient) in dst.iter_mut().enumerate() {
+                let score = outputs[item_ix];
+                let own_distance_gradient: Weight = outputs.iter().map(|&other| sign(score - other)).sum();
+                let others_distance_gradient: Weight = outputs
+                    .iter()
+                    .zip(logit_gradients.iter())
+                    .map(|(&other, &logit_gradient)| logit_gradient * sign(other - score))
+                    .sum();
+                *gradient += logit_gradients[item_ix] * (scale - own_distance_gradient) + others_distance_gradient;
+            }
+        }
+    }
+}
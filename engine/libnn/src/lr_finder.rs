@@ -0,0 +1,90 @@
+use crate::{Network, Trainer, Weight};
+
+/// Training stops early once the loss exceeds the best loss seen so far by this factor
+const DIVERGENCE_FACTOR: Weight = 4.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LrFinderResult {
+    pub learning_rates: Vec<Weight>,
+    /// Loss over the data before the update made with the learning rate at the same index
+    pub losses: Vec<Weight>,
+    /// Whether training was stopped early because the loss blew up
+    pub diverged: bool,
+    /// The learning rate at which the loss was falling fastest
+    pub suggested_lr: Weight,
+}
+
+/// Finds a reasonable learning rate by training with an exponentially increasing learning rate and watching the loss.
+pub struct LrFinder;
+
+impl LrFinder {
+    /// Trains a copy of `network` for `num_iters` steps, each a single update on the mean gradient over all of the
+    /// examples, raising the learning rate from `start_lr` to `end_lr` along the way.  `network` itself is left
+    /// untouched.
+    pub fn run(
+        network: &Network,
+        examples: &[Vec<Weight>],
+        expected: &[Vec<Weight>],
+        start_lr: Weight,
+        end_lr: Weight,
+        num_iters: usize,
+    ) -> LrFinderResult {
+        assert_eq!(examples.len(), expected.len());
+        assert!(!examples.is_empty(), "Need at least one example");
+        assert!(
+            0. < start_lr && start_lr < end_lr,
+            "Learning rates must be positive and increasing"
+        );
+        assert!(num_iters >= 2, "Need at least 2 iterations");
+
+        let mut trainer = Trainer::new(network.clone());
+        trainer.set_accumulation_steps(examples.len());
+
+        let growth = (end_lr / start_lr).powf(1. / (num_iters - 1) as Weight);
+        let mut learning_rates = Vec::with_capacity(num_iters);
+        let mut losses = Vec::with_capacity(num_iters);
+        let mut best_loss = Weight::INFINITY;
+        let mut diverged = false;
+        for iter in 0..num_iters {
+            let learning_rate = start_lr * growth.powi(iter as i32);
+            let mut total_loss = 0.;
+            for (example, expected) in examples.iter().zip(expected.iter()) {
+                total_loss += trainer.train_one_example(example, expected, learning_rate);
+            }
+            let loss = total_loss / examples.len() as Weight;
+
+            learning_rates.push(learning_rate);
+            losses.push(loss);
+            if !loss.is_finite() || loss > DIVERGENCE_FACTOR * best_loss {
+                diverged = true;
+                break;
+            }
+            best_loss = best_loss.min(loss);
+        }
+
+        let suggested_lr = Self::steepest_descent_lr(&learning_rates, &losses);
+        LrFinderResult {
+            learning_rates,
+            losses,
+            diverged,
+            suggested_lr,
+        }
+    }
+
+    /// Picks the learning rate where the loss falls most steeply against the log of the learning rate.
+    fn steepest_descent_lr(learning_rates: &[Weight], losses: &[Weight]) -> Weight {
+        let mut best_ix = 0;
+        let mut steepest_slope = Weight::INFINITY;
+        for ix in 0..losses.len().saturating_sub(1) {
+            if !losses[ix + 1].is_finite() {
+                break;
+            }
+            let slope = (losses[ix + 1] - losses[ix]) / (learning_rates[ix + 1].ln() - learning_rates[ix].ln());
+            if slope < steepest_slope {
+                steepest_slope = slope;
+                best_ix = ix;
+            }
+        }
+        learning_rates[best_ix]
+    }
+}
@@ -0,0 +1,43 @@
+use crate::Weight;
+
+/// Picks the learning rate to use for each training step.
+pub trait LrScheduler {
+    /// Returns the learning rate for `step`, counting from 0.
+    fn get_learning_rate(&self, step: usize) -> Weight;
+}
+
+pub struct ConstantLr(pub Weight);
+
+impl LrScheduler for ConstantLr {
+    fn get_learning_rate(&self, _step: usize) -> Weight { self.0 }
+}
+
+/// Multiplies the learning rate by `decay_rate` every step.
+pub struct ExponentialDecayLr {
+    pub initial_lr: Weight,
+    pub decay_rate: Weight,
+}
+
+impl LrScheduler for ExponentialDecayLr {
+    fn get_learning_rate(&self, step: usize) -> Weight { self.initial_lr * self.decay_rate.powi(step as i32) }
+}
+
+/// Linearly ramps the learning rate from `initial_lr` to `peak_lr` over `warmup_steps` and then hands off to
+/// `decay_scheduler`, which sees steps counted from the end of the warm-up.
+pub struct WarmupLrScheduler {
+    pub warmup_steps: usize,
+    pub initial_lr: Weight,
+    pub peak_lr: Weight,
+    pub decay_scheduler: Box<dyn LrScheduler>,
+}
+
+impl LrScheduler for WarmupLrScheduler {
+    fn get_learning_rate(&self, step: usize) -> Weight {
+        if step >= self.warmup_steps {
+            return self.decay_scheduler.get_learning_rate(step - self.warmup_steps);
+        }
+
+        let progress = step as Weight / self.warmup_steps as Weight;
+        self.initial_lr + (self.peak_lr - self.initial_lr) * progress
+    }
+}
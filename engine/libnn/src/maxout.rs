@@ -0,0 +1,104 @@
+use crate::Weight;
+
+/// A dense layer whose neurons each output the largest of `k` affine functions of the input instead of applying an
+/// activation function.  A neuron can learn any convex piecewise linear function with up to `k` pieces, ReLU and
+/// absolute value included.
+///
+/// Rows `neuron_ix * k..(neuron_ix + 1) * k` of `weights` and `biases` belong to neuron `neuron_ix`.  Only the piece
+/// that produced a neuron's output receives its gradient.
+#[derive(Clone)]
+pub struct MaxoutDenseLayer {
+    pub k: usize,
+    pub weights: Vec<Vec<Weight>>,
+    pub biases: Vec<Weight>,
+    pub outputs: Vec<Weight>,
+    /// For each neuron, the row of `weights` that produced its output in the last call to `forward_propagate`
+    pub max_indices: Vec<usize>,
+    pub neuron_gradients: Vec<Weight>,
+}
+
+impl MaxoutDenseLayer {
+    pub fn new(
+        neuron_count: usize,
+        input_count: usize,
+        k: usize,
+        init_weights: &mut impl FnMut(usize, usize) -> Weight,
+        init_biases: &mut impl FnMut(usize) -> Weight,
+    ) -> Self {
+        assert!(k > 0, "Maxout neurons need at least one piece");
+        let row_count = neuron_count * k;
+        let weights = (0..row_count)
+            .map(|row_ix| {
+                (0..input_count)
+                    .map(|input_ix| init_weights(row_ix, input_ix))
+                    .collect()
+            })
+            .collect();
+        let biases = (0..row_count).map(init_biases).collect();
+
+        MaxoutDenseLayer {
+            k,
+            weights,
+            biases,
+            outputs: vec![0.; neuron_count],
+            max_indices: vec![0; neuron_count],
+            neuron_gradients: vec![0.; neuron_count],
+        }
+    }
+
+    pub fn forward_propagate(&mut self, inputs: &[Weight]) {
+        debug_assert_eq!(self.weights[0].len(), inputs.len());
+        for neuron_ix in 0..self.outputs.len() {
+            let mut max = Weight::NEG_INFINITY;
+            let mut max_ix = neuron_ix * self.k;
+            for row_ix in neuron_ix * self.k..(neuron_ix + 1) * self.k {
+                let weight_sum: Weight = self.weights[row_ix].iter().zip(inputs.iter()).map(|(w, x)| w * x).sum();
+                let val = weight_sum + self.biases[row_ix];
+                if val > max {
+                    max = val;
+                    max_ix = row_ix;
+                }
+            }
+            self.outputs[neuron_ix] = max;
+            self.max_indices[neuron_ix] = max_ix;
+        }
+    }
+
+    /// Calculates the gradients for each neuron and populates `self.neuron_gradients`.  Every piece is linear, so
+    /// unlike `DenseLayer::compute_gradients` there is no activation derivative to apply.
+    pub fn compute_gradients(&mut self, output_weights: &[Vec<Weight>], gradient_of_output_neurons: &[Weight]) {
+        debug_assert_eq!(output_weights.len(), gradient_of_output_neurons.len());
+        for (neuron_ix, neuron_gradient) in self.neuron_gradients.iter_mut().enumerate() {
+            *neuron_gradient = output_weights
+                .iter()
+                .zip(gradient_of_output_neurons.iter())
+                .map(|(output_neuron, &gradient)| output_neuron[neuron_ix] * gradient)
+                .sum();
+        }
+    }
+
+    /// Once `compute_gradients()` has been called, fills `dst` with the gradient of the cost with respect to each of
+    /// this layer's inputs.
+    pub fn compute_input_gradients(&self, dst: &mut [Weight]) {
+        dst.fill(0.);
+        for (&row_ix, &neuron_gradient) in self.max_indices.iter().zip(self.neuron_gradients.iter()) {
+            for (input_gradient, &weight) in dst.iter_mut().zip(self.weights[row_ix].iter()) {
+                *input_gradient += weight * neuron_gradient;
+            }
+        }
+    }
+
+    pub fn update_weights(&mut self, inputs: &[Weight], learning_rate: Weight) {
+        for (&row_ix, &neuron_gradient) in self.max_indices.iter().zip(self.neuron_gradients.iter()) {
+            for (weight, &input) in self.weights[row_ix].iter_mut().zip(inputs.iter()) {
+                *weight += learning_rate * neuron_gradient * input;
+            }
+        }
+    }
+
+    pub fn update_biases(&mut self, learning_rate: Weight) {
+        for (&row_ix, &neuron_gradient) in self.max_indices.iter().zip(self.neuron_gradients.iter()) {
+            self.biases[row_ix] += learning_rate * neuron_gradient;
+        }
+    }
+}
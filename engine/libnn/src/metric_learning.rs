@@ -0,0 +1,149 @@
+use crate::Weight;
+
+pub(crate) fn euclidean_distance(a: &[Weight], b: &[Weight]) -> Weight {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<Weight>()
+        .sqrt()
+}
+
+/// `y * d^2 + (1 - y) * max(0, margin - d)^2` for a pair of embeddings `d` apart, where `y` is 1 if they should be
+/// similar and 0 if not.  Similar pairs are pulled together and dissimilar ones pushed apart until they are at least
+/// `margin` apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContrastiveLoss {
+    pub margin: Weight,
+}
+
+impl ContrastiveLoss {
+    pub fn new(margin: Weight) -> Self { ContrastiveLoss { margin } }
+
+    /// Returns the cost for the pair along with the negated gradients with respect to `first` and `second`.
+    pub fn compute(&self, first: &[Weight], second: &[Weight], similar: bool) -> (Weight, Vec<Weight>, Vec<Weight>) {
+        debug_assert_eq!(first.len(), second.len());
+        let distance = euclidean_distance(first, second);
+        // The gradient of the cost with respect to `first` is `scale * (first - second)`
+        let (cost, scale) = if similar {
+            (distance * distance, 2.)
+        } else if distance < self.margin && distance > 0. {
+            let shortfall = self.margin - distance;
+            (shortfall * shortfall, -2. * shortfall / distance)
+        } else {
+            ((self.margin - distance).max(0.).powi(2), 0.)
+        };
+
+        let first_gradients: Vec<Weight> = first.iter().zip(second.iter()).map(|(a, b)| -scale * (a - b)).collect();
+        let second_gradients = first_gradients.iter().map(|gradient| -gradient).collect();
+        (cost, first_gradients, second_gradients)
+    }
+}
+
+/// The output of a network that embeds pairs of examples, usually the same network run on each half of the pair.
+///
+/// `compute_costs` fills in the gradients for each embedding, which can then be passed to
+/// `Network::backpropagate` after running the network on the matching example again.
+#[derive(Clone, Debug)]
+pub struct PairOutputLayer {
+    pub loss: ContrastiveLoss,
+    pub cost: Weight,
+    pub first_gradients: Vec<Weight>,
+    pub second_gradients: Vec<Weight>,
+}
+
+impl PairOutputLayer {
+    pub fn new(loss: ContrastiveLoss) -> Self {
+        PairOutputLayer {
+            loss,
+            cost: 0.,
+            first_gradients: Vec::new(),
+            second_gradients: Vec::new(),
+        }
+    }
+
+    pub fn compute_costs(&mut self, first: &[Weight], second: &[Weight], similar: bool) -> Weight {
+        let (cost, first_gradients, second_gradients) = self.loss.compute(first, second, similar);
+        self.cost = cost;
+        self.first_gradients = first_gradients;
+        self.second_gradients = second_gradients;
+        cost
+    }
+}
+
+/// `max(0, d(a, p) - d(a, n) + margin)` for an anchor `a`, a positive `p` of the same class, and a negative `n` of a
+/// different class, which is 0 once the negative is at least `margin` further from the anchor than the positive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TripletLoss {
+    pub margin: Weight,
+}
+
+impl TripletLoss {
+    pub fn new(margin: Weight) -> Self { TripletLoss { margin } }
+
+    /// Returns the cost for the triplet along with the negated gradients with respect to the anchor, positive, and
+    /// negative embeddings.
+    pub fn compute(
+        &self,
+        anchor: &[Weight],
+        positive: &[Weight],
+        negative: &[Weight],
+    ) -> (Weight, Vec<Weight>, Vec<Weight>, Vec<Weight>) {
+        let positive_distance = euclidean_distance(anchor, positive);
+        let negative_distance = euclidean_distance(anchor, negative);
+        let cost = (positive_distance - negative_distance + self.margin).max(0.);
+        if cost == 0. {
+            let zeros = vec![0.; anchor.len()];
+            return (cost, zeros.clone(), zeros.clone(), zeros);
+        }
+
+        // The gradient of `d(a, b)` with respect to `a` is the unit vector from `b` to `a`
+        let unit = |from: &[Weight], distance: Weight| -> Vec<Weight> {
+            let distance = distance.max(Weight::EPSILON);
+            anchor
+                .iter()
+                .zip(from.iter())
+                .map(|(a, b)| (a - b) / distance)
+                .collect()
+        };
+        let towards_anchor_from_positive = unit(positive, positive_distance);
+        let towards_anchor_from_negative = unit(negative, negative_distance);
+
+        let anchor_gradients = towards_anchor_from_negative
+            .iter()
+            .zip(towards_anchor_from_positive.iter())
+            .map(|(from_negative, from_positive)| from_negative - from_positive)
+            .collect();
+        let negative_gradients = towards_anchor_from_negative.iter().map(|val| -val).collect();
+        (cost, anchor_gradients, towards_anchor_from_positive, negative_gradients)
+    }
+}
+
+/// Picks triplets from a batch of embeddings that are worth training on.  Every pair of distinct examples with the
+/// same label is used as an anchor and positive, along with the negative closest to the anchor.  Returns each triplet
+/// as indices of (anchor, positive, negative).
+pub fn online_triplet_mining(embeddings: &[Vec<Weight>], labels: &[usize]) -> Vec<(usize, usize, usize)> {
+    debug_assert_eq!(embeddings.len(), labels.len());
+    let mut triplets = Vec::new();
+    for (anchor_ix, anchor) in embeddings.iter().enumerate() {
+        let hardest_negative = embeddings
+            .iter()
+            .enumerate()
+            .filter(|&(ix, _)| labels[ix] != labels[anchor_ix])
+            .map(|(ix, negative)| (ix, euclidean_distance(anchor, negative)))
+            .fold(None, |closest: Option<(usize, Weight)>, (ix, distance)| match closest {
+                Some((_, closest_distance)) if closest_distance <= distance => closest,
+                _ => Some((ix, distance)),
+            });
+        let negative_ix = match hardest_negative {
+            Some((ix, _)) => ix,
+            None => continue,
+        };
+
+        for positive_ix in 0..embeddings.len() {
+            if positive_ix != anchor_ix && labels[positive_ix] == labels[anchor_ix] {
+                triplets.push((anchor_ix, positive_ix, negative_ix));
+            }
+        }
+    }
+    triplets
+}
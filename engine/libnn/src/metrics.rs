@@ -0,0 +1,189 @@
+use crate::Weight;
+
+/// Aggregated cost of a network over a set of examples, as returned by `Network::evaluate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvaluationResult {
+    /// Sum of the mean output cost of every example
+    pub total_loss: Weight,
+    pub mean_loss_per_example: Weight,
+    pub example_count: usize,
+    /// Only computed by `Network::evaluate_classification`
+    pub classification: Option<ClassificationMetrics>,
+}
+
+/// Precision, recall, and F1 scores of a classifier, derived from its confusion matrix.
+///
+/// Classes that are never predicted have a precision of 0, and classes that never occur have a recall of 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassificationMetrics {
+    /// `confusion_matrix[actual][predicted]` counts the examples of class `actual` that were predicted as `predicted`
+    pub confusion_matrix: Vec<Vec<usize>>,
+    pub precision: Vec<Weight>,
+    pub recall: Vec<Weight>,
+    pub f1: Vec<Weight>,
+    pub accuracy: Weight,
+    pub macro_precision: Weight,
+    pub macro_recall: Weight,
+    pub macro_f1: Weight,
+    pub micro_precision: Weight,
+    pub micro_recall: Weight,
+    pub micro_f1: Weight,
+}
+
+fn ratio(numerator: usize, denominator: usize) -> Weight {
+    if denominator == 0 {
+        0.
+    } else {
+        numerator as Weight / denominator as Weight
+    }
+}
+
+fn f1_score(precision: Weight, recall: Weight) -> Weight {
+    if precision + recall == 0. {
+        0.
+    } else {
+        2. * precision * recall / (precision + recall)
+    }
+}
+
+fn mean(vals: &[Weight]) -> Weight { vals.iter().sum::<Weight>() / vals.len() as Weight }
+
+impl ClassificationMetrics {
+    pub fn from_predictions(predicted: &[usize], actual: &[usize], num_classes: usize) -> Self {
+        assert_eq!(predicted.len(), actual.len());
+        assert!(num_classes > 0, "Must have at least one class");
+
+        let mut confusion_matrix = vec![vec![0; num_classes]; num_classes];
+        for (&predicted, &actual) in predicted.iter().zip(actual.iter()) {
+            confusion_matrix[actual][predicted] += 1;
+        }
+
+        let mut precision = Vec::with_capacity(num_classes);
+        let mut recall = Vec::with_capacity(num_classes);
+        let mut f1 = Vec::with_capacity(num_classes);
+        let mut total_true_positives = 0;
+        for class_ix in 0..num_classes {
+            let true_positives = confusion_matrix[class_ix][class_ix];
+            let predicted_count: usize = confusion_matrix.iter().map(|row| row[class_ix]).sum();
+            let actual_count: usize = confusion_matrix[class_ix].iter().sum();
+            total_true_positives += true_positives;
+
+            let class_precision = ratio(true_positives, predicted_count);
+            let class_recall = ratio(true_positives, actual_count);
+            precision.push(class_precision);
+            recall.push(class_recall);
+            f1.push(f1_score(class_precision, class_recall));
+        }
+
+        // Every example is predicted as exactly one class, so the total false positives and false negatives are both
+        // the number of misclassified examples and all micro averages equal the accuracy.
+        let accuracy = ratio(total_true_positives, predicted.len());
+        ClassificationMetrics {
+            macro_precision: mean(&precision),
+            macro_recall: mean(&recall),
+            macro_f1: mean(&f1),
+            micro_precision: accuracy,
+            micro_recall: accuracy,
+            micro_f1: accuracy,
+            confusion_matrix,
+            precision,
+            recall,
+            f1,
+            accuracy,
+        }
+    }
+}
+
+/// Keeps MAPE finite when actual values are 0
+const MAPE_EPSILON: Weight = 1e-8;
+
+/// Error metrics for a forecast as a whole, as opposed to the per-example cost minimized during training.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastMetrics {
+    pub rmse: Weight,
+    pub mae: Weight,
+    /// Mean absolute percentage error, as a percentage
+    pub mape: Weight,
+    /// Coefficient of determination.  A constant `actual` series has no variance to explain, so it scores 1 for a
+    /// perfect forecast and 0 otherwise.
+    pub r2_score: Weight,
+}
+
+impl ForecastMetrics {
+    pub fn compute(predicted: &[Weight], actual: &[Weight]) -> Self {
+        assert_eq!(predicted.len(), actual.len());
+        assert!(
+            !actual.is_empty(),
+            "Need at least one value to compute forecast metrics"
+        );
+
+        let count = actual.len() as Weight;
+        let mut squared_error_sum = 0.;
+        let mut abs_error_sum = 0.;
+        let mut abs_percentage_error_sum = 0.;
+        for (&predicted, &actual) in predicted.iter().zip(actual.iter()) {
+            let error = actual - predicted;
+            squared_error_sum += error * error;
+            abs_error_sum += error.abs();
+            abs_percentage_error_sum += error.abs() / actual.abs().max(MAPE_EPSILON);
+        }
+
+        let actual_mean = actual.iter().sum::<Weight>() / count;
+        let total_sum_of_squares: Weight = actual.iter().map(|&actual| (actual - actual_mean).powi(2)).sum();
+        let r2_score = if total_sum_of_squares > 0. {
+            1. - squared_error_sum / total_sum_of_squares
+        } else if squared_error_sum == 0. {
+            1.
+        } else {
+            0.
+        };
+
+        ForecastMetrics {
+            rmse: (squared_error_sum / count).sqrt(),
+            mae: abs_error_sum / count,
+            mape: 100. * abs_percentage_error_sum / count,
+            r2_score,
+        }
+    }
+}
+
+/// Interprets `outputs` as class probabilities, returning the index of the most likely class.  A single output is
+/// treated as the probability of class 1 in a binary classifier.
+pub fn predicted_class(outputs: &[Weight]) -> usize {
+    if outputs.len() == 1 {
+        return if outputs[0] >= 0.5 { 1 } else { 0 };
+    }
+
+    let mut best_ix = 0;
+    for (ix, &output) in outputs.iter().enumerate() {
+        if output > outputs[best_ix] {
+            best_ix = ix;
+        }
+    }
+    best_ix
+}
+
+/// The gain of an item with relevance grade `relevance` in discounted cumulative gain, `2^relevance - 1`
+pub(crate) fn relevance_gain(relevance: Weight) -> Weight { relevance.exp2() - 1. }
+
+/// How much DCG an item contributes at 0-based `rank`, `1 / log2(rank + 2)`
+pub(crate) fn rank_discount(rank: usize) -> Weight { 1. / ((rank + 2) as Weight).log2() }
+
+/// Normalized discounted cumulative gain of ranking items by `scores`, from 0 to 1 for the ideal ordering.  Lists
+/// where nothing is relevant score 1, since every ordering of them is ideal.
+pub fn ndcg(scores: &[Weight], relevance: &[Weight]) -> Weight {
+    debug_assert_eq!(scores.len(), relevance.len());
+    let dcg = |mut order: Vec<usize>, key: &[Weight]| -> Weight {
+        order.sort_by(|&a, &b| key[b].total_cmp(&key[a]));
+        order
+            .iter()
+            .enumerate()
+            .map(|(rank, &item_ix)| rank_discount(rank) * relevance_gain(relevance[item_ix]))
+            .sum()
+    };
+    let ideal = dcg((0..relevance.len()).collect(), relevance);
+    if ideal <= 0. {
+        return 1.;
+    }
+    dcg((0..scores.len()).collect(), scores) / ideal
+}
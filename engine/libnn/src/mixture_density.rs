@@ -0,0 +1,132 @@
+use rand::Rng;
+
+use crate::{augmentation::standard_normal, log_sum_exp, Network, Weight};
+
+const LN_SQRT_2PI: Weight = 0.918_938_5;
+
+/// Parameters of a 1D Gaussian mixture
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixtureParameters {
+    /// Mixing coefficients, which sum to 1
+    pub pis: Vec<Weight>,
+    pub mus: Vec<Weight>,
+    pub sigmas: Vec<Weight>,
+}
+
+impl MixtureParameters {
+    pub fn mean(&self) -> Weight { self.pis.iter().zip(self.mus.iter()).map(|(pi, mu)| pi * mu).sum() }
+}
+
+/// Interprets a network's raw outputs as the parameters of a mixture of `num_components` Gaussians, so that a
+/// regression network predicts a whole distribution over its target rather than a single point.
+///
+/// The network needs `3 * num_components` outputs with the identity activation function.  The first `num_components`
+/// are softmaxed into the mixing coefficients, the next `num_components` are the means, and the last `num_components`
+/// are the logs of the standard deviations.  The cost is the negative log-likelihood of the target under the mixture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixtureDensityOutput {
+    pub num_components: usize,
+}
+
+impl MixtureDensityOutput {
+    pub fn new(num_components: usize) -> Self {
+        assert!(num_components > 0, "Mixture must have at least one component");
+        MixtureDensityOutput { num_components }
+    }
+
+    pub fn output_count(&self) -> usize { 3 * self.num_components }
+
+    pub fn parameters(&self, outputs: &[Weight]) -> MixtureParameters {
+        debug_assert_eq!(outputs.len(), self.output_count());
+        let k = self.num_components;
+        let logits = &outputs[..k];
+        let max_logit = logits.iter().cloned().fold(Weight::NEG_INFINITY, Weight::max);
+        let mut pis: Vec<Weight> = logits.iter().map(|&logit| (logit - max_logit).exp()).collect();
+        let total: Weight = pis.iter().sum();
+        for pi in &mut pis {
+            *pi /= total;
+        }
+
+        MixtureParameters {
+            pis,
+            mus: outputs[k..2 * k].to_owned(),
+            sigmas: outputs[2 * k..].iter().map(|log_sigma| log_sigma.exp()).collect(),
+        }
+    }
+
+    /// Returns the log of each component's mixing coefficient times its density at `target`.
+    fn weighted_log_densities(&self, outputs: &[Weight], target: Weight) -> Vec<Weight> {
+        let k = self.num_components;
+        let logits = &outputs[..k];
+        let log_normalizer = log_sum_exp(logits);
+
+        (0..k)
+            .map(|component_ix| {
+                let mu = outputs[k + component_ix];
+                let log_sigma = outputs[2 * k + component_ix];
+                let z = (target - mu) / log_sigma.exp();
+                logits[component_ix] - log_normalizer - LN_SQRT_2PI - log_sigma - 0.5 * z * z
+            })
+            .collect()
+    }
+
+    pub fn negative_log_likelihood(&self, outputs: &[Weight], target: Weight) -> Weight {
+        -log_sum_exp(&self.weighted_log_densities(outputs, target))
+    }
+
+    /// Fills `dst` with the negated gradient of the negative log-likelihood with respect to each raw output, ready to
+    /// be passed to `Network::backpropagate`.
+    pub fn compute_output_gradients(&self, outputs: &[Weight], target: Weight, dst: &mut [Weight]) {
+        debug_assert_eq!(dst.len(), self.output_count());
+        let k = self.num_components;
+        let log_densities = self.weighted_log_densities(outputs, target);
+        let log_likelihood = log_sum_exp(&log_densities);
+        let params = self.parameters(outputs);
+
+        for component_ix in 0..k {
+            // Posterior probability that `target` came from this component
+            let responsibility = (log_densities[component_ix] - log_likelihood).exp();
+            let sigma = params.sigmas[component_ix];
+            let z = (target - params.mus[component_ix]) / sigma;
+
+            dst[component_ix] = responsibility - params.pis[component_ix];
+            dst[k + component_ix] = responsibility * z / sigma;
+            dst[2 * k + component_ix] = responsibility * (z * z - 1.);
+        }
+    }
+
+    /// Draws a value from the mixture described by `outputs`.
+    pub fn sample(&self, outputs: &[Weight], rng: &mut impl Rng) -> Weight {
+        let params = self.parameters(outputs);
+        let mut threshold: Weight = rng.gen();
+        let mut component_ix = self.num_components - 1;
+        for (ix, &pi) in params.pis.iter().enumerate() {
+            if threshold < pi {
+                component_ix = ix;
+                break;
+            }
+            threshold -= pi;
+        }
+
+        params.mus[component_ix] + params.sigmas[component_ix] * standard_normal(rng)
+    }
+
+    /// Trains `network` to assign more likelihood to `target` given `example`, returning the negative log-likelihood
+    /// from before the weights were updated.
+    pub fn train_one_example(
+        &self,
+        network: &mut Network,
+        example: &[Weight],
+        target: Weight,
+        learning_rate: Weight,
+    ) -> Weight {
+        network.forward_propagate(example);
+        let outputs = network.outputs.outputs.clone();
+        let mut output_gradients = vec![0.; outputs.len()];
+        self.compute_output_gradients(&outputs, target, &mut output_gradients);
+
+        network.backpropagate(&output_gradients);
+        network.update_weights(example, learning_rate);
+        self.negative_log_likelihood(&outputs, target)
+    }
+}
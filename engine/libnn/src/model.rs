@@ -0,0 +1,139 @@
+use std::io::{self, Read, Write};
+
+use crate::{Network, Weight};
+
+/// Adds the gradient of each weight in a layer to the front of `gradients` and returns the rest of them
+fn accumulate_outer_product<'a>(
+    gradients: &'a mut [Weight],
+    neuron_gradients: &[Weight],
+    inputs: &[Weight],
+) -> &'a mut [Weight] {
+    let (layer_gradients, rest) = gradients.split_at_mut(neuron_gradients.len() * inputs.len());
+    for (neuron_dst, &neuron_gradient) in layer_gradients.chunks_mut(inputs.len()).zip(neuron_gradients.iter()) {
+        for (gradient, &input) in neuron_dst.iter_mut().zip(inputs.iter()) {
+            *gradient += neuron_gradient * input;
+        }
+    }
+    rest
+}
+
+/// Steps `params` by `scale` times the front of `gradients` and returns the rest of them
+fn apply_gradients<'a>(params: &mut [Weight], gradients: &'a [Weight], scale: Weight) -> &'a [Weight] {
+    let (param_gradients, rest) = gradients.split_at(params.len());
+    for (param, &gradient) in params.iter_mut().zip(param_gradients.iter()) {
+        *param += scale * gradient;
+    }
+    rest
+}
+
+/// What code that trains or combines networks needs from one, so that it can be written once for every kind of
+/// network rather than just `Network`.
+pub trait Model {
+    /// Runs the model on `inputs`, leaving whatever state it needs for a backward pass, and returns its outputs.
+    fn forward_propagate(&mut self, inputs: &[Weight]) -> &[Weight];
+
+    /// Returns the outputs for `inputs` as an owned vector.
+    fn predict(&mut self, inputs: &[Weight]) -> Vec<Weight> { self.forward_propagate(inputs).to_vec() }
+
+    /// Takes one training step on a single example and returns the mean cost from before the update.
+    fn train_one_sample(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight;
+
+    /// Runs a backward pass on one example without changing any parameters, adds the gradient of every trainable
+    /// parameter to the matching entry of `gradients` and returns the mean cost.  Gradients point in the direction
+    /// that lowers the cost and are laid out in whatever order the model chooses.  An empty `gradients` is first
+    /// filled with `param_count` zeroes.
+    fn accumulate_gradients(&mut self, example: &[Weight], expected: &[Weight], gradients: &mut Vec<Weight>) -> Weight;
+
+    /// Steps every trainable parameter by `scale` times its entry in `gradients`, laid out as by
+    /// `accumulate_gradients`.
+    fn apply_gradients(&mut self, gradients: &[Weight], scale: Weight);
+
+    /// The neuron gradients of each layer from the last backward pass, checked by `GradientMonitor`.  Models without
+    /// layers of neurons have none to report.
+    fn layer_gradients(&self) -> Vec<&[Weight]> { Vec::new() }
+
+    /// The number of trainable weights and biases
+    fn param_count(&self) -> usize;
+
+    fn save(&self, writer: &mut impl Write) -> io::Result<()>;
+
+    fn load(reader: &mut impl Read) -> io::Result<Self>
+    where
+        Self: Sized;
+}
+
+impl Model for Network {
+    fn forward_propagate(&mut self, inputs: &[Weight]) -> &[Weight] { self.compute(inputs) }
+
+    fn train_one_sample(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+        self.train_one_example(example, expected, learning_rate)
+    }
+
+    /// Lays gradients out as each hidden layer's weights, row by row, then its biases, followed by the output layer's
+    /// weights.
+    fn accumulate_gradients(&mut self, example: &[Weight], expected: &[Weight], gradients: &mut Vec<Weight>) -> Weight {
+        self.compute_gradients(example, expected);
+        if gradients.is_empty() {
+            gradients.resize(self.param_count(), 0.);
+        }
+
+        let mut rest = gradients.as_mut_slice();
+        let mut inputs = example;
+        for layer in &self.hidden_layers {
+            rest = accumulate_outer_product(rest, &layer.neuron_gradients, inputs);
+            let (bias_gradients, after_biases) = rest.split_at_mut(layer.biases.len());
+            for (bias_gradient, &neuron_gradient) in bias_gradients.iter_mut().zip(layer.neuron_gradients.iter()) {
+                *bias_gradient += neuron_gradient;
+            }
+            rest = after_biases;
+            inputs = &layer.outputs;
+        }
+        accumulate_outer_product(rest, &self.outputs.neuron_gradients, inputs);
+
+        self.mean_cost()
+    }
+
+    /// Frozen layers are skipped, and spectrally normalized layers step their raw weights by the scaled gradient as
+    /// `DenseLayer::update_weights` does.
+    fn apply_gradients(&mut self, gradients: &[Weight], scale: Weight) {
+        let mut rest = gradients;
+        for layer in &mut self.hidden_layers {
+            let layer_params = layer.weights.iter().map(|row| row.len()).sum::<usize>() + layer.biases.len();
+            if layer.frozen {
+                rest = &rest[layer_params..];
+                continue;
+            }
+
+            let weight_scale = scale * layer.spectral_norm_scale();
+            for row in &mut layer.weights {
+                rest = apply_gradients(row, rest, weight_scale);
+            }
+            layer.after_weight_update();
+            rest = apply_gradients(&mut layer.biases, rest, scale);
+        }
+        for row in &mut self.outputs.weights {
+            rest = apply_gradients(row, rest, scale);
+        }
+    }
+
+    fn layer_gradients(&self) -> Vec<&[Weight]> {
+        self.hidden_layers
+            .iter()
+            .map(|layer| layer.neuron_gradients.as_slice())
+            .chain(std::iter::once(self.outputs.neuron_gradients.as_slice()))
+            .collect()
+    }
+
+    fn param_count(&self) -> usize {
+        let hidden_params: usize = self
+            .hidden_layers
+            .iter()
+            .map(|layer| layer.weights.iter().map(|row| row.len()).sum::<usize>() + layer.biases.len())
+            .sum();
+        hidden_params + self.outputs.weights.iter().map(|row| row.len()).sum::<usize>()
+    }
+
+    fn save(&self, writer: &mut impl Write) -> io::Result<()> { Network::save(self, writer) }
+
+    fn load(reader: &mut impl Read) -> io::Result<Self> { Network::load(reader) }
+}
@@ -0,0 +1,111 @@
+use crate::{log_sum_exp, Network, Weight};
+
+/// Estimates the mutual information between a network's hidden states and its outputs with MINE (Belghazi et al.,
+/// 2018) so that it can be rewarded as part of the loss.
+///
+/// The critic scores a state concatenated with an output and is trained to maximize the Donsker-Varadhan lower bound
+/// `mean(T(s, y)) - ln(mean(exp(T(s, y'))))`, where `(s, y)` are pairs that occurred together and `(s, y')` pairs
+/// that didn't.  The critic needs a single output with the identity activation.  Mismatched pairs are made by pairing
+/// each state with the output of the next example in the batch.
+pub struct MutualInformationRegularizer {
+    pub beta: Weight,
+    pub critic: Network,
+}
+
+fn critic_input(state: &[Weight], output: &[Weight]) -> Vec<Weight> {
+    state.iter().chain(output.iter()).copied().collect()
+}
+
+impl MutualInformationRegularizer {
+    pub fn new(beta: Weight, critic: Network) -> Self {
+        assert_eq!(
+            critic.outputs.outputs.len(),
+            1,
+            "The critic must produce a single score"
+        );
+        MutualInformationRegularizer { beta, critic }
+    }
+
+    /// Returns the scores for each matching pair and for each mismatched pair.
+    fn scores(&mut self, states: &[Vec<Weight>], outputs: &[Vec<Weight>]) -> (Vec<Weight>, Vec<Weight>) {
+        debug_assert_eq!(states.len(), outputs.len());
+        let count = states.len();
+        let joint = (0..count)
+            .map(|ix| self.critic.compute(&critic_input(&states[ix], &outputs[ix]))[0])
+            .collect();
+        let marginal = (0..count)
+            .map(|ix| {
+                self.critic
+                    .compute(&critic_input(&states[ix], &outputs[(ix + 1) % count]))[0]
+            })
+            .collect();
+        (joint, marginal)
+    }
+
+    fn lower_bound(joint: &[Weight], marginal: &[Weight]) -> Weight {
+        let joint_mean = joint.iter().sum::<Weight>() / joint.len() as Weight;
+        joint_mean - (log_sum_exp(marginal) - (marginal.len() as Weight).ln())
+    }
+
+    /// Returns the critic's current estimate of the mutual information between `states` and `outputs`, in nats.
+    pub fn estimate(&mut self, states: &[Vec<Weight>], outputs: &[Vec<Weight>]) -> Weight {
+        let (joint, marginal) = self.scores(states, outputs);
+        Self::lower_bound(&joint, &marginal)
+    }
+
+    /// How much each score moves the bound: `1 / n` for matching pairs and `-softmax(marginal)` for mismatched ones.
+    fn score_gradients(marginal: &[Weight]) -> (Weight, Vec<Weight>) {
+        let log_total = log_sum_exp(marginal);
+        let marginal_gradients = marginal.iter().map(|&score| -(score - log_total).exp()).collect();
+        (1. / marginal.len() as Weight, marginal_gradients)
+    }
+
+    /// Takes one gradient ascent step on the critic's bound for the batch, returning the estimate from before the
+    /// step.
+    pub fn train_critic(&mut self, states: &[Vec<Weight>], outputs: &[Vec<Weight>], learning_rate: Weight) -> Weight {
+        let (joint, marginal) = self.scores(states, outputs);
+        let estimate = Self::lower_bound(&joint, &marginal);
+        let (joint_gradient, marginal_gradients) = Self::score_gradients(&marginal);
+
+        let count = states.len();
+        for ix in 0..count {
+            for (output, gradient) in [
+                (&outputs[ix], joint_gradient),
+                (&outputs[(ix + 1) % count], marginal_gradients[ix]),
+            ] {
+                let input = critic_input(&states[ix], output);
+                self.critic.forward_propagate(&input);
+                self.critic.backpropagate(&[gradient]);
+                self.critic.update_weights(&input, learning_rate);
+            }
+        }
+        estimate
+    }
+
+    /// Returns, for each state, the negated gradient of `-beta * estimate` with respect to it, which is what the main
+    /// network should add to the gradients of the cost at the layer producing the states.
+    pub fn state_gradients(&mut self, states: &[Vec<Weight>], outputs: &[Vec<Weight>]) -> Vec<Vec<Weight>> {
+        let (_, marginal) = self.scores(states, outputs);
+        let (joint_gradient, marginal_gradients) = Self::score_gradients(&marginal);
+
+        let count = states.len();
+        (0..count)
+            .map(|ix| {
+                let mut state_gradients = vec![0.; states[ix].len()];
+                let mut input_gradients = vec![0.; states[ix].len() + outputs[ix].len()];
+                for (output, gradient) in [
+                    (&outputs[ix], joint_gradient),
+                    (&outputs[(ix + 1) % count], marginal_gradients[ix]),
+                ] {
+                    self.critic.forward_propagate(&critic_input(&states[ix], output));
+                    self.critic.backpropagate(&[gradient]);
+                    self.critic.compute_input_gradients(&mut input_gradients);
+                    for (state_gradient, &input_gradient) in state_gradients.iter_mut().zip(input_gradients.iter()) {
+                        *state_gradient += self.beta * input_gradient;
+                    }
+                }
+                state_gradients
+            })
+            .collect()
+    }
+}
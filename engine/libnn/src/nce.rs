@@ -0,0 +1,84 @@
+use rand::Rng;
+
+use crate::{log_sigmoid, sigmoid, Weight};
+
+/// Noise-contrastive estimation, which replaces a softmax over a whole vocabulary with a binary task: telling the
+/// target word apart from `noise_samples` words drawn from `noise_distribution`.  Only the scores of those words are
+/// needed for each example, so the cost no longer grows with the vocabulary size.
+///
+/// Scores are treated as unnormalized log probabilities.  A word `w` with score `s` is classified as real with
+/// probability `sigmoid(s - ln(k * q(w)))`, where `k` is `noise_samples` and `q` is the noise distribution.
+#[derive(Clone, Debug)]
+pub struct NceLoss {
+    pub noise_samples: usize,
+    /// Probability of drawing each word as noise, usually the unigram distribution of the training data
+    pub noise_distribution: Vec<f32>,
+    /// Running sums of `noise_distribution`, used to sample from it
+    pub cumulative_distribution: Vec<f32>,
+}
+
+impl NceLoss {
+    pub fn new(noise_samples: usize, noise_distribution: Vec<f32>) -> Self {
+        assert!(noise_samples > 0, "NCE needs at least one noise sample");
+        let mut total = 0.;
+        let cumulative_distribution = noise_distribution
+            .iter()
+            .map(|&p| {
+                total += p;
+                total
+            })
+            .collect();
+        NceLoss {
+            noise_samples,
+            noise_distribution,
+            cumulative_distribution,
+        }
+    }
+
+    /// Draws `noise_samples` words from the noise distribution.
+    pub fn sample_noise(&self, rng: &mut impl Rng) -> Vec<usize> {
+        let total = *self
+            .cumulative_distribution
+            .last()
+            .expect("Noise distribution is empty");
+        (0..self.noise_samples)
+            .map(|_| {
+                let val = rng.gen::<f32>() * total;
+                self.cumulative_distribution
+                    .partition_point(|&cumulative| cumulative <= val)
+                    .min(self.cumulative_distribution.len() - 1)
+            })
+            .collect()
+    }
+
+    /// How much more likely the model thinks `word` is to be real than noise, in log space
+    fn log_odds(&self, word_ix: usize, score: Weight) -> Weight {
+        score - (self.noise_samples as Weight * self.noise_distribution[word_ix]).ln()
+    }
+
+    /// Computes the loss for `target` against `noise_words`, where `score` returns the unnormalized log probability
+    /// of a word.  Returns the loss along with the negated gradient for the score of each word involved, with repeated
+    /// words combined into a single entry.
+    pub fn compute(
+        &self,
+        score: impl Fn(usize) -> Weight,
+        target: usize,
+        noise_words: &[usize],
+    ) -> (Weight, Vec<(usize, Weight)>) {
+        let target_log_odds = self.log_odds(target, score(target));
+        let mut loss = -log_sigmoid(target_log_odds);
+        let mut gradients = vec![(target, 1. - sigmoid(target_log_odds))];
+
+        for &word_ix in noise_words {
+            let log_odds = self.log_odds(word_ix, score(word_ix));
+            // ln(1 - sigmoid(x)) = ln(sigmoid(-x))
+            loss -= log_sigmoid(-log_odds);
+            let gradient = -sigmoid(log_odds);
+            match gradients.iter_mut().find(|(ix, _)| *ix == word_ix) {
+                Some((_, total)) => *total += gradient,
+                None => gradients.push((word_ix, gradient)),
+            }
+        }
+        (loss, gradients)
+    }
+}
@@ -0,0 +1,59 @@
+use rand::Rng;
+
+use crate::{augmentation::standard_normal, Weight};
+
+/// Adds Gaussian noise with mean 0 to activations while training so that later layers learn not to rely on exact
+/// values.  Inputs pass through unchanged when `training` is false.
+///
+/// The noise is additive, so gradients flow back through the layer unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaussianNoiseLayer {
+    pub std_dev: Weight,
+    pub training: bool,
+}
+
+impl GaussianNoiseLayer {
+    pub fn new(std_dev: Weight) -> Self {
+        GaussianNoiseLayer {
+            std_dev,
+            training: true,
+        }
+    }
+
+    pub fn forward_propagate(&self, inputs: &[Weight], rng: &mut impl Rng) -> Vec<Weight> {
+        if !self.training {
+            return inputs.to_owned();
+        }
+        inputs
+            .iter()
+            .map(|&x| x + self.std_dev * standard_normal(rng))
+            .collect()
+    }
+}
+
+/// Adds noise drawn uniformly from [low, high) to activations while training.  Inputs pass through unchanged when
+/// `training` is false.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniformNoiseLayer {
+    pub low: Weight,
+    pub high: Weight,
+    pub training: bool,
+}
+
+impl UniformNoiseLayer {
+    pub fn new(low: Weight, high: Weight) -> Self {
+        assert!(low < high, "Noise range must not be empty");
+        UniformNoiseLayer {
+            low,
+            high,
+            training: true,
+        }
+    }
+
+    pub fn forward_propagate(&self, inputs: &[Weight], rng: &mut impl Rng) -> Vec<Weight> {
+        if !self.training {
+            return inputs.to_owned();
+        }
+        inputs.iter().map(|&x| x + rng.gen_range(self.low, self.high)).collect()
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{DenseLayer, Weight};
+
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+#[derive(Debug)]
+pub enum NpyError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion { major: u8, minor: u8 },
+    InvalidHeader(String),
+    UnsupportedDtype(String),
+    ShapeMismatch { expected: Vec<usize>, found: Vec<usize> },
+}
+
+impl fmt::Display for NpyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NpyError::Io(err) => write!(f, "failed to read .npy file: {}", err),
+            NpyError::InvalidMagic => write!(f, "not a .npy file"),
+            NpyError::UnsupportedVersion { major, minor } => write!(f, "unsupported .npy version {}.{}", major, minor),
+            NpyError::InvalidHeader(header) => write!(f, "invalid .npy header: {}", header),
+            NpyError::UnsupportedDtype(dtype) => write!(f, "unsupported dtype {}, expected f4 or f8", dtype),
+            NpyError::ShapeMismatch { expected, found } =>
+                write!(f, "expected an array of shape {:?} but found {:?}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for NpyError {}
+
+impl From<io::Error> for NpyError {
+    fn from(err: io::Error) -> Self { NpyError::Io(err) }
+}
+
+/// A floating point array read from NumPy's .npy format
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    /// Elements in row-major (C) order, whatever order the file stored them in
+    pub data: Vec<Weight>,
+}
+
+/// Returns the text following `'key':` in a header dict, up to the end of the value.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let pattern = format!("'{}':", key);
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| NpyError::InvalidHeader(header.to_owned()))?
+        + pattern.len();
+    let value = header[start..].trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')').map(|ix| ix + 1)
+    } else {
+        value.find([',', '}'])
+    };
+    end.map(|end| value[..end].trim())
+        .ok_or_else(|| NpyError::InvalidHeader(header.to_owned()))
+}
+
+fn parse_shape(header: &str) -> Result<Vec<usize>, NpyError> {
+    let shape = header_value(header, "shape")?;
+    shape
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| NpyError::InvalidHeader(header.to_owned())))
+        .collect()
+}
+
+/// Converts an index into an array of `shape` stored in column-major order into the row-major index of the same
+/// element.
+fn fortran_to_c_ix(mut fortran_ix: usize, shape: &[usize]) -> usize {
+    let mut c_ix = 0;
+    let mut c_stride: usize = shape.iter().product();
+    for &dim in shape {
+        c_stride /= dim;
+        c_ix += (fortran_ix % dim) * c_stride;
+        fortran_ix /= dim;
+    }
+    c_ix
+}
+
+impl NpyArray {
+    /// Parses versions 1.0 and 2.0 of the .npy format, which differ only in the size of the header length.
+    pub fn read(reader: &mut impl Read) -> Result<Self, NpyError> {
+        let mut magic = [0u8; 6];
+        reader.read_exact(&mut magic)?;
+        if &magic != NPY_MAGIC {
+            return Err(NpyError::InvalidMagic);
+        }
+        let mut version = [0u8; 2];
+        reader.read_exact(&mut version)?;
+        let header_len = match version {
+            [1, 0] => {
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_le_bytes(len) as usize
+            },
+            [2, 0] => {
+                let mut len = [0u8; 4];
+                reader.read_exact(&mut len)?;
+                u32::from_le_bytes(len) as usize
+            },
+            [major, minor] => return Err(NpyError::UnsupportedVersion { major, minor }),
+        };
+        // Sizes in the file can't be trusted, so buffers only grow as the data they describe actually arrives
+        let mut header = Vec::new();
+        reader.by_ref().take(header_len as u64).read_to_end(&mut header)?;
+        if header.len() != header_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let header = String::from_utf8(header).map_err(|_| NpyError::InvalidHeader("not valid UTF-8".to_owned()))?;
+
+        let descr = header_value(&header, "descr")?.trim_matches(|c| c == '\'' || c == '"');
+        let fortran_order = match header_value(&header, "fortran_order")? {
+            "True" => true,
+            "False" => false,
+            _ => return Err(NpyError::InvalidHeader(header.clone())),
+        };
+        let shape = parse_shape(&header)?;
+        let count = shape
+            .iter()
+            .try_fold(1usize, |count, &dim| count.checked_mul(dim))
+            .ok_or_else(|| NpyError::InvalidHeader(header.clone()))?;
+
+        let mut data = Vec::new();
+        match descr {
+            "<f4" | ">f4" => {
+                let mut buf = [0u8; 4];
+                for _ in 0..count {
+                    reader.read_exact(&mut buf)?;
+                    data.push(if descr.starts_with('<') {
+                        f32::from_le_bytes(buf)
+                    } else {
+                        f32::from_be_bytes(buf)
+                    });
+                }
+            },
+            "<f8" | ">f8" => {
+                let mut buf = [0u8; 8];
+                for _ in 0..count {
+                    reader.read_exact(&mut buf)?;
+                    data.push(if descr.starts_with('<') {
+                        f64::from_le_bytes(buf)
+                    } else {
+                        f64::from_be_bytes(buf)
+                    } as Weight);
+                }
+            },
+            _ => return Err(NpyError::UnsupportedDtype(descr.to_owned())),
+        }
+
+        if fortran_order {
+            let mut c_order = vec![0.; count];
+            for (fortran_ix, &val) in data.iter().enumerate() {
+                c_order[fortran_to_c_ix(fortran_ix, &shape)] = val;
+            }
+            data = c_order;
+        }
+
+        Ok(NpyArray { shape, data })
+    }
+}
+
+impl DenseLayer {
+    /// Replaces this layer's weights with a 2D array of shape (neuron count, input count) read from a .npy file.
+    /// Biases are left as they are.
+    pub fn load_weights_from_npy(&mut self, path: &Path) -> Result<(), NpyError> {
+        let array = NpyArray::read(&mut BufReader::new(File::open(path)?))?;
+        let expected = vec![self.weights.len(), self.weights.first().map(Vec::len).unwrap_or(0)];
+        if array.shape != expected {
+            return Err(NpyError::ShapeMismatch {
+                expected,
+                found: array.shape,
+            });
+        }
+
+        // A layer without inputs has no weights to replace
+        if expected[1] > 0 {
+            for (neuron_weights, row) in self.weights.iter_mut().zip(array.data.chunks(expected[1])) {
+                neuron_weights.copy_from_slice(row);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,797 @@
+use crate::Weight;
+
+/// Decides how a layer's weights move given its neuron gradients.
+///
+/// Gradients follow the same convention as the rest of this crate: `neuron_gradients` already point in the direction
+/// that reduces cost, so the gradient for the weight connecting input `i` to neuron `n` is `neuron_gradients[n] *
+/// inputs[i]` and plain SGD adds `learning_rate` times that to the weight.
+///
+/// Optimizers are stateful, so each layer needs its own instance.
+pub trait Optimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    );
+
+    /// Moves each bias by its neuron's gradient, which is the gradient for the bias itself.  Defaults to plain SGD,
+    /// same as `DenseLayer::update_biases`.  The adaptive optimizers override this to apply their own rule, treating
+    /// each bias as the weight of a neuron whose input is always 1, with state kept apart from the weights'.
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        for (bias, &neuron_gradient) in biases.iter_mut().zip(neuron_gradients.iter()) {
+            *bias += learning_rate * neuron_gradient;
+        }
+    }
+}
+
+/// Resizes a per-weight state buffer to match `weights`, zeroing it if the shape changed.
+pub(crate) fn ensure_state_shape(state: &mut Vec<Vec<Weight>>, weights: &[Vec<Weight>]) {
+    let shape_matches =
+        state.len() == weights.len() && state.iter().zip(weights.iter()).all(|(s, w)| s.len() == w.len());
+    if !shape_matches {
+        *state = weights
+            .iter()
+            .map(|neuron_weights| vec![0.; neuron_weights.len()])
+            .collect();
+    }
+}
+
+/// Updates `biases` with `bias_optimizer`'s weight update, treating each bias as the only weight of a neuron whose
+/// input is always 1.  `bias_optimizer` should be set aside for the biases so that they get state of their own, and
+/// is created with `new_optimizer` the first time it is needed.
+fn update_biases_as_weights<O: Optimizer>(
+    bias_optimizer: &mut Option<Box<O>>,
+    new_optimizer: impl FnOnce() -> O,
+    biases: &mut [Weight],
+    neuron_gradients: &[Weight],
+    learning_rate: Weight,
+) {
+    let bias_optimizer = bias_optimizer.get_or_insert_with(|| Box::new(new_optimizer()));
+    let mut bias_weights: Vec<Vec<Weight>> = biases.iter().map(|&bias| vec![bias]).collect();
+    bias_optimizer.update_weights(&mut bias_weights, neuron_gradients, &[1.], learning_rate);
+    for (bias, bias_weight) in biases.iter_mut().zip(bias_weights.iter()) {
+        *bias = bias_weight[0];
+    }
+}
+
+/// Vanilla stochastic gradient descent; identical to `DenseLayer::update_weights`.
+pub struct Sgd;
+
+impl Optimizer for Sgd {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        for (neuron_weights, &neuron_gradient) in weights.iter_mut().zip(neuron_gradients.iter()) {
+            for (weight, &input) in neuron_weights.iter_mut().zip(inputs.iter()) {
+                *weight += learning_rate * neuron_gradient * input;
+            }
+        }
+    }
+}
+
+/// SGD with Nesterov momentum.
+///
+/// Evaluating the gradient at the look-ahead position `w + momentum * velocity` would require a second forward pass.
+/// Instead we track the look-ahead position directly (Sutskever et al., 2013), which turns the update into:
+///
+/// ```text
+/// velocity = momentum * velocity + gradient
+/// weight += learning_rate * (gradient + momentum * velocity)
+/// ```
+///
+/// Biases get the same update with a velocity of their own.
+pub struct NesterovSgd {
+    pub momentum: Weight,
+    pub velocity: Vec<Vec<Weight>>,
+    pub bias_velocity: Vec<Weight>,
+}
+
+impl NesterovSgd {
+    pub fn new(momentum: Weight) -> Self {
+        NesterovSgd {
+            momentum,
+            velocity: Vec::new(),
+            bias_velocity: Vec::new(),
+        }
+    }
+}
+
+impl Optimizer for NesterovSgd {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.velocity, weights);
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            let neuron_velocity = &mut self.velocity[neuron_ix];
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let velocity = &mut neuron_velocity[weight_ix];
+                *velocity = self.momentum * *velocity + gradient;
+                *weight += learning_rate * (gradient + self.momentum * *velocity);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        if self.bias_velocity.len() != biases.len() {
+            self.bias_velocity = vec![0.; biases.len()];
+        }
+
+        for ((bias, velocity), &gradient) in biases
+            .iter_mut()
+            .zip(self.bias_velocity.iter_mut())
+            .zip(neuron_gradients.iter())
+        {
+            *velocity = self.momentum * *velocity + gradient;
+            *bias += learning_rate * (gradient + self.momentum * *velocity);
+        }
+    }
+}
+
+/// Adam with the second moment replaced by an exponentially weighted infinity norm (Kingma & Ba, 2015, section 7.1):
+///
+/// ```text
+/// m = beta1 * m + (1 - beta1) * gradient
+/// u = max(beta2 * u, |gradient|)
+/// weight += learning_rate / (1 - beta1^step) * m / (u + epsilon)
+/// ```
+///
+/// `u` needs no bias correction since the max doesn't shrink towards its zero initialization the way an average does.
+pub struct AdamaxOptimizer {
+    pub beta1: Weight,
+    pub beta2: Weight,
+    pub epsilon: Weight,
+    pub m: Vec<Vec<Weight>>,
+    pub u: Vec<Vec<Weight>>,
+    pub step: usize,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<AdamaxOptimizer>>,
+}
+
+impl AdamaxOptimizer {
+    pub fn new(beta1: Weight, beta2: Weight, epsilon: Weight) -> Self {
+        AdamaxOptimizer {
+            beta1,
+            beta2,
+            epsilon,
+            m: Vec::new(),
+            u: Vec::new(),
+            step: 0,
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for AdamaxOptimizer {
+    fn default() -> Self { AdamaxOptimizer::new(0.9, 0.999, 1e-8) }
+}
+
+impl Optimizer for AdamaxOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.m, weights);
+        ensure_state_shape(&mut self.u, weights);
+        self.step += 1;
+        let step_size = learning_rate / (1. - self.beta1.powi(self.step as i32));
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let m = &mut self.m[neuron_ix][weight_ix];
+                let u = &mut self.u[neuron_ix][weight_ix];
+                *m = self.beta1 * *m + (1. - self.beta1) * gradient;
+                *u = (self.beta2 * *u).max(gradient.abs());
+                *weight += step_size * *m / (*u + self.epsilon);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || AdamaxOptimizer::new(beta1, beta2, epsilon),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+/// Adam with Nesterov momentum (Dozat, 2016).  Like `NesterovSgd`, the look-ahead is folded into the update by
+/// applying this step's momentum a second time, with the usual bias corrections:
+///
+/// ```text
+/// m = beta1 * m + (1 - beta1) * gradient
+/// v = beta2 * v + (1 - beta2) * gradient^2
+/// m_hat = beta1 * m / (1 - beta1^(step + 1)) + (1 - beta1) * gradient / (1 - beta1^step)
+/// weight += learning_rate * m_hat / (sqrt(v / (1 - beta2^step)) + epsilon)
+/// ```
+pub struct NadamOptimizer {
+    pub beta1: Weight,
+    pub beta2: Weight,
+    pub epsilon: Weight,
+    pub m: Vec<Vec<Weight>>,
+    pub v: Vec<Vec<Weight>>,
+    pub step: usize,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<NadamOptimizer>>,
+}
+
+impl NadamOptimizer {
+    pub fn new(beta1: Weight, beta2: Weight, epsilon: Weight) -> Self {
+        NadamOptimizer {
+            beta1,
+            beta2,
+            epsilon,
+            m: Vec::new(),
+            v: Vec::new(),
+            step: 0,
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for NadamOptimizer {
+    fn default() -> Self { NadamOptimizer::new(0.9, 0.999, 1e-8) }
+}
+
+impl Optimizer for NadamOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.m, weights);
+        ensure_state_shape(&mut self.v, weights);
+        self.step += 1;
+        let step = self.step as i32;
+        let momentum_correction = 1. - self.beta1.powi(step + 1);
+        let gradient_correction = 1. - self.beta1.powi(step);
+        let variance_correction = 1. - self.beta2.powi(step);
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let m = &mut self.m[neuron_ix][weight_ix];
+                let v = &mut self.v[neuron_ix][weight_ix];
+                *m = self.beta1 * *m + (1. - self.beta1) * gradient;
+                *v = self.beta2 * *v + (1. - self.beta2) * gradient * gradient;
+                let m_hat = self.beta1 * *m / momentum_correction + (1. - self.beta1) * gradient / gradient_correction;
+                *weight += learning_rate * m_hat / ((*v / variance_correction).sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || NadamOptimizer::new(beta1, beta2, epsilon),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+/// Adam with the denominator taken from the largest second moment estimate seen so far (Reddi et al., 2018), so that
+/// a burst of small gradients can't make the effective step size grow again:
+///
+/// ```text
+/// m = beta1 * m + (1 - beta1) * gradient
+/// v = beta2 * v + (1 - beta2) * gradient^2
+/// v_max = max(v_max, v / (1 - beta2^step))
+/// weight += learning_rate * m / (1 - beta1^step) / (sqrt(v_max) + epsilon)
+/// ```
+///
+/// Plain Adam forgets rare large gradients within a few steps and can end up moving in the wrong direction on
+/// average; keeping the maximum fixes that.
+pub struct AmsGradOptimizer {
+    pub beta1: Weight,
+    pub beta2: Weight,
+    pub epsilon: Weight,
+    pub m: Vec<Vec<Weight>>,
+    pub v: Vec<Vec<Weight>>,
+    pub v_max: Vec<Vec<Weight>>,
+    pub step: usize,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<AmsGradOptimizer>>,
+}
+
+impl AmsGradOptimizer {
+    pub fn new(beta1: Weight, beta2: Weight, epsilon: Weight) -> Self {
+        AmsGradOptimizer {
+            beta1,
+            beta2,
+            epsilon,
+            m: Vec::new(),
+            v: Vec::new(),
+            v_max: Vec::new(),
+            step: 0,
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for AmsGradOptimizer {
+    fn default() -> Self { AmsGradOptimizer::new(0.9, 0.999, 1e-8) }
+}
+
+impl Optimizer for AmsGradOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.m, weights);
+        ensure_state_shape(&mut self.v, weights);
+        ensure_state_shape(&mut self.v_max, weights);
+        self.step += 1;
+        let step = self.step as i32;
+        let step_size = learning_rate / (1. - self.beta1.powi(step));
+        let variance_correction = 1. - self.beta2.powi(step);
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let m = &mut self.m[neuron_ix][weight_ix];
+                let v = &mut self.v[neuron_ix][weight_ix];
+                let v_max = &mut self.v_max[neuron_ix][weight_ix];
+                *m = self.beta1 * *m + (1. - self.beta1) * gradient;
+                *v = self.beta2 * *v + (1. - self.beta2) * gradient * gradient;
+                *v_max = v_max.max(*v / variance_correction);
+                *weight += step_size * *m / (v_max.sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || AmsGradOptimizer::new(beta1, beta2, epsilon),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+/// Scales each weight's step by the inverse square root of the sum of all of its squared gradients so far (Duchi et
+/// al., 2011).  Weights that see large gradients often slow down, while rarely-updated ones such as the rows of an
+/// `EmbeddingLayer` for uncommon tokens keep taking large steps.
+///
+/// `initial_lr` is the step size before any gradients have been accumulated.  The learning rate passed to
+/// `update_weights` scales it, so learning rate schedules still apply on top of the decay.
+pub struct AdagradOptimizer {
+    pub accumulated_sq_grad: Vec<Vec<Weight>>,
+    pub epsilon: Weight,
+    pub initial_lr: Weight,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<AdagradOptimizer>>,
+}
+
+impl AdagradOptimizer {
+    pub fn new(initial_lr: Weight) -> Self {
+        AdagradOptimizer {
+            accumulated_sq_grad: Vec::new(),
+            epsilon: 1e-8,
+            initial_lr,
+            bias_optimizer: None,
+        }
+    }
+
+    /// How far the weight connecting input `weight_ix` to neuron `neuron_ix` currently moves per unit of gradient,
+    /// given a learning rate of 1
+    pub fn effective_learning_rate(&self, neuron_ix: usize, weight_ix: usize) -> Weight {
+        let accumulated = self
+            .accumulated_sq_grad
+            .get(neuron_ix)
+            .and_then(|neuron| neuron.get(weight_ix))
+            .copied()
+            .unwrap_or(0.);
+        self.initial_lr / (accumulated.sqrt() + self.epsilon)
+    }
+}
+
+impl Optimizer for AdagradOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.accumulated_sq_grad, weights);
+        let step_size = learning_rate * self.initial_lr;
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let accumulated = &mut self.accumulated_sq_grad[neuron_ix][weight_ix];
+                *accumulated += gradient * gradient;
+                *weight += step_size * gradient / (accumulated.sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (initial_lr, epsilon) = (self.initial_lr, self.epsilon);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || AdagradOptimizer {
+                epsilon,
+                ..AdagradOptimizer::new(initial_lr)
+            },
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+/// Wraps another optimizer so that its weights explore ahead of a second, slowly moving copy (Zhang et al., 2019).
+/// `inner` updates the fast weights, which are the layer's own weights, as usual.  Every `k` steps the slow weights
+/// move `alpha` of the way towards the fast weights and the fast weights are reset to them:
+///
+/// ```text
+/// slow += alpha * (fast - slow)
+/// fast = slow
+/// ```
+///
+/// The slow weights are initialized from the layer's weights on the first update.
+pub struct LookaheadOptimizer {
+    pub inner: Box<dyn Optimizer>,
+    pub k: usize,
+    pub alpha: Weight,
+    pub slow_weights: Vec<Vec<Weight>>,
+    /// Inner steps taken since the slow weights were last updated
+    pub step: usize,
+}
+
+impl LookaheadOptimizer {
+    pub fn new(inner: Box<dyn Optimizer>, k: usize, alpha: Weight) -> Self {
+        assert!(k > 0, "k must be at least 1");
+        LookaheadOptimizer {
+            inner,
+            k,
+            alpha,
+            slow_weights: Vec::new(),
+            step: 0,
+        }
+    }
+}
+
+impl Optimizer for LookaheadOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        let shape_matches = self.slow_weights.len() == weights.len()
+            && self
+                .slow_weights
+                .iter()
+                .zip(weights.iter())
+                .all(|(slow, fast)| slow.len() == fast.len());
+        if !shape_matches {
+            self.slow_weights = weights.to_vec();
+            self.step = 0;
+        }
+
+        self.inner
+            .update_weights(weights, neuron_gradients, inputs, learning_rate);
+        self.step += 1;
+        if self.step < self.k {
+            return;
+        }
+
+        self.step = 0;
+        for (slow_weights, fast_weights) in self.slow_weights.iter_mut().zip(weights.iter_mut()) {
+            for (slow, fast) in slow_weights.iter_mut().zip(fast_weights.iter_mut()) {
+                *slow += self.alpha * (*fast - *slow);
+                *fast = *slow;
+            }
+        }
+    }
+
+    /// Biases are left to the inner optimizer without any slow copy.
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        self.inner.update_biases(biases, neuron_gradients, learning_rate);
+    }
+}
+
+fn sign(x: Weight) -> Weight {
+    if x > 0. {
+        1.
+    } else if x < 0. {
+        -1.
+    } else {
+        0.
+    }
+}
+
+/// EvoLved Sign Momentum (Chen et al., 2023).  Each weight moves by exactly `learning_rate` in the direction of an
+/// interpolation between its momentum and the current gradient, so only the momentum needs to be stored, half the
+/// state of Adam.  Since every step has the same magnitude, Lion usually wants a learning rate 3-10x smaller than
+/// Adam would.
+///
+/// ```text
+/// update = sign(beta1 * m + (1 - beta1) * gradient)
+/// m = beta2 * m + (1 - beta2) * gradient
+/// weight += learning_rate * (update - weight_decay * weight)
+/// ```
+pub struct LionOptimizer {
+    pub beta1: Weight,
+    pub beta2: Weight,
+    pub weight_decay: Weight,
+    pub m: Vec<Vec<Weight>>,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<LionOptimizer>>,
+}
+
+impl LionOptimizer {
+    pub fn new(beta1: Weight, beta2: Weight, weight_decay: Weight) -> Self {
+        LionOptimizer {
+            beta1,
+            beta2,
+            weight_decay,
+            m: Vec::new(),
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for LionOptimizer {
+    fn default() -> Self { LionOptimizer::new(0.9, 0.99, 0.) }
+}
+
+impl Optimizer for LionOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        ensure_state_shape(&mut self.m, weights);
+
+        for (neuron_ix, &neuron_gradient) in neuron_gradients.iter().enumerate() {
+            for (weight_ix, weight) in weights[neuron_ix].iter_mut().enumerate() {
+                let gradient = neuron_gradient * inputs[weight_ix];
+                let m = &mut self.m[neuron_ix][weight_ix];
+                let update = sign(self.beta1 * *m + (1. - self.beta1) * gradient);
+                *m = self.beta2 * *m + (1. - self.beta2) * gradient;
+                *weight += learning_rate * (update - self.weight_decay * *weight);
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, weight_decay) = (self.beta1, self.beta2, self.weight_decay);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || LionOptimizer::new(beta1, beta2, weight_decay),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+/// The moment estimates Adam keeps for each weight, for optimizers that build on Adam's update direction
+pub struct AdamState {
+    pub beta1: Weight,
+    pub beta2: Weight,
+    pub epsilon: Weight,
+    pub m: Vec<Vec<Weight>>,
+    pub v: Vec<Vec<Weight>>,
+    pub step: usize,
+}
+
+impl AdamState {
+    pub fn new(beta1: Weight, beta2: Weight, epsilon: Weight) -> Self {
+        AdamState {
+            beta1,
+            beta2,
+            epsilon,
+            m: Vec::new(),
+            v: Vec::new(),
+            step: 0,
+        }
+    }
+
+    /// Updates the moments with this step's gradients and returns Adam's bias-corrected update direction for each
+    /// weight, `m_hat / (sqrt(v_hat) + epsilon)`, before scaling by the learning rate.
+    pub fn update(
+        &mut self,
+        weights: &[Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+    ) -> Vec<Vec<Weight>> {
+        ensure_state_shape(&mut self.m, weights);
+        ensure_state_shape(&mut self.v, weights);
+        self.step += 1;
+        let momentum_correction = 1. - self.beta1.powi(self.step as i32);
+        let variance_correction = 1. - self.beta2.powi(self.step as i32);
+
+        neuron_gradients
+            .iter()
+            .enumerate()
+            .map(|(neuron_ix, &neuron_gradient)| {
+                (0..weights[neuron_ix].len())
+                    .map(|weight_ix| {
+                        let gradient = neuron_gradient * inputs[weight_ix];
+                        let m = &mut self.m[neuron_ix][weight_ix];
+                        let v = &mut self.v[neuron_ix][weight_ix];
+                        *m = self.beta1 * *m + (1. - self.beta1) * gradient;
+                        *v = self.beta2 * *v + (1. - self.beta2) * gradient * gradient;
+                        (*m / momentum_correction) / ((*v / variance_correction).sqrt() + self.epsilon)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Default for AdamState {
+    fn default() -> Self { AdamState::new(0.9, 0.999, 1e-8) }
+}
+
+/// Adam (Kingma & Ba, 2015), which scales each weight's step by running estimates of the mean and uncentered variance
+/// of its gradient:
+///
+/// ```text
+/// m = beta1 * m + (1 - beta1) * gradient
+/// v = beta2 * v + (1 - beta2) * gradient^2
+/// weight += learning_rate * m_hat / (sqrt(v_hat) + epsilon)
+/// ```
+///
+/// `m_hat` and `v_hat` are `m` and `v` divided by `1 - beta^step` to correct for their zero initialization.
+pub struct AdamOptimizer {
+    pub adam_state: AdamState,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<AdamOptimizer>>,
+}
+
+impl AdamOptimizer {
+    pub fn new(beta1: Weight, beta2: Weight, epsilon: Weight) -> Self {
+        AdamOptimizer {
+            adam_state: AdamState::new(beta1, beta2, epsilon),
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for AdamOptimizer {
+    fn default() -> Self { AdamOptimizer::new(0.9, 0.999, 1e-8) }
+}
+
+impl Optimizer for AdamOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        let updates = self.adam_state.update(weights, neuron_gradients, inputs);
+        for (neuron_weights, neuron_updates) in weights.iter_mut().zip(updates.iter()) {
+            for (weight, update) in neuron_weights.iter_mut().zip(neuron_updates.iter()) {
+                *weight += learning_rate * update;
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, epsilon) = (self.adam_state.beta1, self.adam_state.beta2, self.adam_state.epsilon);
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || AdamOptimizer::new(beta1, beta2, epsilon),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
+
+fn frobenius_norm(matrix: &[Vec<Weight>]) -> Weight {
+    matrix.iter().flatten().map(|val| val * val).sum::<Weight>().sqrt()
+}
+
+/// Layer-wise Adaptive Moments (You et al., 2019).  Adam's update is rescaled for each layer so that its size is
+/// proportional to the size of the layer's weights:
+///
+/// ```text
+/// trust_ratio = trust_coeff * ||weights|| / ||adam_update||
+/// weights += learning_rate * trust_ratio * adam_update
+/// ```
+///
+/// Layers with small weights relative to their gradients take small steps, which keeps the very large learning rates
+/// used with huge batches from blowing up any one layer.  Optimizers are per-layer, so the norms are taken over all of
+/// the weights passed to `update_weights`.  While either norm is zero, such as for weights initialized to zero, the
+/// ratio is `trust_coeff` alone.
+pub struct LambOptimizer {
+    pub adam_state: AdamState,
+    pub trust_coeff: Weight,
+    /// The trust ratio used for the most recent update
+    pub last_trust_ratio: Weight,
+    /// A copy of this optimizer that updates the biases, created by the first call to `update_biases`
+    pub bias_optimizer: Option<Box<LambOptimizer>>,
+}
+
+impl LambOptimizer {
+    pub fn new(adam_state: AdamState, trust_coeff: Weight) -> Self {
+        LambOptimizer {
+            adam_state,
+            trust_coeff,
+            last_trust_ratio: trust_coeff,
+            bias_optimizer: None,
+        }
+    }
+}
+
+impl Default for LambOptimizer {
+    fn default() -> Self { LambOptimizer::new(AdamState::default(), 1.) }
+}
+
+impl Optimizer for LambOptimizer {
+    fn update_weights(
+        &mut self,
+        weights: &mut [Vec<Weight>],
+        neuron_gradients: &[Weight],
+        inputs: &[Weight],
+        learning_rate: Weight,
+    ) {
+        let updates = self.adam_state.update(weights, neuron_gradients, inputs);
+        let weight_norm = frobenius_norm(weights);
+        let update_norm = frobenius_norm(&updates);
+        self.last_trust_ratio = if weight_norm > 0. && update_norm > 0. {
+            self.trust_coeff * weight_norm / update_norm
+        } else {
+            self.trust_coeff
+        };
+
+        let step_size = learning_rate * self.last_trust_ratio;
+        for (neuron_weights, neuron_updates) in weights.iter_mut().zip(updates.iter()) {
+            for (weight, update) in neuron_weights.iter_mut().zip(neuron_updates.iter()) {
+                *weight += step_size * update;
+            }
+        }
+    }
+
+    fn update_biases(&mut self, biases: &mut [Weight], neuron_gradients: &[Weight], learning_rate: Weight) {
+        let (beta1, beta2, epsilon) = (self.adam_state.beta1, self.adam_state.beta2, self.adam_state.epsilon);
+        let trust_coeff = self.trust_coeff;
+        update_biases_as_weights(
+            &mut self.bias_optimizer,
+            || LambOptimizer::new(AdamState::new(beta1, beta2, epsilon), trust_coeff),
+            biases,
+            neuron_gradients,
+            learning_rate,
+        );
+    }
+}
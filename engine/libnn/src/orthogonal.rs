@@ -0,0 +1,75 @@
+use crate::{DenseLayer, Weight};
+
+/// `W^T W - I` for a weight matrix stored as one row per neuron
+fn gram_deviation(weights: &[Vec<Weight>]) -> Vec<Vec<Weight>> {
+    let input_count = weights.first().map_or(0, |row| row.len());
+    (0..input_count)
+        .map(|i| {
+            (0..input_count)
+                .map(|j| {
+                    let dot: Weight = weights.iter().map(|row| row[i] * row[j]).sum();
+                    if i == j {
+                        dot - 1.
+                    } else {
+                        dot
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Penalizes `lambda * ||W^T W - I||_F^2`, pushing the columns of a weight matrix towards an orthonormal set so
+/// that repeatedly applying it neither shrinks nor blows up its inputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthogonalRegularizer {
+    pub lambda: Weight,
+}
+
+impl OrthogonalRegularizer {
+    pub fn new(lambda: Weight) -> Self { OrthogonalRegularizer { lambda } }
+
+    pub fn penalty(&self, weights: &[Vec<Weight>]) -> Weight {
+        self.lambda
+            * gram_deviation(weights)
+                .iter()
+                .flatten()
+                .map(|val| val * val)
+                .sum::<Weight>()
+    }
+
+    /// Returns the negated gradient of the penalty with respect to each weight, `-4 * lambda * W (W^T W - I)`.
+    pub fn gradients(&self, weights: &[Vec<Weight>]) -> Vec<Vec<Weight>> {
+        let deviation = gram_deviation(weights);
+        weights
+            .iter()
+            .map(|row| {
+                (0..row.len())
+                    .map(|j| {
+                        let sum: Weight = row
+                            .iter()
+                            .zip(deviation.iter())
+                            .map(|(weight, dev)| weight * dev[j])
+                            .sum();
+                        -4. * self.lambda * sum
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Moves the layer's weights down the gradient of the penalty.  Call this alongside the layer's regular weight
+    /// update so that the penalty is effectively part of the loss.
+    pub fn apply(&self, layer: &mut DenseLayer, learning_rate: Weight) {
+        if layer.frozen {
+            return;
+        }
+        let gradients = self.gradients(&layer.weights);
+        for (row, gradient_row) in layer.weights.iter_mut().zip(gradients.iter()) {
+            for (weight, gradient) in row.iter_mut().zip(gradient_row.iter()) {
+                *weight += learning_rate * gradient;
+            }
+        }
+        layer.after_weight_update();
+    }
+}
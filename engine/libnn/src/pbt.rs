@@ -0,0 +1,118 @@
+use rand::Rng;
+
+use crate::{LabeledExamples, Network, Weight};
+
+/// The hyperparameters that population-based training tunes for each member of the population
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HyperParams {
+    pub learning_rate: Weight,
+    /// Every weight update also shrinks the weights by a factor of `1 - learning_rate * weight_decay`
+    pub weight_decay: Weight,
+}
+
+/// Population-based training (Jaderberg et al., 2017).  Every member of the population trains on its own, and after
+/// each evaluation period the worst performers are replaced by copies of the best ones, with the copied
+/// hyperparameters perturbed so that the population keeps exploring around whatever is working.
+pub struct PopulationBasedTrainer {
+    pub population: Vec<Network>,
+    pub hyperparams: Vec<HyperParams>,
+    /// Mean validation loss per example of each member at the end of the last `step`
+    pub validation_losses: Vec<Weight>,
+    /// Fraction of the population replaced after each evaluation
+    pub truncation_fraction: Weight,
+    /// Each copied hyperparameter is multiplied by one of these, picked at random
+    pub perturbation_factors: [Weight; 2],
+    /// Index of the next training example, so that successive steps keep working through the data
+    example_ix: usize,
+}
+
+impl PopulationBasedTrainer {
+    pub fn new(population: Vec<Network>, hyperparams: Vec<HyperParams>) -> Self {
+        assert_eq!(population.len(), hyperparams.len());
+        assert!(!population.is_empty(), "The population can't be empty");
+
+        PopulationBasedTrainer {
+            validation_losses: vec![Weight::INFINITY; population.len()],
+            population,
+            hyperparams,
+            truncation_fraction: 0.25,
+            perturbation_factors: [0.8, 1.2],
+            example_ix: 0,
+        }
+    }
+
+    /// Trains every member on the next `eval_interval` training examples, wrapping around at the end of the data, then
+    /// evaluates them on `validation` and replaces the worst with perturbed copies of the best.  Returns the mean
+    /// validation loss of the population after replacement.
+    pub fn step(
+        &mut self,
+        training: LabeledExamples,
+        validation: LabeledExamples,
+        eval_interval: usize,
+        rng: &mut impl Rng,
+    ) -> Weight {
+        let (examples, expected) = training;
+        assert_eq!(examples.len(), expected.len());
+        assert!(!examples.is_empty(), "Need at least one training example");
+
+        for (network, hyperparams) in self.population.iter_mut().zip(self.hyperparams.iter()) {
+            network.learning_rate = hyperparams.learning_rate;
+            for offset in 0..eval_interval {
+                let ix = (self.example_ix + offset) % examples.len();
+                network.train_one_example(&examples[ix], &expected[ix], hyperparams.learning_rate);
+                decay_weights(network, 1. - hyperparams.learning_rate * hyperparams.weight_decay);
+            }
+        }
+        self.example_ix = (self.example_ix + eval_interval) % examples.len();
+
+        for (loss, network) in self.validation_losses.iter_mut().zip(self.population.iter_mut()) {
+            *loss = network.evaluate(validation.0, validation.1).mean_loss_per_example;
+        }
+        self.exploit_and_explore(rng);
+
+        self.validation_losses.iter().sum::<Weight>() / self.population.len() as Weight
+    }
+
+    /// Index of the member with the lowest validation loss at the last evaluation
+    pub fn best_ix(&self) -> usize {
+        (0..self.population.len())
+            .min_by(|&a, &b| self.validation_losses[a].total_cmp(&self.validation_losses[b]))
+            .unwrap()
+    }
+
+    fn exploit_and_explore(&mut self, rng: &mut impl Rng) {
+        let mut ranked: Vec<usize> = (0..self.population.len()).collect();
+        ranked.sort_by(|&a, &b| self.validation_losses[a].total_cmp(&self.validation_losses[b]));
+        let replace_count =
+            ((self.population.len() as Weight * self.truncation_fraction) as usize).min(self.population.len() / 2);
+
+        for rank in 0..replace_count {
+            let (source_ix, target_ix) = (ranked[rank], ranked[ranked.len() - 1 - rank]);
+            self.population[target_ix] = self.population[source_ix].clone();
+            self.validation_losses[target_ix] = self.validation_losses[source_ix];
+
+            let mut perturb = |value: Weight| value * self.perturbation_factors[rng.gen_range(0, 2)];
+            let source = self.hyperparams[source_ix];
+            self.hyperparams[target_ix] = HyperParams {
+                learning_rate: perturb(source.learning_rate),
+                weight_decay: perturb(source.weight_decay),
+            };
+        }
+    }
+}
+
+fn decay_weights(network: &mut Network, factor: Weight) {
+    if factor == 1. {
+        return;
+    }
+    for layer in network.hidden_layers.iter_mut().filter(|layer| !layer.frozen) {
+        layer.weights.iter_mut().flatten().for_each(|weight| *weight *= factor);
+        layer.after_weight_update();
+    }
+    network
+        .outputs
+        .weights
+        .iter_mut()
+        .flatten()
+        .for_each(|weight| *weight *= factor);
+}
@@ -0,0 +1,102 @@
+use crate::Weight;
+
+fn add_encoding(inputs: &[Weight], encoding: &[Weight]) -> Vec<Weight> {
+    debug_assert_eq!(inputs.len(), encoding.len());
+    inputs
+        .iter()
+        .zip(encoding.iter())
+        .map(|(input, encoding)| input + encoding)
+        .collect()
+}
+
+/// Adds a trainable vector for each position to the inputs at that position, letting the network learn how to tell
+/// positions apart.
+///
+/// Gradients are accumulated and applied like those of `EmbeddingLayer`.  The gradient for a position's vector is the
+/// gradient of the cost with respect to the encoded inputs, which `Network::compute_input_gradients` provides.
+#[derive(Clone)]
+pub struct LearnedPositionalEncoding {
+    /// One row of weights for each position
+    pub embeddings: Vec<Vec<Weight>>,
+    pub max_len: usize,
+    /// Gradients accumulated for each position since the last call to `update_weights`
+    pub gradients: Vec<Vec<Weight>>,
+}
+
+impl LearnedPositionalEncoding {
+    pub fn new(max_len: usize, d_model: usize, init_weights: &mut impl FnMut(usize, usize) -> Weight) -> Self {
+        let embeddings = (0..max_len)
+            .map(|position| (0..d_model).map(|dim_ix| init_weights(position, dim_ix)).collect())
+            .collect();
+
+        LearnedPositionalEncoding {
+            embeddings,
+            max_len,
+            gradients: vec![vec![0.; d_model]; max_len],
+        }
+    }
+
+    pub fn encoding(&self, position: usize) -> &[Weight] { &self.embeddings[position] }
+
+    /// Returns `inputs` with the vector for `position` added.
+    pub fn encode(&self, position: usize, inputs: &[Weight]) -> Vec<Weight> {
+        add_encoding(inputs, &self.embeddings[position])
+    }
+
+    pub fn accumulate_gradients(&mut self, position: usize, gradients: &[Weight]) {
+        for (accumulated, &gradient) in self.gradients[position].iter_mut().zip(gradients.iter()) {
+            *accumulated += gradient;
+        }
+    }
+
+    /// Applies all accumulated gradients and clears them.
+    pub fn update_weights(&mut self, learning_rate: Weight) {
+        for (embedding, gradients) in self.embeddings.iter_mut().zip(self.gradients.iter_mut()) {
+            for (weight, gradient) in embedding.iter_mut().zip(gradients.iter_mut()) {
+                *weight += learning_rate * *gradient;
+                *gradient = 0.;
+            }
+        }
+    }
+}
+
+/// The fixed encodings from "Attention Is All You Need": dimension `2i` of position `pos` is
+/// `sin(pos / 10000^(2i / d_model))` and dimension `2i + 1` is the cosine of the same angle.
+#[derive(Clone)]
+pub struct SinusoidalPositionalEncoding {
+    pub encodings: Vec<Vec<Weight>>,
+    pub d_model: usize,
+    pub max_len: usize,
+}
+
+impl SinusoidalPositionalEncoding {
+    pub fn new(d_model: usize, max_len: usize) -> Self {
+        let encodings = (0..max_len)
+            .map(|position| {
+                (0..d_model)
+                    .map(|dim_ix| {
+                        let pair_ix = (dim_ix / 2) as Weight;
+                        let angle = position as Weight / (10_000 as Weight).powf(2. * pair_ix / d_model as Weight);
+                        if dim_ix % 2 == 0 {
+                            angle.sin()
+                        } else {
+                            angle.cos()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        SinusoidalPositionalEncoding {
+            encodings,
+            d_model,
+            max_len,
+        }
+    }
+
+    pub fn encoding(&self, position: usize) -> &[Weight] { &self.encodings[position] }
+
+    pub fn encode(&self, position: usize, inputs: &[Weight]) -> Vec<Weight> {
+        add_encoding(inputs, &self.encodings[position])
+    }
+}
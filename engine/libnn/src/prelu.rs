@@ -0,0 +1,40 @@
+use crate::Weight;
+
+/// Parametric ReLU: `x` for non-negative inputs and `alpha * x` otherwise, with `alpha` learned along with the rest of
+/// the network.  It can't be an `ActivationFunction` since those are shared statics without trainable state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PRelu {
+    pub alpha: Weight,
+}
+
+impl PRelu {
+    pub fn new(alpha: Weight) -> Self { PRelu { alpha } }
+
+    pub fn forward_propagate(&self, inputs: &[Weight]) -> Vec<Weight> {
+        inputs
+            .iter()
+            .map(|&x| if x >= 0. { x } else { self.alpha * x })
+            .collect()
+    }
+
+    /// Fills `dst` with the gradients with respect to `inputs` given `output_gradients`, which like the neuron
+    /// gradients of other layers are negated gradients of the cost.  Returns the gradient for `alpha`, in the same
+    /// form, to be passed to `update_alpha`.
+    pub fn compute_gradients(&self, inputs: &[Weight], output_gradients: &[Weight], dst: &mut [Weight]) -> Weight {
+        debug_assert_eq!(inputs.len(), output_gradients.len());
+        debug_assert_eq!(inputs.len(), dst.len());
+
+        let mut alpha_gradient = 0.;
+        for ((input_gradient, &x), &output_gradient) in dst.iter_mut().zip(inputs.iter()).zip(output_gradients.iter()) {
+            if x >= 0. {
+                *input_gradient = output_gradient;
+            } else {
+                *input_gradient = self.alpha * output_gradient;
+                alpha_gradient += output_gradient * x;
+            }
+        }
+        alpha_gradient
+    }
+
+    pub fn update_alpha(&mut self, learning_rate: Weight, gradient: Weight) { self.alpha += learning_rate * gradient; }
+}
@@ -0,0 +1,62 @@
+use std::time::Instant;
+
+use crate::{Network, Weight};
+
+/// Time spent in each phase of training, summed over every call since the last reset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub forward_time_ns: u64,
+    pub backward_time_ns: u64,
+    pub weight_update_time_ns: u64,
+}
+
+/// Wraps a `Network`, timing its forward passes, backward passes, and weight updates.
+///
+/// `std::time::Instant` panics on `wasm32-unknown-unknown`, so this can't be used from the web build.
+pub struct ProfilingNetwork {
+    pub network: Network,
+    profile: Profile,
+}
+
+fn elapsed_ns(start: Instant) -> u64 { start.elapsed().as_nanos() as u64 }
+
+impl ProfilingNetwork {
+    pub fn new(network: Network) -> Self {
+        ProfilingNetwork {
+            network,
+            profile: Profile::default(),
+        }
+    }
+
+    pub fn get_profile(&self) -> &Profile { &self.profile }
+
+    pub fn reset_profile(&mut self) { self.profile = Profile::default(); }
+
+    pub fn into_inner(self) -> Network { self.network }
+
+    pub fn compute(&mut self, inputs: &[Weight]) -> &[Weight] {
+        let start = Instant::now();
+        self.network.forward_propagate(inputs);
+        self.profile.forward_time_ns += elapsed_ns(start);
+        &self.network.outputs.outputs
+    }
+
+    /// Same as `Network::train_one_example`, with each phase timed separately.
+    pub fn train_one_example(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+        let start = Instant::now();
+        self.network.forward_propagate(example);
+        self.profile.forward_time_ns += elapsed_ns(start);
+
+        let start = Instant::now();
+        self.network.outputs.compute_costs(expected);
+        self.network.outputs.compute_gradients();
+        self.network.compute_hidden_layer_gradients();
+        self.profile.backward_time_ns += elapsed_ns(start);
+
+        let start = Instant::now();
+        self.network.update_weights(example, learning_rate);
+        self.profile.weight_update_time_ns += elapsed_ns(start);
+
+        self.network.mean_cost()
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{DenseLayer, Network, Weight};
+
+/// How many weights a call to `prune_by_magnitude` pruned
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Newly pruned weights in each layer, in order.  A single `DenseLayer` reports one entry.
+    pub per_layer: Vec<usize>,
+    pub total: usize,
+}
+
+impl DenseLayer {
+    /// Zeroes every weight whose magnitude is below `threshold` and records it in `prune_mask`.  While
+    /// `apply_mask_during_update` is set, every later update keeps pruned weights at zero, maintaining the sparsity.
+    ///
+    /// Weights pruned by earlier calls stay pruned and aren't counted again, so a threshold of 0 prunes nothing and
+    /// leaves the layer's outputs unchanged.  Biases are never pruned.
+    pub fn prune_by_magnitude(&mut self, threshold: Weight) -> PruneReport {
+        let mut mask = self
+            .prune_mask
+            .take()
+            .unwrap_or_else(|| self.weights.iter().map(|row| vec![true; row.len()]).collect());
+        let mut pruned_count = 0;
+        for (row, mask_row) in self.weights.iter().zip(mask.iter_mut()) {
+            for (weight, kept) in row.iter().zip(mask_row.iter_mut()) {
+                if *kept && weight.abs() < threshold {
+                    *kept = false;
+                    pruned_count += 1;
+                }
+            }
+        }
+        self.prune_mask = Some(mask);
+        self.apply_prune_mask();
+        self.apply_spectral_normalization();
+        PruneReport {
+            per_layer: vec![pruned_count],
+            total: pruned_count,
+        }
+    }
+
+    /// Zeroes every weight that has been pruned, whether or not `apply_mask_during_update` is set.
+    pub fn apply_prune_mask(&mut self) {
+        if let Some(mask) = &self.prune_mask {
+            for (row, mask_row) in self.weights.iter_mut().zip(mask.iter()) {
+                for (weight, &kept) in row.iter_mut().zip(mask_row.iter()) {
+                    if !kept {
+                        *weight = 0.;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Network {
+    /// Prunes every hidden layer with `DenseLayer::prune_by_magnitude`.  The output layer is left dense.
+    pub fn prune_by_magnitude(&mut self, threshold: Weight) -> PruneReport {
+        let per_layer: Vec<usize> = self
+            .hidden_layers
+            .iter_mut()
+            .map(|layer| layer.prune_by_magnitude(threshold).total)
+            .collect();
+        PruneReport {
+            total: per_layer.iter().sum(),
+            per_layer,
+        }
+    }
+}
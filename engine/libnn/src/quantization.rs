@@ -0,0 +1,150 @@
+use crate::{ActivationFunction, DenseLayer, Weight};
+
+/// How weights are rounded for quantization-aware training.  Quantization is symmetric around zero, so `bits` bits
+/// represent the integers in `-(2^(bits - 1) - 1)..=2^(bits - 1) - 1`, each multiplied by a scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantizationConfig {
+    pub bits: u8,
+    /// If true, each neuron's weights get their own scale.  Otherwise one scale is shared by the whole layer.
+    pub per_channel: bool,
+}
+
+impl QuantizationConfig {
+    pub fn new(bits: u8, per_channel: bool) -> Self {
+        assert!((2..=8).contains(&bits), "Quantization needs between 2 and 8 bits");
+        QuantizationConfig { bits, per_channel }
+    }
+
+    fn max_level(&self) -> Weight { ((1 << (self.bits - 1)) - 1) as Weight }
+
+    /// Returns the scale for each neuron's weights, which maps the largest weight magnitude onto the highest level.
+    fn scales(&self, weights: &[Vec<Weight>]) -> Vec<Weight> {
+        let max_abs = |row: &Vec<Weight>| row.iter().fold(0., |acc: Weight, w| acc.max(w.abs()));
+        let to_scale = |max_abs: Weight| if max_abs > 0. { max_abs / self.max_level() } else { 1. };
+        if self.per_channel {
+            weights.iter().map(|row| to_scale(max_abs(row))).collect()
+        } else {
+            let scale = to_scale(weights.iter().map(max_abs).fold(0., Weight::max));
+            vec![scale; weights.len()]
+        }
+    }
+}
+
+/// Wraps a `DenseLayer` so that its forward pass sees only weights rounded to the levels that `config` can represent,
+/// letting the rest of the network learn to tolerate the rounding before the layer is converted with
+/// `to_quantized_inference_layer`.
+///
+/// Rounding has a derivative of zero almost everywhere, so gradients instead pass straight through it: they are
+/// computed against the rounded weights but applied to `full_precision_weights`, which are re-rounded after every
+/// update.
+#[derive(Clone)]
+pub struct QuantizedDenseLayer {
+    /// The wrapped layer, whose `weights` hold the fake-quantized weights used by the forward pass
+    pub layer: DenseLayer,
+    pub full_precision_weights: Vec<Vec<Weight>>,
+    pub config: QuantizationConfig,
+}
+
+impl QuantizedDenseLayer {
+    pub fn new(layer: DenseLayer, config: QuantizationConfig) -> Self {
+        let mut quantized = QuantizedDenseLayer {
+            full_precision_weights: layer.weights.clone(),
+            layer,
+            config,
+        };
+        quantized.quantize_weights();
+        quantized
+    }
+
+    /// Overwrites the wrapped layer's weights with `full_precision_weights` rounded to the nearest level.
+    fn quantize_weights(&mut self) {
+        let scales = self.config.scales(&self.full_precision_weights);
+        for ((quantized, full_precision), scale) in self
+            .layer
+            .weights
+            .iter_mut()
+            .zip(self.full_precision_weights.iter())
+            .zip(scales)
+        {
+            for (quantized, &weight) in quantized.iter_mut().zip(full_precision.iter()) {
+                *quantized = (weight / scale).round() * scale;
+            }
+        }
+    }
+
+    pub fn forward_propagate(&mut self, inputs: &[Weight]) { self.layer.forward_propagate(inputs); }
+
+    pub fn outputs(&self) -> &[Weight] { &self.layer.outputs }
+
+    pub fn compute_gradients(&mut self, output_weights: &[Vec<Weight>], gradient_of_output_neurons: &[Weight]) {
+        self.layer.compute_gradients(output_weights, gradient_of_output_neurons);
+    }
+
+    pub fn update_weights(&mut self, inputs: &[Weight], learning_rate: Weight) {
+        std::mem::swap(&mut self.layer.weights, &mut self.full_precision_weights);
+        self.layer.update_weights(inputs, learning_rate);
+        std::mem::swap(&mut self.layer.weights, &mut self.full_precision_weights);
+        self.quantize_weights();
+    }
+
+    pub fn update_biases(&mut self, learning_rate: Weight) { self.layer.update_biases(learning_rate); }
+
+    /// Converts the trained weights into integers for inference.
+    pub fn to_quantized_inference_layer(&self) -> QuantizedInferenceLayer {
+        let weight_scales = self.config.scales(&self.full_precision_weights);
+        let weights = self
+            .full_precision_weights
+            .iter()
+            .zip(weight_scales.iter())
+            .map(|(row, &scale)| row.iter().map(|&weight| (weight / scale).round() as i8).collect())
+            .collect();
+
+        QuantizedInferenceLayer {
+            weights,
+            weight_scales,
+            biases: self.layer.biases.clone(),
+            activation_fn: self.layer.activation_fn,
+        }
+    }
+}
+
+/// A dense layer with integer weights.  Inputs are quantized to 8 bits with a scale chosen for each call, so the
+/// weighted sums are computed entirely with integers.  Only rescaling the sums, adding the biases, and the activation
+/// function use floating point.
+#[derive(Clone)]
+pub struct QuantizedInferenceLayer {
+    pub weights: Vec<Vec<i8>>,
+    /// Multiplying one of a neuron's integer weights by its scale gives the weight it approximates
+    pub weight_scales: Vec<Weight>,
+    /// Empty if the layer was built without biases
+    pub biases: Vec<Weight>,
+    pub activation_fn: &'static dyn ActivationFunction,
+}
+
+impl QuantizedInferenceLayer {
+    pub fn forward_propagate(&self, inputs: &[Weight]) -> Vec<Weight> {
+        let max_abs_input = inputs.iter().fold(0., |acc: Weight, x| acc.max(x.abs()));
+        let input_scale = if max_abs_input > 0. { max_abs_input / 127. } else { 1. };
+        let quantized_inputs: Vec<i32> = inputs.iter().map(|&x| (x / input_scale).round() as i32).collect();
+
+        let outputs_before_activation: Vec<Weight> = self
+            .weights
+            .iter()
+            .zip(self.weight_scales.iter())
+            .enumerate()
+            .map(|(neuron_ix, (neuron_weights, &weight_scale))| {
+                let sum: i32 = neuron_weights
+                    .iter()
+                    .zip(quantized_inputs.iter())
+                    .map(|(&weight, &input)| weight as i32 * input)
+                    .sum();
+                let bias = self.biases.get(neuron_ix).copied().unwrap_or(0.);
+                sum as Weight * weight_scale * input_scale + bias
+            })
+            .collect();
+
+        let mut outputs = vec![0.; outputs_before_activation.len()];
+        self.activation_fn.apply_batch(&mut outputs, &outputs_before_activation);
+        outputs
+    }
+}
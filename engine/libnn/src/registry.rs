@@ -0,0 +1,145 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{OnceLock, RwLock},
+};
+
+use crate::{
+    ActivationFunction, CostFunction, Elu, FocalLoss, NdcgLoss, Weight, AMEO, ELU, FOCAL_LOSS, GAUSSIAN, GCU, GELU,
+    GELU_TANH, HINGE_LOSS, IDENTITY, KL_DIVERGENCE_LOSS, LEAKY_RELU, LISTNET_LOSS, MEAN_ABSOLUTE_ERROR,
+    MEAN_SQUARED_ERROR, MULTICLASS_HINGE_LOSS, NDCG_LOSS, RELU, SELU, SIGMOID, STABLE_SIGMOID, SWISH, TANH,
+};
+
+/// Names are `&'static str`s, so the names of functions with custom parameters are leaked.  Keeping them here means
+/// each distinct name is only leaked once, however often it's asked for.
+fn interned_names() -> &'static RwLock<HashSet<&'static str>> {
+    static NAMES: OnceLock<RwLock<HashSet<&'static str>>> = OnceLock::new();
+    NAMES.get_or_init(Default::default)
+}
+
+/// The name of a built-in function with custom parameters, `base(param,param,...)`.  Floats are formatted with just
+/// enough digits to be parsed back exactly, so looking the name up gives back a function with the same parameters.
+pub(crate) fn parameterized_name(base: &str, params: &[Weight]) -> &'static str {
+    let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+    let name = format!("{}({})", base, params.join(","));
+    if let Some(&interned) = interned_names().read().unwrap().get(name.as_str()) {
+        return interned;
+    }
+    let mut names = interned_names().write().unwrap();
+    if let Some(&interned) = names.get(name.as_str()) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// Splits a name made by `parameterized_name` back into its base and parameters.
+fn parse_parameterized_name(name: &str) -> Option<(&str, Vec<Weight>)> {
+    let (base, params) = name.strip_suffix(')')?.split_once('(')?;
+    let params = params
+        .split(',')
+        .map(|param| param.parse().ok())
+        .collect::<Option<_>>()?;
+    Some((base, params))
+}
+
+type ActivationFnMap = HashMap<String, &'static (dyn ActivationFunction + Sync)>;
+
+fn activation_fns() -> &'static RwLock<ActivationFnMap> {
+    static ACTIVATION_FNS: OnceLock<RwLock<ActivationFnMap>> = OnceLock::new();
+    ACTIVATION_FNS.get_or_init(|| {
+        let builtins: [&'static (dyn ActivationFunction + Sync); 14] = [
+            &SIGMOID,
+            &STABLE_SIGMOID,
+            &TANH,
+            &IDENTITY,
+            &RELU,
+            &LEAKY_RELU,
+            &GCU,
+            &GAUSSIAN,
+            &SWISH,
+            &GELU,
+            &GELU_TANH,
+            &ELU,
+            &SELU,
+            &AMEO,
+        ];
+        RwLock::new(builtins.iter().map(|&f| (f.name().to_owned(), f)).collect())
+    })
+}
+
+/// Maps names to activation functions so that saved networks can refer to them.  All built-in functions are
+/// registered under the names returned by their `ActivationFunction::name`, and names of built-in functions with
+/// custom parameters, such as `elu(0.5)`, are turned into a matching function when they are first looked up.
+pub struct ActivationFunctionRegistry;
+
+impl ActivationFunctionRegistry {
+    /// Registers `f` under `name`, replacing any function already registered with that name.  The registry is shared
+    /// between threads, hence the `Sync` bound, which any `static` already satisfies.
+    pub fn register(name: &str, f: &'static (dyn ActivationFunction + Sync)) {
+        activation_fns().write().unwrap().insert(name.to_owned(), f);
+    }
+
+    pub fn lookup(name: &str) -> Option<&'static dyn ActivationFunction> {
+        let f = activation_fns().read().unwrap().get(name).copied();
+        f.or_else(|| {
+            let (base, params) = parse_parameterized_name(name)?;
+            let f: &'static (dyn ActivationFunction + Sync) = match (base, params.as_slice()) {
+                ("elu", &[alpha]) => Box::leak(Box::new(Elu { alpha })),
+                _ => return None,
+            };
+            Self::register(name, f);
+            Some(f)
+        })
+        .map(|f| f as &'static dyn ActivationFunction)
+    }
+}
+
+pub fn register_activation_fn(name: &str, f: &'static (dyn ActivationFunction + Sync)) {
+    ActivationFunctionRegistry::register(name, f)
+}
+
+type CostFnMap = HashMap<String, &'static (dyn CostFunction + Sync)>;
+
+fn cost_fns() -> &'static RwLock<CostFnMap> {
+    static COST_FNS: OnceLock<RwLock<CostFnMap>> = OnceLock::new();
+    COST_FNS.get_or_init(|| {
+        let builtins: [&'static (dyn CostFunction + Sync); 8] = [
+            &MEAN_SQUARED_ERROR,
+            &MEAN_ABSOLUTE_ERROR,
+            &HINGE_LOSS,
+            &MULTICLASS_HINGE_LOSS,
+            &FOCAL_LOSS,
+            &KL_DIVERGENCE_LOSS,
+            &LISTNET_LOSS,
+            &NDCG_LOSS,
+        ];
+        RwLock::new(builtins.iter().map(|&f| (f.name().to_owned(), f)).collect())
+    })
+}
+
+/// Maps names to cost functions so that saved networks can refer to them, just like `ActivationFunctionRegistry`.
+/// Names of built-in costs with custom parameters, such as `focal(0.5,1)`, don't need to be registered; looking one
+/// up creates and registers a matching function.
+pub struct CostFunctionRegistry;
+
+impl CostFunctionRegistry {
+    pub fn register(name: &str, f: &'static (dyn CostFunction + Sync)) {
+        cost_fns().write().unwrap().insert(name.to_owned(), f);
+    }
+
+    pub fn lookup(name: &str) -> Option<&'static dyn CostFunction> {
+        let f = cost_fns().read().unwrap().get(name).copied();
+        f.or_else(|| {
+            let (base, params) = parse_parameterized_name(name)?;
+            let f: &'static (dyn CostFunction + Sync) = match (base, params.as_slice()) {
+                ("focal", &[alpha, gamma]) => Box::leak(Box::new(FocalLoss { alpha, gamma })),
+                ("ndcg", &[temperature]) => Box::leak(Box::new(NdcgLoss { temperature })),
+                _ => return None,
+            };
+            Self::register(name, f);
+            Some(f)
+        })
+        .map(|f| f as &'static dyn CostFunction)
+    }
+}
@@ -0,0 +1,140 @@
+use crate::Weight;
+
+/// Number of times the matrix is squared when estimating its spectral radius, giving `A^(2^k)`
+const SPECTRAL_RADIUS_SQUARINGS: usize = 12;
+
+/// The reservoir of an echo state network: a fixed, randomly connected recurrent layer whose state is read by a
+/// trainable output layer.
+///
+/// None of these weights are trained, which avoids backpropagation through time entirely.  Only a readout from the
+/// states returned by `step` needs training, for example an `OutputLayer` or a whole `Network` fed those states.  The
+/// reservoir weights are scaled to `spectral_radius`, which should be below 1 for the influence of old inputs to fade.
+#[derive(Clone)]
+pub struct ReservoirLayer {
+    /// `reservoir_weights[i][j]` is the weight from unit `j` to unit `i` at the previous step
+    pub reservoir_weights: Vec<Vec<Weight>>,
+    pub input_weights: Vec<Vec<Weight>>,
+    pub state: Vec<Weight>,
+    pub spectral_radius: f32,
+}
+
+impl ReservoirLayer {
+    pub fn new(
+        input_count: usize,
+        reservoir_size: usize,
+        spectral_radius: f32,
+        init_input_weights: &mut impl FnMut(usize, usize) -> Weight,
+        init_reservoir_weights: &mut impl FnMut(usize, usize) -> Weight,
+    ) -> Self {
+        let input_weights = (0..reservoir_size)
+            .map(|unit_ix| {
+                (0..input_count)
+                    .map(|input_ix| init_input_weights(unit_ix, input_ix))
+                    .collect()
+            })
+            .collect();
+        let reservoir_weights = (0..reservoir_size)
+            .map(|unit_ix| {
+                (0..reservoir_size)
+                    .map(|src_ix| init_reservoir_weights(unit_ix, src_ix))
+                    .collect()
+            })
+            .collect();
+
+        let mut layer = ReservoirLayer {
+            reservoir_weights,
+            input_weights,
+            state: vec![0.; reservoir_size],
+            spectral_radius,
+        };
+        layer.set_spectral_radius(spectral_radius);
+        layer
+    }
+
+    /// Rescales the reservoir weights so that their spectral radius is `r`.
+    pub fn set_spectral_radius(&mut self, r: f32) {
+        let current = estimate_spectral_radius(&self.reservoir_weights);
+        if current > 0. {
+            let scale = r / current;
+            for weight in self.reservoir_weights.iter_mut().flatten() {
+                *weight *= scale;
+            }
+        }
+        self.spectral_radius = r;
+    }
+
+    /// Advances the reservoir by one step with `inputs`, returning the new state.
+    pub fn step(&mut self, inputs: &[Weight]) -> &[Weight] {
+        let new_state: Vec<Weight> = self
+            .input_weights
+            .iter()
+            .zip(self.reservoir_weights.iter())
+            .map(|(input_weights, reservoir_weights)| {
+                let input_sum: Weight = input_weights.iter().zip(inputs.iter()).map(|(w, x)| w * x).sum();
+                let state_sum: Weight = reservoir_weights
+                    .iter()
+                    .zip(self.state.iter())
+                    .map(|(w, s)| w * s)
+                    .sum();
+                (input_sum + state_sum).tanh()
+            })
+            .collect();
+        self.state = new_state;
+        &self.state
+    }
+
+    pub fn reset_state(&mut self) { self.state.fill(0.); }
+
+    /// Runs the whole sequence through the reservoir from a reset state, returning the state after each step.
+    pub fn collect_states(&mut self, sequence: &[Vec<Weight>]) -> Vec<Vec<Weight>> {
+        self.reset_state();
+        sequence.iter().map(|inputs| self.step(inputs).to_owned()).collect()
+    }
+}
+
+/// Estimates the largest eigenvalue magnitude of a square matrix using Gelfand's formula, `rho(A) = lim
+/// ||A^k||^(1/k)`.  Unlike power iteration, this converges even when the dominant eigenvalues are a complex pair, as
+/// they usually are for random matrices.
+pub fn estimate_spectral_radius(matrix: &[Vec<Weight>]) -> f32 {
+    let size = matrix.len();
+    let mut power: Vec<Vec<f64>> = matrix
+        .iter()
+        .map(|row| row.iter().map(|&weight| weight as f64).collect())
+        .collect();
+    // `power` holds `A^(2^i) / exp(log_scale)`, renormalized every step so that it doesn't overflow
+    let mut log_scale = 0.;
+    let mut exponent = 1.;
+    for _ in 0..SPECTRAL_RADIUS_SQUARINGS {
+        let norm = frobenius_norm(&power);
+        if norm == 0. {
+            return 0.;
+        }
+        for val in power.iter_mut().flatten() {
+            *val /= norm;
+        }
+        log_scale += norm.ln();
+
+        let mut squared = vec![vec![0.; size]; size];
+        for (row, dst_row) in power.iter().zip(squared.iter_mut()) {
+            for (k, &val) in row.iter().enumerate() {
+                if val == 0. {
+                    continue;
+                }
+                for (dst, &other) in dst_row.iter_mut().zip(power[k].iter()) {
+                    *dst += val * other;
+                }
+            }
+        }
+        power = squared;
+        log_scale *= 2.;
+        exponent *= 2.;
+    }
+
+    let norm = frobenius_norm(&power);
+    if norm == 0. {
+        return 0.;
+    }
+    ((log_scale + norm.ln()) / exponent).exp() as f32
+}
+
+fn frobenius_norm(matrix: &[Vec<f64>]) -> f64 { matrix.iter().flatten().map(|val| val * val).sum::<f64>().sqrt() }
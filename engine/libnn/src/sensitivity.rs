@@ -0,0 +1,94 @@
+use crate::{Network, Weight};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightSensitivity {
+    /// Index into `Network::hidden_layers`, or `hidden_layers.len()` for the output layer
+    pub layer_ix: usize,
+    pub neuron_ix: usize,
+    pub weight_ix: usize,
+    /// Mean absolute change in loss from nudging the weight up and down
+    pub sensitivity: Weight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerSensitivity {
+    pub layer_ix: usize,
+    pub mean_sensitivity: Weight,
+    pub max_sensitivity: Weight,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitivityReport {
+    /// Every weight in the network, most sensitive first
+    pub weights: Vec<WeightSensitivity>,
+    /// One summary per hidden layer followed by one for the output layer
+    pub layers: Vec<LayerSensitivity>,
+    /// Loss of the unperturbed network
+    pub base_loss: Weight,
+}
+
+impl Network {
+    fn weights_mut(&mut self, layer_ix: usize) -> &mut Vec<Vec<Weight>> {
+        match self.hidden_layers.get_mut(layer_ix) {
+            Some(layer) => &mut layer.weights,
+            None => &mut self.outputs.weights,
+        }
+    }
+
+    /// Measures how much the mean loss over `examples` changes when each weight is moved by `epsilon` in either
+    /// direction, one weight at a time.  Biases aren't perturbed.  All weights are restored before returning.
+    ///
+    /// This evaluates the whole data set twice per weight, so it's only practical for small networks.
+    pub fn weight_sensitivity(
+        &mut self,
+        examples: &[Vec<Weight>],
+        expected: &[Vec<Weight>],
+        epsilon: Weight,
+    ) -> SensitivityReport {
+        let base_loss = self.evaluate(examples, expected).mean_loss_per_example;
+
+        let mut weights = Vec::new();
+        let mut layers = Vec::with_capacity(self.hidden_layers.len() + 1);
+        for layer_ix in 0..=self.hidden_layers.len() {
+            let layer_start = weights.len();
+            let neuron_count = self.weights_mut(layer_ix).len();
+            for neuron_ix in 0..neuron_count {
+                let weight_count = self.weights_mut(layer_ix)[neuron_ix].len();
+                for weight_ix in 0..weight_count {
+                    let original = self.weights_mut(layer_ix)[neuron_ix][weight_ix];
+
+                    self.weights_mut(layer_ix)[neuron_ix][weight_ix] = original + epsilon;
+                    let loss_up = self.evaluate(examples, expected).mean_loss_per_example;
+                    self.weights_mut(layer_ix)[neuron_ix][weight_ix] = original - epsilon;
+                    let loss_down = self.evaluate(examples, expected).mean_loss_per_example;
+                    self.weights_mut(layer_ix)[neuron_ix][weight_ix] = original;
+
+                    weights.push(WeightSensitivity {
+                        layer_ix,
+                        neuron_ix,
+                        weight_ix,
+                        sensitivity: ((loss_up - base_loss).abs() + (loss_down - base_loss).abs()) / 2.,
+                    });
+                }
+            }
+
+            let layer_weights = &weights[layer_start..];
+            layers.push(LayerSensitivity {
+                layer_ix,
+                mean_sensitivity: layer_weights.iter().map(|weight| weight.sensitivity).sum::<Weight>()
+                    / layer_weights.len() as Weight,
+                max_sensitivity: layer_weights
+                    .iter()
+                    .map(|weight| weight.sensitivity)
+                    .fold(0., Weight::max),
+            });
+        }
+
+        weights.sort_by(|a, b| b.sensitivity.total_cmp(&a.sensitivity));
+        SensitivityReport {
+            weights,
+            layers,
+            base_loss,
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    ActivationFunction, ActivationFunctionRegistry, CostFunctionRegistry, DenseLayer, Network, OutputLayer, Weight,
+};
+
+const WEIGHTS_MAGIC: &[u8; 4] = b"LNNW";
+const WEIGHTS_VERSION: u32 = 1;
+const MODEL_MAGIC: &[u8; 4] = b"LNNM";
+const MODEL_VERSION: u32 = 2;
+
+pub(crate) fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+pub(crate) fn write_u32(writer: &mut impl Write, val: u32) -> io::Result<()> { writer.write_all(&val.to_le_bytes()) }
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn write_weights(writer: &mut impl Write, weights: &[Weight]) -> io::Result<()> {
+    for &weight in weights {
+        writer.write_all(&weight.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_weights(reader: &mut impl Read, dst: &mut [Weight]) -> io::Result<()> {
+    let mut buf = [0u8; 4];
+    for weight in dst {
+        reader.read_exact(&mut buf)?;
+        *weight = Weight::from_le_bytes(buf);
+    }
+    Ok(())
+}
+
+fn write_str(writer: &mut impl Write, val: &str) -> io::Result<()> {
+    write_u32(writer, val.len() as u32)?;
+    writer.write_all(val.as_bytes())
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    // The length can't be trusted, so the buffer only grows as bytes actually arrive
+    let mut buf = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|_| invalid_data("Name is not valid UTF-8"))
+}
+
+fn read_magic(reader: &mut impl Read, expected_magic: &[u8; 4], expected_version: u32, what: &str) -> io::Result<()> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != expected_magic {
+        return Err(invalid_data(format!("Not a saved {}", what)));
+    }
+    let version = read_u32(reader)?;
+    if version != expected_version {
+        return Err(invalid_data(format!("Unsupported {} version {}", what, version)));
+    }
+    Ok(())
+}
+
+fn lookup_activation_fn(name: &str) -> io::Result<&'static dyn ActivationFunction> {
+    ActivationFunctionRegistry::lookup(name)
+        .ok_or_else(|| invalid_data(format!("No activation function registered as \"{}\"", name)))
+}
+
+/// Makes sure that a layer with `input_count` inputs can follow the last of `hidden_layers`.
+fn check_input_count(hidden_layers: &[DenseLayer], input_count: usize) -> io::Result<()> {
+    match hidden_layers.last() {
+        Some(prev) if prev.outputs.len() != input_count => Err(invalid_data(format!(
+            "Layer with {} inputs can't follow a layer with {} neurons",
+            input_count,
+            prev.outputs.len()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn write_matrix(writer: &mut impl Write, matrix: &[Vec<Weight>]) -> io::Result<()> {
+    write_u32(writer, matrix.len() as u32)?;
+    write_u32(writer, matrix.first().map(Vec::len).unwrap_or(0) as u32)?;
+    for row in matrix {
+        write_weights(writer, row)?;
+    }
+    Ok(())
+}
+
+/// Reads a matrix written by `write_matrix` one weight at a time.  Its dimensions can't be trusted, so memory is only
+/// allocated for weights that are actually there, and a header claiming more than the input holds fails once it runs
+/// out rather than allocating the whole matrix up front.
+fn read_matrix(reader: &mut impl Read) -> io::Result<Vec<Vec<Weight>>> {
+    let row_count = read_u32(reader)? as usize;
+    let col_count = read_u32(reader)? as usize;
+    // Rows without any weights would take no input to claim, and no layer can be built without inputs anyway
+    if row_count > 0 && col_count == 0 {
+        return Err(invalid_data(format!(
+            "Weight matrix with {} rows has no columns",
+            row_count
+        )));
+    }
+
+    let mut matrix = Vec::new();
+    for _ in 0..row_count {
+        let mut row = Vec::new();
+        let mut weight = [0.];
+        for _ in 0..col_count {
+            read_weights(reader, &mut weight)?;
+            row.push(weight[0]);
+        }
+        matrix.push(row);
+    }
+    Ok(matrix)
+}
+
+fn read_matrix_into(reader: &mut impl Read, matrix: &mut [Vec<Weight>]) -> io::Result<()> {
+    let row_count = read_u32(reader)? as usize;
+    let col_count = read_u32(reader)? as usize;
+    let expected_col_count = matrix.first().map(Vec::len).unwrap_or(0);
+    if row_count != matrix.len() || col_count != expected_col_count {
+        return Err(invalid_data(format!(
+            "Expected a {}x{} weight matrix but found {}x{}",
+            matrix.len(),
+            expected_col_count,
+            row_count,
+            col_count
+        )));
+    }
+
+    for row in matrix {
+        read_weights(reader, row)?;
+    }
+    Ok(())
+}
+
+impl Network {
+    /// Writes the weights and biases of every layer.  Activation and cost functions aren't included, so the weights
+    /// can only be loaded back into a network with the same architecture.
+    pub fn save_weights(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(WEIGHTS_MAGIC)?;
+        write_u32(writer, WEIGHTS_VERSION)?;
+        write_u32(writer, self.hidden_layers.len() as u32)?;
+        for layer in &self.hidden_layers {
+            write_matrix(writer, &layer.weights)?;
+            write_u32(writer, layer.biases.len() as u32)?;
+            write_weights(writer, &layer.biases)?;
+        }
+        write_matrix(writer, &self.outputs.weights)
+    }
+
+    /// Loads weights written by `save_weights`, failing if they were saved from a network with a different shape.
+    pub fn load_weights(&mut self, reader: &mut impl Read) -> io::Result<()> {
+        read_magic(reader, WEIGHTS_MAGIC, WEIGHTS_VERSION, "set of network weights")?;
+
+        let hidden_layer_count = read_u32(reader)? as usize;
+        if hidden_layer_count != self.hidden_layers.len() {
+            return Err(invalid_data(format!(
+                "Expected {} hidden layers but found {}",
+                self.hidden_layers.len(),
+                hidden_layer_count
+            )));
+        }
+        for layer in &mut self.hidden_layers {
+            read_matrix_into(reader, &mut layer.weights)?;
+            let bias_count = read_u32(reader)? as usize;
+            if bias_count != layer.biases.len() {
+                return Err(invalid_data(format!(
+                    "Expected {} biases but found {}",
+                    layer.biases.len(),
+                    bias_count
+                )));
+            }
+            read_weights(reader, &mut layer.biases)?;
+        }
+        read_matrix_into(reader, &mut self.outputs.weights)
+    }
+
+    /// Writes the whole network: its shape, the names of its activation and cost functions, and all weights and
+    /// biases.  Any custom functions must be registered with `ActivationFunctionRegistry` or `CostFunctionRegistry` for
+    /// the network to be loaded again.
+    pub fn save(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MODEL_MAGIC)?;
+        write_u32(writer, MODEL_VERSION)?;
+        writer.write_all(&self.learning_rate.to_le_bytes())?;
+        write_u32(writer, self.hidden_layers.len() as u32)?;
+        for layer in &self.hidden_layers {
+            write_str(writer, layer.activation_fn.name())?;
+            writer.write_all(&[layer.use_bias as u8])?;
+            write_matrix(writer, &layer.weights)?;
+            write_weights(writer, &layer.biases)?;
+        }
+        write_str(writer, self.outputs.activation_fn.name())?;
+        write_str(writer, self.outputs.cost_fn.name())?;
+        write_matrix(writer, &self.outputs.weights)
+    }
+
+    /// Loads a network written by `save`, looking up activation and cost functions by name.
+    pub fn load(reader: &mut impl Read) -> io::Result<Network> {
+        read_magic(reader, MODEL_MAGIC, MODEL_VERSION, "network")?;
+        let mut learning_rate = [0.];
+        read_weights(reader, &mut learning_rate)?;
+
+        let hidden_layer_count = read_u32(reader)? as usize;
+        let mut hidden_layers = Vec::new();
+        for _ in 0..hidden_layer_count {
+            let activation_fn = lookup_activation_fn(&read_string(reader)?)?;
+            let mut use_bias = [0u8];
+            reader.read_exact(&mut use_bias)?;
+            let weights = read_matrix(reader)?;
+            let neuron_count = weights.len();
+            let input_count = weights.first().map(Vec::len).unwrap_or(0);
+            check_input_count(&hidden_layers, input_count)?;
+
+            let mut init_weights = |neuron_ix: usize, input_ix: usize| weights[neuron_ix][input_ix];
+            let layer = if use_bias[0] != 0 {
+                let mut biases = vec![0.; neuron_count];
+                read_weights(reader, &mut biases)?;
+                DenseLayer::new(
+                    neuron_count,
+                    input_count,
+                    &mut init_weights,
+                    &mut |neuron_ix| biases[neuron_ix],
+                    activation_fn,
+                )
+            } else {
+                DenseLayer::new_without_bias(neuron_count, input_count, &mut init_weights, activation_fn)
+            };
+            hidden_layers.push(layer);
+        }
+
+        let activation_fn = lookup_activation_fn(&read_string(reader)?)?;
+        let cost_fn_name = read_string(reader)?;
+        let cost_fn = CostFunctionRegistry::lookup(&cost_fn_name)
+            .ok_or_else(|| invalid_data(format!("No cost function registered as \"{}\"", cost_fn_name)))?;
+        let weights = read_matrix(reader)?;
+        if !hidden_layers.is_empty() {
+            check_input_count(&hidden_layers, weights.first().map(Vec::len).unwrap_or(0))?;
+        }
+        let outputs = OutputLayer::new(
+            activation_fn,
+            cost_fn,
+            &mut |neuron_ix, input_ix| weights[neuron_ix][input_ix],
+            weights.first().map(Vec::len).unwrap_or(0),
+            weights.len(),
+        );
+
+        Ok(Network {
+            hidden_layers,
+            outputs: Box::new(outputs),
+            learning_rate: learning_rate[0],
+        })
+    }
+}
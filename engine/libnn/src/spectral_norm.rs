@@ -0,0 +1,83 @@
+use crate::{DenseLayer, Weight};
+
+/// Power iterations run when spectral normalization is first enabled, so that the very first forward pass already
+/// uses a good estimate of the largest singular value
+const WARMUP_ITERATIONS: usize = 20;
+
+fn normalize(vector: &mut [Weight]) -> Weight {
+    let norm = vector.iter().map(|val| val * val).sum::<Weight>().sqrt();
+    if norm > 0. {
+        for val in vector.iter_mut() {
+            *val /= norm;
+        }
+    }
+    norm
+}
+
+/// Takes one step of power iteration towards the leading left singular vector of `weights`, updating `left_vector`
+/// in place, and returns the resulting estimate of the largest singular value.  Repeated calls converge on the
+/// spectral norm of `weights` from below.
+pub fn spectral_norm_step(weights: &[Vec<Weight>], left_vector: &mut [Weight]) -> Weight {
+    debug_assert_eq!(weights.len(), left_vector.len());
+    let input_count = weights.first().map_or(0, |row| row.len());
+    let mut right_vector = vec![0.; input_count];
+    for (row, &u) in weights.iter().zip(left_vector.iter()) {
+        for (v, &weight) in right_vector.iter_mut().zip(row.iter()) {
+            *v += weight * u;
+        }
+    }
+    normalize(&mut right_vector);
+
+    for (u, row) in left_vector.iter_mut().zip(weights.iter()) {
+        *u = row.iter().zip(right_vector.iter()).map(|(weight, v)| weight * v).sum();
+    }
+    normalize(left_vector)
+}
+
+/// The power iteration state of a spectrally normalized `DenseLayer`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectralNorm {
+    /// The estimate of the weights' leading left singular vector
+    pub left_vector: Vec<Weight>,
+    /// The estimate of the weights' largest singular value, which they are divided by when computing outputs
+    pub sigma: Weight,
+}
+
+impl DenseLayer {
+    /// Computes outputs with the weights divided by their largest singular value, which bounds how much the layer can
+    /// stretch its inputs (Miyato et al., 2018).  The singular value is tracked with one step of power iteration per
+    /// weight update, so running examples through the layer without training leaves it untouched.
+    ///
+    /// `weights` keeps the raw weights, so the division happens on the weighted sums rather than in place.  Gradients
+    /// flowing back through the layer and updates to the raw weights are scaled down by the same factor, treating
+    /// the singular value as a constant.
+    pub fn enable_spectral_normalization(&mut self) {
+        let mut left_vector = vec![1.; self.weights.len()];
+        normalize(&mut left_vector);
+        let mut sigma = 0.;
+        for _ in 0..WARMUP_ITERATIONS {
+            sigma = spectral_norm_step(&self.weights, &mut left_vector);
+        }
+        self.spectral_norm = Some(SpectralNorm { left_vector, sigma });
+    }
+
+    pub fn disable_spectral_normalization(&mut self) { self.spectral_norm = None; }
+
+    /// Takes a power iteration step and returns the updated estimate of the spectral norm.  Does nothing and returns
+    /// `None` if spectral normalization isn't enabled.  This is part of `after_weight_update`, so it only needs calling
+    /// directly to refine the estimate.
+    pub fn apply_spectral_normalization(&mut self) -> Option<Weight> {
+        let spectral_norm = self.spectral_norm.as_mut()?;
+        spectral_norm.sigma = spectral_norm_step(&self.weights, &mut spectral_norm.left_vector);
+        Some(spectral_norm.sigma)
+    }
+
+    /// What the raw weights are multiplied by when computing outputs: `1 / sigma` if spectral normalization is
+    /// enabled and 1 otherwise
+    pub fn spectral_norm_scale(&self) -> Weight {
+        match &self.spectral_norm {
+            Some(spectral_norm) if spectral_norm.sigma > 0. => 1. / spectral_norm.sigma,
+            _ => 1.,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{ActivationFunction, CostFunction, Weight};
+
+/// `OutputLayer` with its sizes fixed at compile time.  Weights and every per-neuron buffer are arrays stored inline,
+/// so the layer never touches the heap and the compiler can unroll its loops for small layers.
+///
+/// The methods mirror `OutputLayer`'s, except that sizes are checked by the types instead of at runtime.  The
+/// `Optimizer` trait works on `Vec`-based weights, so there is no `update_weights_with_optimizer`.
+pub struct StaticOutputLayer<const IN: usize, const OUT: usize> {
+    pub weights: [[Weight; IN]; OUT],
+    pub activation_fn: &'static dyn ActivationFunction,
+    pub outputs_before_activation: [Weight; OUT],
+    pub outputs: [Weight; OUT],
+    pub errors: [Weight; OUT],
+    pub costs: [Weight; OUT],
+    pub cost_fn: &'static dyn CostFunction,
+    pub neuron_gradients: [Weight; OUT],
+}
+
+impl<const IN: usize, const OUT: usize> StaticOutputLayer<IN, OUT> {
+    pub fn new(
+        activation_fn: &'static dyn ActivationFunction,
+        cost_fn: &'static dyn CostFunction,
+        init_weights: &mut impl FnMut(usize, usize) -> Weight,
+    ) -> Self {
+        let mut weights = [[0.; IN]; OUT];
+        for (i, neuron_weights) in weights.iter_mut().enumerate() {
+            for (j, weight) in neuron_weights.iter_mut().enumerate() {
+                *weight = init_weights(i, j);
+            }
+        }
+
+        StaticOutputLayer {
+            weights,
+            activation_fn,
+            outputs_before_activation: [0.; OUT],
+            outputs: [0.; OUT],
+            errors: [0.; OUT],
+            costs: [0.; OUT],
+            cost_fn,
+            neuron_gradients: [0.; OUT],
+        }
+    }
+
+    /// Fills `self.outputs` with output values given the outputs from the previous layer in `inputs`.
+    pub fn compute(&mut self, inputs: &[Weight; IN]) { self.forward_propagate(inputs) }
+
+    /// Once `compute()` has been called, calculates the cost using the error for each output value and populates
+    /// `self.costs`.
+    pub fn compute_costs(&mut self, expected: &[Weight; OUT]) {
+        for ((error, &expected), &output) in self.errors.iter_mut().zip(expected.iter()).zip(self.outputs.iter()) {
+            *error = expected - output;
+        }
+        self.cost_fn.compute_costs(&self.outputs, &self.errors, &mut self.costs);
+    }
+
+    /// The gradient of a single neuron for costs that depend on nothing but its error.  `compute_gradients` handles
+    /// every cost, including those that look at the whole layer.
+    pub fn compute_neuron_gradient(&self, neuron_output_before_activation: Weight, neuron_error: Weight) -> Weight {
+        (self.cost_fn).derivative(neuron_error) * (self.activation_fn).derivative(neuron_output_before_activation)
+    }
+
+    /// Once `compute_costs()` has been called, calculates the gradients for each neuron and populates
+    /// `self.neuron_gradients`.
+    pub fn compute_gradients(&mut self) {
+        self.cost_fn
+            .compute_error_gradients(&self.outputs, &self.errors, &mut self.neuron_gradients);
+        for (gradient, &output_before_activation) in self
+            .neuron_gradients
+            .iter_mut()
+            .zip(self.outputs_before_activation.iter())
+        {
+            *gradient *= (self.activation_fn).derivative(output_before_activation);
+        }
+    }
+
+    pub fn update_weights(&mut self, inputs: &[Weight; IN], learning_rate: Weight) {
+        for (neuron_weights, &neuron_gradient) in self.weights.iter_mut().zip(self.neuron_gradients.iter()) {
+            for (weight, &input) in neuron_weights.iter_mut().zip(inputs.iter()) {
+                *weight += learning_rate * neuron_gradient * input;
+            }
+        }
+    }
+
+    /// Once `compute_gradients()` has been called, fills `dst` with the gradient of the cost with respect to each of
+    /// this layer's inputs.
+    pub fn compute_input_gradients(&self, dst: &mut [Weight; IN]) {
+        dst.fill(0.);
+        for (neuron_weights, &neuron_gradient) in self.weights.iter().zip(self.neuron_gradients.iter()) {
+            for (input_gradient, &weight) in dst.iter_mut().zip(neuron_weights.iter()) {
+                *input_gradient += weight * neuron_gradient;
+            }
+        }
+    }
+
+    pub fn forward_propagate(&mut self, inputs: &[Weight; IN]) {
+        for (output, neuron_weights) in self.outputs_before_activation.iter_mut().zip(self.weights.iter()) {
+            *output = neuron_weights
+                .iter()
+                .zip(inputs.iter())
+                .map(|(&weight, &input)| weight * input)
+                .sum();
+        }
+
+        (self.activation_fn).apply_batch(&mut self.outputs, &self.outputs_before_activation);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{Network, Weight};
+
+/// Stochastic weight averaging (Izmailov et al., 2018).  Snapshots of a network's weights taken periodically late in
+/// training, typically with a constant or cyclical learning rate, are averaged.  SGD keeps bouncing around the edges
+/// of a wide minimum, and the average of where it's been lands closer to the middle, which usually generalizes better
+/// than any single snapshot.
+///
+/// `average` holds one flattened vector per layer: the weights followed by the biases of each hidden layer, then the
+/// output layer's weights.
+#[derive(Clone, Debug, Default)]
+pub struct SwaAverager {
+    pub average: Vec<Vec<Weight>>,
+    pub num_models: usize,
+}
+
+impl SwaAverager {
+    pub fn new() -> Self { SwaAverager::default() }
+
+    /// Folds the current weights of `network` into the running average.
+    pub fn update_average(&mut self, network: &Network) {
+        let snapshot = flatten_layers(network);
+        if self.num_models == 0 {
+            self.average = snapshot;
+        } else {
+            assert_eq!(
+                self.average.len(),
+                snapshot.len(),
+                "Network has a different number of layers"
+            );
+            let weight = 1. / (self.num_models + 1) as Weight;
+            for (average, snapshot) in self.average.iter_mut().zip(snapshot.iter()) {
+                assert_eq!(average.len(), snapshot.len(), "Layer has a different number of weights");
+                for (average, &val) in average.iter_mut().zip(snapshot.iter()) {
+                    *average += (val - *average) * weight;
+                }
+            }
+        }
+        self.num_models += 1;
+    }
+
+    /// Overwrites the weights of `network` with the average.  `network` needs to have the same shape as the networks
+    /// that were averaged.
+    pub fn apply_to(&self, network: &mut Network) {
+        assert!(self.num_models > 0, "No weights have been averaged yet");
+        assert_eq!(self.average.len(), network.hidden_layers.len() + 1);
+
+        for (layer, average) in network.hidden_layers.iter_mut().zip(self.average.iter()) {
+            let mut vals = average.iter();
+            for weight in layer.weights.iter_mut().flatten().chain(layer.biases.iter_mut()) {
+                *weight = *vals.next().expect("Layer has a different number of weights");
+            }
+            layer.after_weight_update();
+        }
+        let mut vals = self.average[self.average.len() - 1].iter();
+        for weight in network.outputs.weights.iter_mut().flatten() {
+            *weight = *vals.next().expect("Layer has a different number of weights");
+        }
+    }
+}
+
+fn flatten_layers(network: &Network) -> Vec<Vec<Weight>> {
+    network
+        .hidden_layers
+        .iter()
+        .map(|layer| {
+            layer
+                .weights
+                .iter()
+                .flatten()
+                .chain(layer.biases.iter())
+                .copied()
+                .collect()
+        })
+        .chain(std::iter::once(
+            network.outputs.weights.iter().flatten().copied().collect(),
+        ))
+        .collect()
+}
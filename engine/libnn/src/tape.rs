@@ -0,0 +1,130 @@
+use std::{
+    cell::RefCell,
+    ops::{Add, Mul},
+};
+
+use crate::Weight;
+
+/// One recorded operation: the indices of up to two inputs and the derivative of the result with respect to each
+#[derive(Clone, Copy, Debug)]
+struct TapeNode {
+    parents: [(usize, Weight); 2],
+    parent_count: usize,
+}
+
+/// A Wengert tape for reverse-mode automatic differentiation of scalar expressions.
+///
+/// Every operation on a `Var` is appended to the tape along with its local derivatives, so `gradients` can then walk
+/// the tape backwards once to get the derivative of a result with respect to every variable that went into it.  This
+/// makes it possible to try out a new activation or cost function without deriving its gradient by hand.
+#[derive(Debug, Default)]
+pub struct Tape {
+    nodes: RefCell<Vec<TapeNode>>,
+}
+
+/// A value recorded on a `Tape`
+#[derive(Clone, Copy, Debug)]
+pub struct Var<'t> {
+    pub tape: &'t Tape,
+    /// Position on the tape, which is also this variable's position in the output of `Tape::gradients`
+    pub index: usize,
+    pub value: Weight,
+}
+
+impl Tape {
+    pub fn new() -> Self { Tape::default() }
+
+    pub fn len(&self) -> usize { self.nodes.borrow().len() }
+
+    pub fn is_empty(&self) -> bool { self.nodes.borrow().is_empty() }
+
+    fn push(&self, value: Weight, parents: &[(usize, Weight)]) -> Var<'_> {
+        let mut node = TapeNode {
+            parents: [(0, 0.); 2],
+            parent_count: parents.len(),
+        };
+        node.parents[..parents.len()].copy_from_slice(parents);
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(node);
+        Var {
+            tape: self,
+            index: nodes.len() - 1,
+            value,
+        }
+    }
+
+    /// Records an input variable.  Constants are recorded the same way; their gradients are just never read.
+    pub fn var(&self, value: Weight) -> Var<'_> { self.push(value, &[]) }
+
+    /// Returns the derivative of `output` with respect to every variable on the tape, indexed by `Var::index`.
+    pub fn gradients(&self, output: Var<'_>) -> Vec<Weight> {
+        debug_assert!(std::ptr::eq(output.tape, self), "Var belongs to a different tape");
+        let nodes = self.nodes.borrow();
+        let mut gradients = vec![0.; nodes.len()];
+        gradients[output.index] = 1.;
+        for node_ix in (0..=output.index).rev() {
+            let gradient = gradients[node_ix];
+            if gradient == 0. {
+                continue;
+            }
+            let node = &nodes[node_ix];
+            for &(parent_ix, local_derivative) in &node.parents[..node.parent_count] {
+                gradients[parent_ix] += gradient * local_derivative;
+            }
+        }
+        gradients
+    }
+}
+
+impl<'t> Var<'t> {
+    pub fn exp(self) -> Var<'t> {
+        let value = self.value.exp();
+        self.tape.push(value, &[(self.index, value)])
+    }
+
+    pub fn ln(self) -> Var<'t> { self.tape.push(self.value.ln(), &[(self.index, 1. / self.value)]) }
+
+    /// The larger of the two values.  All of the gradient goes to whichever is larger, or to `self` on a tie.
+    pub fn max(self, other: Var<'t>) -> Var<'t> {
+        debug_assert!(std::ptr::eq(self.tape, other.tape), "Vars belong to different tapes");
+        if self.value >= other.value {
+            self.tape.push(self.value, &[(self.index, 1.), (other.index, 0.)])
+        } else {
+            self.tape.push(other.value, &[(self.index, 0.), (other.index, 1.)])
+        }
+    }
+}
+
+impl<'t> Add for Var<'t> {
+    type Output = Var<'t>;
+
+    fn add(self, other: Var<'t>) -> Var<'t> {
+        debug_assert!(std::ptr::eq(self.tape, other.tape), "Vars belong to different tapes");
+        self.tape
+            .push(self.value + other.value, &[(self.index, 1.), (other.index, 1.)])
+    }
+}
+
+impl<'t> Add<Weight> for Var<'t> {
+    type Output = Var<'t>;
+
+    fn add(self, other: Weight) -> Var<'t> { self.tape.push(self.value + other, &[(self.index, 1.)]) }
+}
+
+impl<'t> Mul for Var<'t> {
+    type Output = Var<'t>;
+
+    fn mul(self, other: Var<'t>) -> Var<'t> {
+        debug_assert!(std::ptr::eq(self.tape, other.tape), "Vars belong to different tapes");
+        self.tape.push(self.value * other.value, &[
+            (self.index, other.value),
+            (other.index, self.value),
+        ])
+    }
+}
+
+impl<'t> Mul<Weight> for Var<'t> {
+    type Output = Var<'t>;
+
+    fn mul(self, other: Weight) -> Var<'t> { self.tape.push(self.value * other, &[(self.index, other)]) }
+}
@@ -0,0 +1,193 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{DenseLayer, Weight};
+
+/// Number of equal-width buckets in each weight histogram
+const HISTOGRAM_BUCKETS: usize = 30;
+
+/// CRC-32C (Castagnoli), which TFRecord uses to check both the length and the data of every record
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0x82f6_3b78 & mask);
+        }
+    }
+    !crc
+}
+
+/// TFRecord stores CRCs rotated and offset so that computing the CRC of data containing CRCs stays well behaved
+pub(crate) fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x80 {
+        buf.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+    buf.push(val as u8);
+}
+
+/// Writes a protobuf field key for `field_number` with the given wire type.
+fn write_key(buf: &mut Vec<u8>, field_number: u64, wire_type: u64) {
+    write_varint(buf, (field_number << 3) | wire_type);
+}
+
+fn write_bytes_field(buf: &mut Vec<u8>, field_number: u64, bytes: &[u8]) {
+    write_key(buf, field_number, 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_double_field(buf: &mut Vec<u8>, field_number: u64, val: f64) {
+    write_key(buf, field_number, 1);
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn write_packed_doubles(buf: &mut Vec<u8>, field_number: u64, vals: &[f64]) {
+    let bytes: Vec<u8> = vals.iter().flat_map(|val| val.to_le_bytes()).collect();
+    write_bytes_field(buf, field_number, &bytes);
+}
+
+/// Encodes a `HistogramProto` with equal-width buckets between the smallest and largest value.
+fn encode_histogram(vals: &[Weight]) -> Vec<u8> {
+    let min = vals.iter().copied().fold(Weight::INFINITY, Weight::min) as f64;
+    let max = vals.iter().copied().fold(Weight::NEG_INFINITY, Weight::max) as f64;
+    let sum: f64 = vals.iter().map(|&val| val as f64).sum();
+    let sum_squares: f64 = vals.iter().map(|&val| (val as f64).powi(2)).sum();
+
+    // Bucket `i` counts the values above the previous limit and at most `bucket_limits[i]`
+    let bucket_count = if max > min { HISTOGRAM_BUCKETS } else { 1 };
+    let width = (max - min) / bucket_count as f64;
+    let bucket_limits: Vec<f64> = (1..=bucket_count)
+        .map(|bucket_ix| {
+            if bucket_ix == bucket_count {
+                max
+            } else {
+                min + width * bucket_ix as f64
+            }
+        })
+        .collect();
+    let mut buckets = vec![0f64; bucket_count];
+    for &val in vals {
+        let bucket_ix = bucket_limits
+            .partition_point(|&limit| limit < val as f64)
+            .min(bucket_count - 1);
+        buckets[bucket_ix] += 1.;
+    }
+
+    let mut buf = Vec::new();
+    write_double_field(&mut buf, 1, min);
+    write_double_field(&mut buf, 2, max);
+    write_double_field(&mut buf, 3, vals.len() as f64);
+    write_double_field(&mut buf, 4, sum);
+    write_double_field(&mut buf, 5, sum_squares);
+    write_packed_doubles(&mut buf, 6, &bucket_limits);
+    write_packed_doubles(&mut buf, 7, &buckets);
+    buf
+}
+
+/// The value of a single summary entry
+enum SummaryValue<'a> {
+    Scalar(f32),
+    Histogram(&'a [Weight]),
+}
+
+/// Encodes a `Summary.Value`.
+fn encode_summary_value(tag: &str, value: SummaryValue) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_bytes_field(&mut buf, 1, tag.as_bytes());
+    match value {
+        SummaryValue::Scalar(val) => {
+            write_key(&mut buf, 2, 5);
+            buf.extend_from_slice(&val.to_le_bytes());
+        },
+        SummaryValue::Histogram(vals) => write_bytes_field(&mut buf, 5, &encode_histogram(vals)),
+    }
+    buf
+}
+
+/// Writes summaries that TensorBoard can display to an event file in `log_dir`.
+///
+/// Events are written as TFRecords holding `Event` protocol buffers, encoded by hand so that no protobuf or
+/// TensorFlow dependency is needed.  Every event is flushed as soon as it is written so that a running TensorBoard
+/// picks it up.
+pub struct TensorBoardLogger {
+    pub log_dir: PathBuf,
+    /// The event file being written, named the way TensorBoard expects
+    pub path: PathBuf,
+    pub writer: BufWriter<File>,
+}
+
+impl TensorBoardLogger {
+    /// Creates `log_dir` if needed and starts a new event file in it.
+    pub fn new(log_dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let log_dir = log_dir.into();
+        fs::create_dir_all(&log_dir)?;
+        let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let path = log_dir.join(format!(
+            "events.out.tfevents.{}.{}.libnn",
+            start.as_secs(),
+            std::process::id()
+        ));
+        let writer = BufWriter::new(File::create(&path)?);
+        let mut logger = TensorBoardLogger { log_dir, path, writer };
+
+        let mut event = logger.event_header(0);
+        write_bytes_field(&mut event, 3, b"brain.Event:2");
+        logger.write_record(&event)?;
+        Ok(logger)
+    }
+
+    /// Encodes the wall time and step fields shared by every `Event`.
+    fn event_header(&self, step: u64) -> Vec<u8> {
+        let wall_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut buf = Vec::new();
+        write_double_field(&mut buf, 1, wall_time.as_secs_f64());
+        write_key(&mut buf, 2, 0);
+        write_varint(&mut buf, step);
+        buf
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> io::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer.write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())?;
+        self.writer.flush()
+    }
+
+    fn write_summary(&mut self, step: u64, values: Vec<(String, SummaryValue)>) -> io::Result<()> {
+        let mut summary = Vec::new();
+        for (tag, value) in values {
+            write_bytes_field(&mut summary, 1, &encode_summary_value(&tag, value));
+        }
+        let mut event = self.event_header(step);
+        write_bytes_field(&mut event, 5, &summary);
+        self.write_record(&event)
+    }
+
+    pub fn log_scalar(&mut self, step: u64, tag: &str, value: f32) -> io::Result<()> {
+        self.write_summary(step, vec![(tag.to_owned(), SummaryValue::Scalar(value))])
+    }
+
+    /// Logs histograms of the layer's weights and biases, tagged `{layer_name}/weights` and `{layer_name}/biases`.
+    pub fn log_weights(&mut self, step: u64, layer_name: &str, layer: &DenseLayer) -> io::Result<()> {
+        let weights: Vec<Weight> = layer.weights.iter().flatten().copied().collect();
+        let mut values = vec![(format!("{}/weights", layer_name), SummaryValue::Histogram(&weights))];
+        if layer.use_bias {
+            values.push((format!("{}/biases", layer_name), SummaryValue::Histogram(&layer.biases)));
+        }
+        self.write_summary(step, values)
+    }
+}
@@ -14,6 +14,11 @@ fn test_dense_layer_forward_propagation() {
         outputs_before_activation: vec![0., 0.],
         errors_scratch: vec![0., 0.],
         outputs: vec![0., 0.],
+        frozen: false,
+        use_bias: true,
+        spectral_norm: None,
+        prune_mask: None,
+        apply_mask_during_update: true,
     };
 
     let sigmoid = Sigmoid;
@@ -60,6 +65,11 @@ fn test_forward_propagation() {
             errors_scratch: vec![0., 0.],
             outputs_before_activation: vec![0., 0.],
             outputs: vec![0., 0.],
+            frozen: false,
+            use_bias: true,
+            spectral_norm: None,
+            prune_mask: None,
+            apply_mask_during_update: true,
         }],
         outputs: Box::new(OutputLayer {
             weights: vec![vec![-1.2, 0.4], vec![2.0, -1.0]],
@@ -217,6 +227,11 @@ fn test_hidden_layer_single_weight_updating() {
         activation_fn: &Identity,
         outputs_before_activation: vec![0.],
         outputs: vec![0.],
+        frozen: false,
+        use_bias: true,
+        spectral_norm: None,
+        prune_mask: None,
+        apply_mask_during_update: true,
     };
 
     // Run forward once with initial random weights and compute our costs
@@ -261,6 +276,11 @@ fn test_hidden_layer_single_neuron_bias_updating() {
         errors_scratch: vec![0.],
         outputs_before_activation: vec![0.],
         outputs: vec![0.],
+        frozen: false,
+        use_bias: true,
+        spectral_norm: None,
+        prune_mask: None,
+        apply_mask_during_update: true,
     };
 
     // Run forward once with initial random weights and compute our costs
@@ -586,3 +606,4209 @@ fn test_complex_network_application() {
     assert_eq!(network.outputs.outputs_before_activation[0], -2. * 0.5);
     assert_eq!(network.outputs.outputs[0], (-1.0f32).tanh());
 }
+
+/// Trains a single identity-activated neuron to fit `y = 0.8 * x0 - 0.5 * x1 + 0.3` and returns the final mean cost
+/// over a held-out set of examples.
+fn train_linear_regression(
+    iterations: usize,
+    learning_rate: Weight,
+    mut optimizer: Option<&mut dyn Optimizer>,
+) -> Weight {
+    let mut rng = pcg::Pcg::default();
+    let mut layer = DenseLayer::new(1, 2, &mut |_, _| 0., &mut |_| 0., &Identity);
+    let target = |x0: Weight, x1: Weight| 0.8 * x0 - 0.5 * x1 + 0.3;
+
+    for _ in 0..iterations {
+        let inputs = [rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)];
+        layer.forward_propagate(&inputs);
+        let error = target(inputs[0], inputs[1]) - layer.outputs[0];
+        layer.compute_gradients(&[vec![1.]], &[MeanSquaredError.derivative(error)]);
+        match optimizer.as_deref_mut() {
+            Some(optimizer) => layer.update_weights_with_optimizer(&inputs, learning_rate, optimizer),
+            None => layer.update_weights(&inputs, learning_rate),
+        }
+        layer.update_biases(learning_rate);
+    }
+
+    let mut total_cost = 0.;
+    for i in 0..100 {
+        let inputs = [(i as Weight / 50.) - 1., 1. - (i as Weight / 75.)];
+        layer.forward_propagate(&inputs);
+        total_cost += MeanSquaredError.get_cost(target(inputs[0], inputs[1]) - layer.outputs[0]);
+    }
+    total_cost / 100.
+}
+
+#[test]
+fn test_sgd_optimizer_matches_update_weights() {
+    let vanilla_cost = train_linear_regression(500, 0.01, None);
+    let sgd_cost = train_linear_regression(500, 0.01, Some(&mut Sgd));
+    assert_eq!(vanilla_cost, sgd_cost);
+}
+
+#[test]
+fn test_nesterov_sgd_converges_faster_than_vanilla_sgd() {
+    let mut nesterov = NesterovSgd::new(0.9);
+    let vanilla_cost = train_linear_regression(300, 0.005, None);
+    let nesterov_cost = train_linear_regression(300, 0.005, Some(&mut nesterov));
+    println!("vanilla cost={}, nesterov cost={}", vanilla_cost, nesterov_cost);
+
+    assert_eq!(nesterov.velocity.len(), 1);
+    assert_eq!(nesterov.velocity[0].len(), 2);
+    assert!(nesterov_cost < vanilla_cost / 2.);
+}
+
+#[test]
+fn test_bias_momentum_converges_faster() {
+    // With inputs of 0 only the bias can move the output towards the target
+    let train_bias = |steps: usize, mut optimizer: Option<&mut dyn Optimizer>| {
+        let mut layer = DenseLayer::new(1, 1, &mut |_, _| 0., &mut |_| 0., &Identity);
+        for _ in 0..steps {
+            layer.forward_propagate(&[0.]);
+            let error = 2. - layer.outputs[0];
+            layer.compute_gradients(&[vec![1.]], &[MeanSquaredError.derivative(error)]);
+            match optimizer.as_deref_mut() {
+                Some(optimizer) => layer.update_biases_with_optimizer(0.02, optimizer),
+                None => layer.update_biases(0.02),
+            }
+        }
+        (2. - layer.biases[0]).abs()
+    };
+
+    assert_eq!(train_bias(40, None), train_bias(40, Some(&mut Sgd)));
+    let mut nesterov = NesterovSgd::new(0.9);
+    let momentum_error = train_bias(40, Some(&mut nesterov));
+    assert_eq!(nesterov.bias_velocity.len(), 1);
+    assert!(
+        momentum_error < train_bias(40, None) / 5.,
+        "{} vs {}",
+        momentum_error,
+        train_bias(40, None)
+    );
+
+    let mut frozen = DenseLayer::new(1, 1, &mut |_, _| 0., &mut |_| 0., &Identity);
+    frozen.neuron_gradients[0] = 1.;
+    frozen.freeze();
+    frozen.update_biases_with_optimizer(0.1, &mut NesterovSgd::new(0.9));
+    assert_eq!(frozen.biases[0], 0.);
+}
+
+#[test]
+fn test_adamax_optimizer() {
+    let mut adamax = AdamaxOptimizer::default();
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    let adamax_cost = train_linear_regression(300, 0.02, Some(&mut adamax));
+    println!("untrained cost={}, adamax cost={}", untrained_cost, adamax_cost);
+
+    assert_eq!(adamax.step, 300);
+    assert_eq!(adamax.u[0].len(), 2);
+    assert!(adamax.u[0].iter().all(|&u| u > 0.));
+    assert!(adamax_cost < untrained_cost / 50.);
+
+    // The first step moves each weight by the full learning rate in the direction of its gradient, whatever its scale
+    let mut adamax = AdamaxOptimizer::default();
+    let mut weights = vec![vec![0., 0.]];
+    adamax.update_weights(&mut weights, &[2.], &[100., -0.01], 0.1);
+    assert!((weights[0][0] - 0.1).abs() < 1e-6);
+    assert!((weights[0][1] + 0.1).abs() < 1e-4);
+}
+
+/// Minimizes the poorly-conditioned quadratic `50 * x^2 + 0.5 * y^2` from `(1, 1)` and returns the final loss.  The
+/// weights are `[[x, y]]` and the descent direction is passed as the inputs, so that `neuron_gradient * input` is
+/// `-dL/dw`.
+fn minimize_ill_conditioned_quadratic(optimizer: &mut dyn Optimizer, learning_rate: Weight, steps: usize) -> Weight {
+    let loss = |w: &[Weight]| 50. * w[0] * w[0] + 0.5 * w[1] * w[1];
+    let mut weights = vec![vec![1., 1.]];
+    for _ in 0..steps {
+        let descent = [-100. * weights[0][0], -weights[0][1]];
+        optimizer.update_weights(&mut weights, &[1.], &descent, learning_rate);
+    }
+    loss(&weights[0])
+}
+
+#[test]
+fn test_adam_optimizer() {
+    let mut adam = AdamOptimizer::default();
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    let adam_cost = train_linear_regression(300, 0.02, Some(&mut adam));
+    assert_eq!(adam.adam_state.step, 300);
+    assert!(adam_cost < untrained_cost / 50.);
+
+    // Bias correction makes the first step the learning rate times the sign of each gradient
+    let mut adam = AdamOptimizer::default();
+    let mut weights = vec![vec![0., 0.]];
+    adam.update_weights(&mut weights, &[2.], &[100., -0.01], 0.1);
+    assert!((weights[0][0] - 0.1).abs() < 1e-6);
+    assert!((weights[0][1] + 0.1).abs() < 1e-4);
+}
+
+#[test]
+fn test_nadam_optimizer() {
+    let mut nadam = NadamOptimizer::default();
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    let nadam_cost = train_linear_regression(300, 0.02, Some(&mut nadam));
+    assert_eq!(nadam.step, 300);
+    assert!(nadam_cost < untrained_cost / 50.);
+
+    let nadam_loss = minimize_ill_conditioned_quadratic(&mut NadamOptimizer::default(), 0.05, 100);
+    let adam_loss = minimize_ill_conditioned_quadratic(&mut AdamOptimizer::default(), 0.05, 100);
+    println!("nadam loss={}, adam loss={}", nadam_loss, adam_loss);
+    assert!(nadam_loss < adam_loss / 2.);
+}
+
+/// The online problem from Reddi et al. (2018): the loss is `1010 * x` every third step and `-10 * x` otherwise, with
+/// `x` kept within `[-1, 1]`.  The average loss `330 * x` is minimized at `x = -1`.  Returns where `x` ends up.
+fn run_amsgrad_counterexample(optimizer: &mut dyn Optimizer, steps: usize) -> Weight {
+    let mut weights = vec![vec![0.]];
+    for step in 0..steps {
+        let gradient = if step % 3 == 0 { 1010. } else { -10. };
+        let learning_rate = 0.1 / ((step + 1) as Weight).sqrt();
+        optimizer.update_weights(&mut weights, &[1.], &[-gradient], learning_rate);
+        weights[0][0] = weights[0][0].clamp(-1., 1.);
+    }
+    weights[0][0]
+}
+
+#[test]
+fn test_amsgrad_converges_where_adam_does_not() {
+    // The paper's choice of `beta2 = 1 / (1 + C^2)` for gradients of `C` and -1, here scaled by 10
+    let (beta1, beta2) = (0., 1. / (1. + 101. * 101.));
+    let adam_x = run_amsgrad_counterexample(&mut AdamOptimizer::new(beta1, beta2, 1e-8), 3000);
+    let mut amsgrad = AmsGradOptimizer::new(beta1, beta2, 1e-8);
+    let amsgrad_x = run_amsgrad_counterexample(&mut amsgrad, 3000);
+    println!("adam x={}, amsgrad x={}", adam_x, amsgrad_x);
+
+    assert!(adam_x > 0.9);
+    assert!(amsgrad_x < -0.99);
+    assert!(amsgrad.v_max[0][0] >= 1010. * 1010. * (1. - beta2));
+
+    let mut amsgrad = AmsGradOptimizer::default();
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    assert!(train_linear_regression(300, 0.02, Some(&mut amsgrad)) < untrained_cost / 50.);
+}
+
+#[test]
+fn test_adagrad_optimizer() {
+    let mut rng = XorShiftRng(7);
+    let mut layer = DenseLayer::new(1, 2, &mut |_, _| 0., &mut |_| 0., &Identity);
+    let mut adagrad = AdagradOptimizer::new(0.1);
+    assert_eq!(
+        adagrad.effective_learning_rate(0, 0),
+        adagrad.effective_learning_rate(0, 1)
+    );
+
+    // The first input is large and always present, the second is small and only there one step in ten
+    let mut previous_lrs = [Weight::INFINITY; 2];
+    for step in 0..200 {
+        let inputs = [rng.gen_range(-2., 2.), if step % 10 == 0 { 0.5 } else { 0. }];
+        layer.forward_propagate(&inputs);
+        let error = 0.8 * inputs[0] - 0.5 * inputs[1] - layer.outputs[0];
+        layer.compute_gradients(&[vec![1.]], &[MeanSquaredError.derivative(error)]);
+        layer.update_weights_with_optimizer(&inputs, 1., &mut adagrad);
+
+        let lrs = [
+            adagrad.effective_learning_rate(0, 0),
+            adagrad.effective_learning_rate(0, 1),
+        ];
+        assert!(lrs[0] <= previous_lrs[0] && lrs[1] <= previous_lrs[1]);
+        previous_lrs = lrs;
+    }
+    println!(
+        "effective learning rates: {:?}, weights: {:?}",
+        previous_lrs, layer.weights[0]
+    );
+
+    assert!(previous_lrs[0] < previous_lrs[1] / 4.);
+    assert!((layer.weights[0][0] - 0.8).abs() < 0.05);
+
+    // The learning rate passed in scales the adapted step
+    let step = |learning_rate: Weight| {
+        let mut adagrad = AdagradOptimizer::new(0.1);
+        let mut weights = vec![vec![0., 0.]];
+        adagrad.update_weights(&mut weights, &[1.], &[2., -1.], learning_rate);
+        weights[0].clone()
+    };
+    let (full, half) = (step(1.), step(0.5));
+    assert!((full[0] - 0.1).abs() < 1e-6 && (full[1] + 0.1).abs() < 1e-6);
+    assert!((half[0] - full[0] / 2.).abs() < 1e-6 && (half[1] - full[1] / 2.).abs() < 1e-6);
+}
+
+#[test]
+fn test_adaptive_optimizers_update_biases_with_their_own_rule() {
+    let optimizers: [fn() -> Box<dyn Optimizer>; 7] = [
+        || Box::new(AdamaxOptimizer::default()),
+        || Box::new(NadamOptimizer::default()),
+        || Box::new(AmsGradOptimizer::default()),
+        || Box::new(AdagradOptimizer::new(0.1)),
+        || Box::new(LionOptimizer::default()),
+        || Box::new(AdamOptimizer::default()),
+        || Box::new(LambOptimizer::default()),
+    ];
+    for new_optimizer in optimizers {
+        let mut optimizer = new_optimizer();
+        let mut weights = vec![vec![0.5, -0.5], vec![0.2, 0.1]];
+        let mut biases = vec![0., 0.];
+        // Each bias should move like the weight of a neuron whose only input is 1, with state of its own
+        let mut reference = new_optimizer();
+        let mut reference_weights = vec![vec![0.], vec![0.]];
+        let mut sgd_biases = vec![0., 0.];
+        for step in 0..5 {
+            let neuron_gradients = [0.01 * (step + 1) as Weight, -0.02];
+            optimizer.update_weights(&mut weights, &neuron_gradients, &[3., -2.], 0.1);
+            optimizer.update_biases(&mut biases, &neuron_gradients, 0.1);
+            reference.update_weights(&mut reference_weights, &neuron_gradients, &[1.], 0.1);
+            Sgd.update_biases(&mut sgd_biases, &neuron_gradients, 0.1);
+        }
+        for ((bias, reference_weight), sgd_bias) in biases.iter().zip(reference_weights.iter()).zip(sgd_biases.iter()) {
+            assert!(
+                (bias - reference_weight[0]).abs() < 1e-6,
+                "{} vs {}",
+                bias,
+                reference_weight[0]
+            );
+            assert!((bias - sgd_bias).abs() > 1e-3, "{} is what plain SGD would give", bias);
+        }
+    }
+}
+
+#[test]
+fn test_lookahead_optimizer() {
+    let mut lookahead = LookaheadOptimizer::new(Box::new(Sgd), 3, 0.5);
+    let mut weights = vec![vec![1., -1.]];
+    let inputs = [1., 2.];
+
+    // Plain SGD moves the fast weights by 0.1 * [1, 2] each step
+    for step in 1..3 {
+        lookahead.update_weights(&mut weights, &[0.1], &inputs, 1.);
+        assert_eq!(lookahead.step, step);
+        assert_eq!(lookahead.slow_weights, vec![vec![1., -1.]]);
+        assert!((weights[0][0] - (1. + 0.1 * step as Weight)).abs() < 1e-6);
+    }
+    // On the third step the fast weights reach [1.3, -0.4] and the slow weights move halfway there
+    lookahead.update_weights(&mut weights, &[0.1], &inputs, 1.);
+    assert_eq!(lookahead.step, 0);
+    assert!((lookahead.slow_weights[0][0] - 1.15).abs() < 1e-6);
+    assert!((lookahead.slow_weights[0][1] + 0.7).abs() < 1e-6);
+    assert_eq!(weights, lookahead.slow_weights);
+
+    let mut lookahead = LookaheadOptimizer::new(Box::new(NesterovSgd::new(0.9)), 5, 0.5);
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    assert!(train_linear_regression(300, 0.02, Some(&mut lookahead)) < untrained_cost / 50.);
+}
+
+#[test]
+fn test_lion_optimizer() {
+    let mut lion = LionOptimizer::default();
+    let mut weights = vec![vec![0.5, 0.5, 0.5]];
+    lion.update_weights(&mut weights, &[2.], &[3., -0.001, 0.], 0.1);
+    // Every weight with a gradient moves by the full learning rate, whatever the gradient's size
+    assert_eq!(weights, vec![vec![0.6, 0.4, 0.5]]);
+    assert!((lion.m[0][0] - 0.06).abs() < 1e-6);
+
+    // Lion's fixed step size leaves it bouncing around the minimum instead of settling into it like Adam, so compare
+    // them while they're still converging
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    let adam_cost = train_linear_regression(100, 0.02, Some(&mut AdamOptimizer::default()));
+    let lion_cost = train_linear_regression(100, 0.02, Some(&mut LionOptimizer::default()));
+    println!(
+        "untrained cost={}, adam cost={}, lion cost={}",
+        untrained_cost, adam_cost, lion_cost
+    );
+    assert!(lion_cost < untrained_cost / 100.);
+    assert!(lion_cost < 5. * adam_cost);
+
+    // Decoupled weight decay pulls weights towards zero even without any gradient
+    let mut lion = LionOptimizer::new(0.9, 0.99, 0.5);
+    let mut weights = vec![vec![2.]];
+    lion.update_weights(&mut weights, &[0.], &[1.], 0.1);
+    assert!((weights[0][0] - 1.9).abs() < 1e-6);
+}
+
+#[test]
+fn test_lamb_optimizer() {
+    // Adam's first update is the sign of each gradient, so the update norm is the square root of the weight count
+    let mut first_layer = LambOptimizer::default();
+    let mut second_layer = LambOptimizer::new(AdamState::default(), 0.5);
+    let mut first_weights = vec![vec![3., 0.], vec![0., 4.]];
+    let mut second_weights = vec![vec![0.1, -0.1, 0.1, -0.1]];
+
+    first_layer.update_weights(&mut first_weights, &[1., -1.], &[0.5, 2.], 0.01);
+    assert!((first_layer.last_trust_ratio - 5. / 2.).abs() < 1e-5);
+    let expected = [[3. + 0.025, 0.025], [-0.025, 4. - 0.025]];
+    for (row, expected_row) in first_weights.iter().zip(expected.iter()) {
+        for (weight, expected) in row.iter().zip(expected_row.iter()) {
+            assert!((weight - expected).abs() < 1e-5);
+        }
+    }
+
+    second_layer.update_weights(&mut second_weights, &[2.], &[1., 1., -1., 0.], 0.01);
+    // Only three of the weights have a gradient
+    let expected_ratio = 0.5 * 0.2 / (3. as Weight).sqrt();
+    assert!((second_layer.last_trust_ratio - expected_ratio).abs() < 1e-5);
+    assert!((second_weights[0][0] - (0.1 + 0.01 * expected_ratio)).abs() < 1e-6);
+    assert_eq!(second_weights[0][3], -0.1);
+
+    let mut lamb = LambOptimizer::default();
+    let untrained_cost = train_linear_regression(0, 0.02, None);
+    assert!(train_linear_regression(300, 0.02, Some(&mut lamb)) < untrained_cost / 50.);
+    assert_eq!(lamb.adam_state.step, 300);
+}
+
+#[test]
+fn test_warmup_lr_scheduler_ramps_linearly() {
+    let scheduler = WarmupLrScheduler {
+        warmup_steps: 4,
+        initial_lr: 0.,
+        peak_lr: 0.1,
+        decay_scheduler: Box::new(ConstantLr(0.1)),
+    };
+
+    let expected = [0., 0.025, 0.05, 0.075];
+    for (step, &expected_lr) in expected.iter().enumerate() {
+        assert!((scheduler.get_learning_rate(step) - expected_lr).abs() < 1e-7);
+    }
+}
+
+#[test]
+fn test_warmup_lr_scheduler_hands_off_to_decay_scheduler() {
+    let scheduler = WarmupLrScheduler {
+        warmup_steps: 10,
+        initial_lr: 0.001,
+        peak_lr: 0.1,
+        decay_scheduler: Box::new(ExponentialDecayLr {
+            initial_lr: 0.1,
+            decay_rate: 0.5,
+        }),
+    };
+
+    // The decay scheduler sees steps counted from the end of the warm-up, so it starts out at the peak.
+    assert_eq!(scheduler.get_learning_rate(10), 0.1);
+    assert_eq!(scheduler.get_learning_rate(11), 0.05);
+    assert_eq!(scheduler.get_learning_rate(13), 0.0125);
+    assert!(scheduler.get_learning_rate(9) < 0.1);
+    assert!(scheduler.get_learning_rate(9) > scheduler.get_learning_rate(8));
+}
+
+fn build_small_network(rng: &mut pcg::Pcg) -> Network {
+    let mut init_weights = |_, _| rng.gen_range(-1.0, 1.0);
+    Network {
+        hidden_layers: vec![DenseLayer::new(3, 2, &mut init_weights, &mut |_| 0.1, &Tanh)],
+        outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut init_weights, 3, 1)),
+        learning_rate: 0.1,
+    }
+}
+
+fn all_weights(network: &Network) -> Vec<Weight> {
+    let mut weights = Vec::new();
+    for layer in &network.hidden_layers {
+        weights.extend(layer.weights.iter().flatten());
+        weights.extend(layer.biases.iter());
+    }
+    weights.extend(network.outputs.weights.iter().flatten());
+    weights
+}
+
+#[test]
+fn test_gradient_accumulation_matches_mini_batch() {
+    let mut rng = pcg::Pcg::default();
+    let network = build_small_network(&mut rng);
+    let examples = [[0.1, 0.9], [-0.5, 0.3], [0.7, -0.2], [-0.9, -0.4]];
+    let expecteds = [[0.5], [-0.2], [0.9], [0.]];
+    let learning_rate = 0.1;
+
+    let mut trainer = Trainer::new(network.clone());
+    trainer.set_accumulation_steps(4);
+    for i in 0..3 {
+        trainer.train_one_example(&examples[i], &expecteds[i], learning_rate);
+        // Nothing is updated until the whole batch has been seen
+        assert_eq!(all_weights(&trainer.network), all_weights(&network));
+    }
+    trainer.train_one_example(&examples[3], &expecteds[3], learning_rate);
+
+    // A true mini-batch update moves the weights by the average of each example's gradient, all computed against the
+    // original weights.
+    let original_weights = all_weights(&network);
+    let mut expected_weights = original_weights.clone();
+    for (example, expected) in examples.iter().zip(expecteds.iter()) {
+        let mut single_step = network.clone();
+        single_step.learning_rate = learning_rate / 4.;
+        single_step.train_one_example(example, expected, learning_rate / 4.);
+        for (i, weight) in all_weights(&single_step).into_iter().enumerate() {
+            expected_weights[i] += weight - original_weights[i];
+        }
+    }
+
+    let actual_weights = all_weights(&trainer.network);
+    assert_eq!(actual_weights.len(), expected_weights.len());
+    for (actual, expected) in actual_weights.iter().zip(expected_weights.iter()) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+    assert_eq!(trainer.accumulated_steps, 0);
+}
+
+#[test]
+fn test_network_input_gradients_match_finite_differences() {
+    let mut rng = pcg::Pcg::default();
+    let mut network = build_small_network(&mut rng);
+    let example = [0.3, -0.6];
+    let expected = [0.4];
+
+    network.compute_gradients(&example, &expected);
+    let mut input_gradients = [0.; 2];
+    network.compute_input_gradients(&mut input_gradients);
+
+    let epsilon = 1e-3;
+    for input_ix in 0..example.len() {
+        let mut cost_at = |offset: Weight| {
+            let mut perturbed = example;
+            perturbed[input_ix] += offset;
+            network.forward_propagate(&perturbed);
+            network.outputs.compute_costs(&expected);
+            network.outputs.costs[0]
+        };
+        let numerical_gradient = (cost_at(epsilon) - cost_at(-epsilon)) / (2. * epsilon);
+        // Gradients in this crate point in the direction that reduces cost
+        assert!((input_gradients[input_ix] + numerical_gradient).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_embedding_layer_accumulates_into_looked_up_row() {
+    let mut embedding = EmbeddingLayer::new(3, 2, &mut |token_ix, dim_ix| (token_ix * 2 + dim_ix) as Weight);
+    assert_eq!(embedding.lookup(1), &[2., 3.]);
+
+    embedding.accumulate_gradients(1, &[1., -1.]);
+    embedding.accumulate_gradients(1, &[0.5, 0.5]);
+    embedding.update_weights(0.1);
+
+    assert_eq!(embedding.lookup(0), &[0., 1.]);
+    assert_eq!(embedding.lookup(1), &[2. + 0.15, 3. - 0.05]);
+    assert_eq!(embedding.lookup(2), &[4., 5.]);
+    assert!(embedding.gradients.iter().flatten().all(|&gradient| gradient == 0.));
+}
+
+#[test]
+fn test_embeddings_cluster_tokens_with_similar_targets() {
+    let mut rng = pcg::Pcg::default();
+    let mut embedding = EmbeddingLayer::new(4, 2, &mut |_, _| rng.gen_range(-0.5, 0.5));
+    let mut network = build_small_network(&mut rng);
+    // Tokens 0 and 1 belong to one class and tokens 2 and 3 to the other
+    let targets = [1., 1., -1., -1.];
+
+    for i in 0..4_000 {
+        let token_ix = i % 4;
+        network.train_one_token(&mut embedding, token_ix, &[targets[token_ix]], 0.05);
+    }
+
+    let distance = |a: usize, b: usize| {
+        let (a, b) = (embedding.lookup(a), embedding.lookup(b));
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+    };
+    println!("embeddings: {:?}", embedding.embeddings);
+    assert!(distance(0, 1) < distance(0, 2));
+    assert!(distance(0, 1) < distance(1, 3));
+    assert!(distance(2, 3) < distance(0, 2));
+    assert!(distance(2, 3) < distance(1, 3));
+    for (token_ix, &target) in targets.iter().enumerate() {
+        let output = network.compute(embedding.lookup(token_ix))[0];
+        assert!((output - target).abs() < 0.1);
+    }
+}
+
+#[test]
+fn test_dense_layer_builder_matches_constructor() {
+    let layer = DenseLayer::builder()
+        .output_count(3)
+        .input_count(2)
+        .activation(&TANH)
+        .weight_init(|neuron_ix, input_ix| (neuron_ix * 2 + input_ix) as Weight)
+        .bias_init(|neuron_ix| neuron_ix as Weight * -0.5)
+        .build()
+        .unwrap();
+    let expected = DenseLayer::new(
+        3,
+        2,
+        &mut |neuron_ix, input_ix| (neuron_ix * 2 + input_ix) as Weight,
+        &mut |neuron_ix| neuron_ix as Weight * -0.5,
+        &TANH,
+    );
+
+    assert_eq!(layer.weights, expected.weights);
+    assert_eq!(layer.biases, expected.biases);
+    assert_eq!(layer.outputs.len(), 3);
+    assert_eq!(layer.activation_fn.get_output(0.5), TANH.get_output(0.5));
+}
+
+#[test]
+fn test_dense_layer_builder_defaults() {
+    let layer = DenseLayer::builder().output_count(4).input_count(3).build().unwrap();
+
+    assert!(layer
+        .weights
+        .iter()
+        .flatten()
+        .all(|&weight| (-1. ..=1.).contains(&weight)));
+    assert!(layer.biases.iter().all(|&bias| bias == 0.));
+    assert_eq!(layer.activation_fn.get_output(-3.), -3.);
+}
+
+#[test]
+fn test_dense_layer_builder_rejects_invalid_configurations() {
+    let err = DenseLayer::builder().input_count(3).build().err();
+    assert_eq!(err, Some(BuildError::ZeroOutputCount));
+
+    let err = DenseLayer::builder().output_count(3).build().err();
+    assert_eq!(err, Some(BuildError::ZeroInputCount));
+
+    let err = DenseLayer::builder().output_count(0).input_count(0).build().err();
+    assert_eq!(err, Some(BuildError::ZeroOutputCount));
+}
+
+#[test]
+fn test_stable_sigmoid_at_extremes() {
+    assert_eq!(sigmoid(0.), 0.5);
+    assert!(sigmoid(-1000.).abs() < 1e-30);
+    assert!((sigmoid(1000.) - 1.).abs() < 1e-7);
+    assert!(!sigmoid(Weight::MIN).is_nan());
+    assert!(!sigmoid(Weight::MAX).is_nan());
+
+    // Gradients vanish at the extremes rather than blowing up
+    assert_eq!(STABLE_SIGMOID.derivative(0.), 0.25);
+    assert!(STABLE_SIGMOID.derivative(-1000.).abs() < 1e-30);
+    assert!(STABLE_SIGMOID.derivative(1000.).abs() < 1e-7);
+    assert!(!STABLE_SIGMOID.derivative(-1000.).is_nan());
+
+    let epsilon = 1e-2;
+    for &x in &[-20., -5., -1., 0.3, 4., 15.] {
+        let numerical = (sigmoid(x + epsilon) - sigmoid(x - epsilon)) / (2. * epsilon);
+        assert!((STABLE_SIGMOID.derivative(x) - numerical).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_log_sigmoid() {
+    assert!((log_sigmoid(0.) - 0.5f32.ln()).abs() < 1e-7);
+    assert_eq!(log_sigmoid(-1000.), -1000.);
+    assert!(log_sigmoid(1000.).abs() < 1e-7);
+    for &x in &[-10., -2., 0.5, 3.] {
+        assert!((log_sigmoid(x) - sigmoid(x).ln()).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_evaluate_aggregates_costs_without_training() {
+    // Computes `2 * (x0 + x1)` for both outputs
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new(1, 2, &mut |_, _| 1., &mut |_| 0., &Identity)],
+        outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut |_, _| 2., 1, 2)),
+        learning_rate: 0.1,
+    };
+    let examples = vec![vec![1., 0.], vec![0.5, 0.5], vec![-1., 0.]];
+    let expected = vec![vec![2., 3.], vec![0., 2.], vec![-2., -4.]];
+    let weights_before = all_weights(&network);
+
+    let result = network.evaluate(&examples, &expected);
+
+    // Outputs are [2, 2], [2, 2], and [-2, -2] giving squared errors of [0, 1], [4, 0], and [0, 4]
+    let expected_total = (0. + 1.) / 2. + (4. + 0.) / 2. + (0. + 4.) / 2.;
+    assert_eq!(result.total_loss, expected_total);
+    assert_eq!(result.mean_loss_per_example, expected_total / 3.);
+    assert_eq!(result.example_count, 3);
+    assert_eq!(all_weights(&network), weights_before);
+}
+
+#[test]
+fn test_tied_output_weights_receive_combined_gradient() {
+    let mut rng = pcg::Pcg::default();
+    let embedding = EmbeddingLayer::new(3, 2, &mut |_, _| rng.gen_range(-1.0, 1.0));
+    let network = Network {
+        hidden_layers: vec![DenseLayer::new(
+            2,
+            2,
+            &mut |_, _| rng.gen_range(-1.0, 1.0),
+            &mut |_| 0.,
+            &Tanh,
+        )],
+        outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut |_, _| 0., 2, 3)),
+        learning_rate: 0.1,
+    };
+    let mut tied = network.clone().with_tied_output_weights(&embedding);
+    assert_eq!(tied.network.outputs.weights, embedding.embeddings);
+
+    let token_ix = 1;
+    let expected = [0., 1., 0.];
+    let learning_rate = 0.1;
+
+    // Compute both gradient contributions separately on an untouched copy
+    let mut reference = tied.network.clone();
+    reference.compute_gradients(embedding.lookup(token_ix), &expected);
+    let mut input_gradients = [0.; 2];
+    reference.compute_input_gradients(&mut input_gradients);
+    let hidden_outputs = reference.hidden_layers[0].outputs.clone();
+
+    tied.train_one_token(token_ix, &expected, learning_rate);
+
+    for row_ix in 0..3 {
+        for dim_ix in 0..2 {
+            let mut gradient = reference.outputs.neuron_gradients[row_ix] * hidden_outputs[dim_ix];
+            if row_ix == token_ix {
+                gradient += input_gradients[dim_ix];
+            }
+            let expected_weight = embedding.embeddings[row_ix][dim_ix] + learning_rate * gradient;
+            assert!((tied.embedding.embeddings[row_ix][dim_ix] - expected_weight).abs() < 1e-6);
+        }
+    }
+    assert_eq!(tied.network.outputs.weights, tied.embedding.embeddings);
+
+    // Without hidden layers the output layer is fed the embedding directly, so the scores are dot products of
+    // embeddings
+    let network = Network {
+        hidden_layers: Vec::new(),
+        outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut |_, _| 0., 2, 3)),
+        learning_rate: 0.1,
+    };
+    let mut tied = network.with_tied_output_weights(&embedding);
+    let untrained = tied.train_one_token(token_ix, &expected, learning_rate);
+    for _ in 0..50 {
+        tied.train_one_token(token_ix, &expected, learning_rate);
+    }
+    assert!(tied.train_one_token(token_ix, &expected, learning_rate) < untrained);
+    assert_eq!(tied.network.outputs.weights, tied.embedding.embeddings);
+}
+
+#[test]
+fn test_frozen_layer_is_unchanged_by_training() {
+    let mut rng = pcg::Pcg::default();
+    let mut network = Network {
+        hidden_layers: vec![
+            DenseLayer::new(3, 2, &mut |_, _| rng.gen_range(-1.0, 1.0), &mut |_| 0.1, &Tanh),
+            DenseLayer::new(3, 3, &mut |_, _| rng.gen_range(-1.0, 1.0), &mut |_| 0.1, &Tanh),
+        ],
+        outputs: Box::new(OutputLayer::new(
+            &Identity,
+            &MeanSquaredError,
+            &mut |_, _| rng.gen_range(-1.0, 1.0),
+            3,
+            1,
+        )),
+        learning_rate: 0.1,
+    };
+    network.hidden_layers[0].freeze();
+    let frozen_weights = network.hidden_layers[0].weights.clone();
+    let frozen_biases = network.hidden_layers[0].biases.clone();
+    let trainable_weights = network.hidden_layers[1].weights.clone();
+    let output_weights = network.outputs.weights.clone();
+
+    network.train_one_example(&[0.5, -0.5], &[1.], 0.1);
+    network.hidden_layers[0].update_weights_with_optimizer(&[0.5, -0.5], 0.1, &mut Sgd);
+
+    assert_eq!(network.hidden_layers[0].weights, frozen_weights);
+    assert_eq!(network.hidden_layers[0].biases, frozen_biases);
+    assert_ne!(network.hidden_layers[1].weights, trainable_weights);
+    assert_ne!(network.outputs.weights, output_weights);
+
+    let mut trainer = Trainer::new(network);
+    trainer.train_one_example(&[0.2, 0.7], &[-1.], 0.1);
+    assert_eq!(trainer.network.hidden_layers[0].weights, frozen_weights);
+    assert_eq!(trainer.network.hidden_layers[0].biases, frozen_biases);
+
+    trainer.network.hidden_layers[0].unfreeze();
+    trainer.train_one_example(&[0.2, 0.7], &[-1.], 0.1);
+    assert_ne!(trainer.network.hidden_layers[0].weights, frozen_weights);
+}
+
+#[test]
+fn test_network_builder_wires_layers_together() {
+    let mut network = Network::builder()
+        .input_size(2)
+        .hidden_layer(4, &TANH)
+        .hidden_layer(3, &RELU)
+        .output_size(2)
+        .output_activation(&SIGMOID)
+        .weight_init(WeightInit::Constant(0.5))
+        .learning_rate(0.2)
+        .build()
+        .unwrap();
+
+    assert_eq!(network.hidden_layers.len(), 2);
+    assert_eq!(network.hidden_layers[0].weights, vec![vec![0.5; 2]; 4]);
+    assert_eq!(network.hidden_layers[1].weights, vec![vec![0.5; 4]; 3]);
+    assert_eq!(network.outputs.weights, vec![vec![0.5; 3]; 2]);
+    assert_eq!(network.learning_rate, 0.2);
+
+    let hidden_0 = TANH.get_output(0.5 * 1. + 0.5 * -0.5);
+    let hidden_1 = RELU.get_output(0.5 * hidden_0 * 4.);
+    let expected = SIGMOID.get_output(0.5 * hidden_1 * 3.);
+    assert_eq!(network.compute(&[1., -0.5]), &[expected, expected]);
+}
+
+#[test]
+fn test_network_builder_trains() {
+    let mut network = Network::builder()
+        .input_size(1)
+        .hidden_layer(4, &TANH)
+        .output_size(1)
+        .weight_init(WeightInit::Uniform { min: -0.5, max: 0.5 })
+        .learning_rate(0.05)
+        .build()
+        .unwrap();
+
+    let mut rng = pcg::Pcg::default();
+    for _ in 0..5_000 {
+        let x = rng.gen_range(-1.0, 1.0);
+        network.train_one_example(&[x], &[0.5 * x], 0.05);
+    }
+    assert!((network.compute(&[0.5])[0] - 0.25).abs() < 0.05);
+}
+
+#[test]
+fn test_network_builder_rejects_invalid_configurations() {
+    let valid = || Network::builder().input_size(2).hidden_layer(3, &TANH).output_size(1);
+    assert!(valid().build().is_ok());
+
+    assert_eq!(valid().output_size(0).build().err(), Some(BuildError::ZeroOutputCount));
+    assert_eq!(valid().input_size(0).build().err(), Some(BuildError::ZeroInputCount));
+    assert_eq!(
+        Network::builder().input_size(2).output_size(1).build().err(),
+        Some(BuildError::NoHiddenLayers)
+    );
+    assert_eq!(
+        valid().hidden_layer(0, &TANH).build().err(),
+        Some(BuildError::ZeroNeuronCount { hidden_layer_ix: 1 })
+    );
+    for (min, max) in [(0.5, 0.5), (1., -1.), (Weight::NAN, 1.)] {
+        assert_eq!(
+            valid().weight_init(WeightInit::Uniform { min, max }).build().err(),
+            Some(BuildError::EmptyWeightRange)
+        );
+    }
+    assert!(valid().weight_init(WeightInit::Constant(0.5)).build().is_ok());
+}
+
+#[test]
+fn test_dense_layer_without_bias() {
+    let mut layer = DenseLayer::builder()
+        .output_count(2)
+        .input_count(2)
+        .weight_init(|neuron_ix, input_ix| if neuron_ix == input_ix { 1. } else { -0.5 })
+        .bias_init(|_| 10.)
+        .use_bias(false)
+        .build()
+        .unwrap();
+    assert!(layer.biases.is_empty());
+    assert!(!layer.use_bias);
+
+    layer.forward_propagate(&[2., 4.]);
+    assert_eq!(layer.outputs, vec![2. - 2., 4. - 1.]);
+
+    layer.compute_gradients(&[vec![1., 1.]], &[1.]);
+    layer.update_biases(0.1);
+    assert!(layer.biases.is_empty());
+
+    // Training a network containing a bias-free layer works as usual
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new_without_bias(2, 2, &mut |_, _| 0.5, &Tanh)],
+        outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut |_, _| 0.5, 2, 1)),
+        learning_rate: 0.1,
+    };
+    let cost_before = network.train_one_example(&[1., -0.5], &[1.], 0.1);
+    let cost_after = network.train_one_example(&[1., -0.5], &[1.], 0.1);
+    assert!(cost_after < cost_before);
+    assert!(network.hidden_layers[0].biases.is_empty());
+}
+
+#[derive(Default)]
+struct RecordingCallback {
+    events: Vec<String>,
+}
+
+impl Callback for RecordingCallback {
+    fn on_epoch_start(&mut self, epoch: usize, _network: &mut Network) { self.events.push(format!("start {}", epoch)); }
+
+    fn on_epoch_end(&mut self, epoch: usize, _train_loss: Weight, val_loss: Option<Weight>, _network: &Network) {
+        assert!(val_loss.is_some());
+        self.events.push(format!("end {}", epoch));
+    }
+
+    fn on_batch_end(&mut self, batch: usize, _loss: Weight) { self.events.push(format!("batch {}", batch)); }
+
+    fn on_training_end(&mut self, _network: &Network) { self.events.push("done".to_owned()); }
+}
+
+#[test]
+fn test_fit_runs_composed_callbacks() {
+    let mut rng = pcg::Pcg::default();
+    let examples = vec![vec![0.1, 0.9], vec![-0.5, 0.3], vec![0.7, -0.2]];
+    let expected = vec![vec![0.5], vec![-0.2], vec![0.9]];
+    let checkpoint_path = std::env::temp_dir().join(format!("libnn_checkpoint_{}.bin", std::process::id()));
+    let _ = std::fs::remove_file(&checkpoint_path);
+
+    let mut trainer = Trainer::new(build_small_network(&mut rng));
+    trainer.set_accumulation_steps(2);
+    let mut recorder = RecordingCallback::default();
+    let mut checkpoint = ModelCheckpointCallback::new(&checkpoint_path);
+    let mut lr_schedule = LrScheduleCallback::new(Box::new(ExponentialDecayLr {
+        initial_lr: 0.1,
+        decay_rate: 0.5,
+    }));
+    let mut progress = ProgressCallback::new(2);
+    trainer.fit(&examples, &expected, Some((&examples, &expected)), 2, &mut [
+        &mut lr_schedule,
+        &mut recorder,
+        &mut checkpoint,
+        &mut progress,
+    ]);
+
+    // Three examples with two accumulation steps makes one full and one partial batch per epoch
+    assert_eq!(recorder.events, vec![
+        "start 0", "batch 0", "batch 1", "end 0", "start 1", "batch 2", "batch 3", "end 1", "done"
+    ]);
+    assert_eq!(trainer.network.learning_rate, 0.05);
+    assert!(checkpoint.error.is_none());
+    assert!(checkpoint.best_loss.is_some());
+
+    // The checkpoint holds the weights from the epoch with the best validation loss
+    let mut restored = build_small_network(&mut rng);
+    let mut reader = std::io::BufReader::new(std::fs::File::open(&checkpoint_path).unwrap());
+    restored.load_weights(&mut reader).unwrap();
+    let restored_loss = restored.evaluate(&examples, &expected).mean_loss_per_example;
+    assert!((restored_loss - checkpoint.best_loss.unwrap()).abs() < 1e-6);
+    std::fs::remove_file(&checkpoint_path).unwrap();
+}
+
+#[test]
+fn test_load_weights_rejects_mismatched_shape() {
+    let mut rng = pcg::Pcg::default();
+    let network = build_small_network(&mut rng);
+    let mut saved = Vec::new();
+    network.save_weights(&mut saved).unwrap();
+
+    let mut other = Network::builder()
+        .input_size(2)
+        .hidden_layer(4, &TANH)
+        .output_size(1)
+        .build()
+        .unwrap();
+    assert!(other.load_weights(&mut saved.as_slice()).is_err());
+}
+
+#[test]
+fn test_load_rejects_corrupt_sizes_without_allocating_them() {
+    let network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &TANH)
+        .output_size(1)
+        .build()
+        .unwrap();
+    let mut saved = Vec::new();
+    network.save(&mut saved).unwrap();
+    // Magic, version, learning rate and hidden layer count, then the activation function's name
+    let name_offset = 16;
+    let matrix_offset = name_offset + 4 + TANH.name().len() + 1;
+    let corrupt = |offset: usize, values: &[u32]| {
+        let mut corrupt = saved.clone();
+        for (ix, val) in values.iter().enumerate() {
+            corrupt[offset + 4 * ix..offset + 4 * ix + 4].copy_from_slice(&val.to_le_bytes());
+        }
+        Network::load(&mut corrupt.as_slice())
+    };
+
+    assert!(corrupt(name_offset, &[u32::MAX]).is_err());
+    assert!(corrupt(matrix_offset, &[u32::MAX, u32::MAX]).is_err());
+    assert!(corrupt(matrix_offset, &[u32::MAX, 0]).is_err());
+    assert!(corrupt(matrix_offset, &[3, u32::MAX]).is_err());
+    let loaded = corrupt(matrix_offset, &[3, 2]).unwrap();
+    assert_eq!(loaded.hidden_layers[0].weights, network.hidden_layers[0].weights);
+}
+
+#[test]
+fn test_sequence_augmentation() {
+    let mut rng = pcg::Pcg::default();
+    let sequence: Vec<Weight> = (0..1000).map(|i| (i as Weight * 0.01).sin()).collect();
+
+    let jittered = SequenceAugmenter::new().jitter(0.1).augment(&sequence, &mut rng);
+    assert_eq!(jittered.len(), sequence.len());
+    let noise: Vec<Weight> = jittered.iter().zip(sequence.iter()).map(|(a, b)| a - b).collect();
+    let noise_mean = noise.iter().sum::<Weight>() / noise.len() as Weight;
+    let noise_std = (noise.iter().map(|n| (n - noise_mean).powi(2)).sum::<Weight>() / noise.len() as Weight).sqrt();
+    // Loose bounds, as `pcg::Pcg` doesn't produce particularly uniform floats
+    assert!(noise_mean.abs() < 0.05);
+    assert!(noise_std > 0.05 && noise_std < 0.2);
+
+    // Scaling keeps the ratios between elements, so the shape of the sequence is preserved
+    let scaled = SequenceAugmenter::new().scale(0.5, 2.).augment(&sequence, &mut rng);
+    let factor = scaled[1] / sequence[1];
+    assert!((0.5..2.).contains(&factor));
+    for (a, b) in scaled.iter().zip(sequence.iter()) {
+        assert!((a - b * factor).abs() < 1e-5);
+    }
+
+    // Warping a ramp keeps it a ramp covering the same range
+    let ramp: Vec<Weight> = (0..100).map(|i| i as Weight).collect();
+    let warped = SequenceAugmenter::new().time_warp(0.3).augment(&ramp, &mut rng);
+    assert_ne!(warped.len(), ramp.len());
+    assert!((70..=130).contains(&warped.len()));
+    assert_eq!(warped[0], 0.);
+    assert!((warped[warped.len() - 1] - 99.).abs() < 1e-3);
+    assert!(warped.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let cropped = SequenceAugmenter::new().crop(10, 20).augment(&ramp, &mut rng);
+    assert!((10..=20).contains(&cropped.len()));
+    let start = cropped[0] as usize;
+    assert_eq!(cropped, ramp[start..start + cropped.len()]);
+
+    // Transforms compose in the order they were added
+    let composed = SequenceAugmenter::new()
+        .crop(50, 50)
+        .scale(2., 3.)
+        .jitter(0.01)
+        .augment(&ramp, &mut rng);
+    assert_eq!(composed.len(), 50);
+    assert!(composed.windows(2).all(|pair| pair[1] - pair[0] > 1.5));
+}
+
+#[test]
+fn test_k_fold_splits_partition_examples() {
+    for (example_count, k) in [(10, 3), (9, 3), (5, 5), (7, 2)] {
+        let folds = k_fold_splits(example_count, k);
+        assert_eq!(folds.len(), k);
+
+        let mut seen = vec![false; example_count];
+        for fold in &folds {
+            assert!(fold.len() == example_count / k || fold.len() == example_count / k + 1);
+            for ix in fold.clone() {
+                assert!(!seen[ix], "Example {} is in more than one fold", ix);
+                seen[ix] = true;
+            }
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+}
+
+#[test]
+fn test_k_fold_cross_validate() {
+    let examples: Vec<Vec<Weight>> = (0..12)
+        .map(|i| vec![(i as Weight / 6.) - 1., ((i * 7) % 12) as Weight / 12.])
+        .collect();
+    let expected: Vec<Vec<Weight>> = examples.iter().map(|ex| vec![0.5 * ex[0] - 0.25 * ex[1]]).collect();
+
+    let untrained = k_fold_cross_validate(
+        || build_small_network(&mut pcg::Pcg::default()),
+        &examples,
+        &expected,
+        4,
+        0,
+        0.1,
+    );
+    let trained = k_fold_cross_validate(
+        || build_small_network(&mut pcg::Pcg::default()),
+        &examples,
+        &expected,
+        4,
+        50,
+        0.1,
+    );
+    assert_eq!(trained.fold_losses.len(), 4);
+    assert!(trained.mean_loss < untrained.mean_loss);
+    assert!(trained.std_loss >= 0.);
+
+    let mean = trained.fold_losses.iter().sum::<Weight>() / 4.;
+    assert!((trained.mean_loss - mean).abs() < 1e-6);
+}
+
+#[test]
+fn test_binary_classification_metrics() {
+    // 3 true positives, 1 false positive, 2 false negatives, 4 true negatives
+    let actual = [1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+    let predicted = [1, 1, 1, 0, 0, 1, 0, 0, 0, 0];
+    let metrics = ClassificationMetrics::from_predictions(&predicted, &actual, 2);
+
+    assert_eq!(metrics.confusion_matrix, vec![vec![4, 1], vec![2, 3]]);
+    assert_eq!(metrics.precision, vec![4. / 6., 3. / 4.]);
+    assert_eq!(metrics.recall, vec![4. / 5., 3. / 5.]);
+    let f1_positive = 2. * 0.75 * 0.6 / (0.75 + 0.6);
+    assert!((metrics.f1[1] - f1_positive).abs() < 1e-6);
+    assert_eq!(metrics.accuracy, 0.7);
+    assert!((metrics.macro_precision - (4. / 6. + 0.75) / 2.).abs() < 1e-6);
+    assert!((metrics.macro_recall - 0.7).abs() < 1e-6);
+    assert_eq!(metrics.micro_f1, 0.7);
+}
+
+#[test]
+fn test_multiclass_classification_metrics() {
+    let actual = [0, 0, 1, 1, 2, 2];
+    let predicted = [0, 1, 1, 1, 0, 0];
+    let metrics = ClassificationMetrics::from_predictions(&predicted, &actual, 3);
+
+    assert_eq!(metrics.confusion_matrix, vec![vec![1, 1, 0], vec![0, 2, 0], vec![
+        2, 0, 0
+    ]]);
+    assert_eq!(metrics.precision, vec![1. / 3., 2. / 3., 0.]);
+    assert_eq!(metrics.recall, vec![0.5, 1., 0.]);
+    assert!((metrics.f1[0] - 0.4).abs() < 1e-6);
+    assert!((metrics.f1[1] - 0.8).abs() < 1e-6);
+    assert_eq!(metrics.f1[2], 0.);
+    assert!((metrics.macro_f1 - 0.4).abs() < 1e-6);
+    assert!((metrics.micro_precision - 0.5).abs() < 1e-6);
+    assert!((metrics.micro_recall - 0.5).abs() < 1e-6);
+
+    // Outputs are read as class probabilities
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new(
+            2,
+            2,
+            &mut |n, i| if n == i { 1. } else { 0. },
+            &mut |_| 0.,
+            &IDENTITY,
+        )],
+        outputs: Box::new(OutputLayer::new(
+            &Identity,
+            &MeanSquaredError,
+            &mut |n, i| if n == i { 1. } else { 0. },
+            2,
+            2,
+        )),
+        learning_rate: 0.1,
+    };
+    let examples = vec![vec![0.9, 0.1], vec![0.2, 0.8], vec![0.6, 0.4]];
+    let expected = vec![vec![1., 0.], vec![0., 1.], vec![0., 1.]];
+    let result = network.evaluate_classification(&examples, &expected);
+    let classification = result.classification.unwrap();
+    assert_eq!(classification.confusion_matrix, vec![vec![1, 0], vec![1, 1]]);
+    assert!(network.evaluate(&examples, &expected).classification.is_none());
+}
+
+#[test]
+fn test_forecast_metrics() {
+    let actual = [1., 2., 3., 4.];
+    let predicted = [1.5, 2., 2., 4.5];
+    let metrics = ForecastMetrics::compute(&predicted, &actual);
+
+    // Errors are -0.5, 0, 1, -0.5
+    assert!((metrics.rmse - (1.5 as Weight / 4.).sqrt()).abs() < 1e-6);
+    assert!((metrics.mae - 0.5).abs() < 1e-6);
+    assert!((metrics.mape - 100. * (0.5 + 0. + 1. / 3. + 0.125) / 4.).abs() < 1e-4);
+    // The actual values have a total sum of squares of 5
+    assert!((metrics.r2_score - (1. - 1.5 / 5.)).abs() < 1e-6);
+
+    let perfect = ForecastMetrics::compute(&actual, &actual);
+    assert_eq!(
+        (perfect.rmse, perfect.mae, perfect.mape, perfect.r2_score),
+        (0., 0., 0., 1.)
+    );
+
+    let with_zero = ForecastMetrics::compute(&[0., 1.], &[0., 2.]);
+    assert!(with_zero.mape.is_finite());
+    assert!((with_zero.mape - 25.).abs() < 1e-4);
+    let zero_miss = ForecastMetrics::compute(&[0.5], &[0.]);
+    assert!(zero_miss.mape.is_finite());
+    assert_eq!(zero_miss.r2_score, 0.);
+}
+
+#[test]
+fn test_lr_finder() {
+    let mut rng = pcg::Pcg::default();
+    let network = build_small_network(&mut rng);
+    let weights_before = all_weights(&network);
+    let examples = vec![vec![0.1, 0.9], vec![-0.5, 0.3], vec![0.7, -0.2], vec![-0.9, -0.4]];
+    let expected = vec![vec![0.5], vec![-0.2], vec![0.9], vec![0.]];
+
+    let result = LrFinder::run(&network, &examples, &expected, 1e-4, 100., 60);
+    assert_eq!(all_weights(&network), weights_before);
+    assert!(result.diverged);
+    assert!(result.losses.len() < 60);
+    assert_eq!(result.learning_rates.len(), result.losses.len());
+    assert!(result.learning_rates.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(result.suggested_lr >= 1e-4 && result.suggested_lr < *result.learning_rates.last().unwrap());
+    assert!(result.losses.iter().cloned().fold(Weight::INFINITY, Weight::min) < result.losses[0]);
+}
+
+#[test]
+fn test_gradient_monitoring_reports_non_finite_gradients() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut rng = pcg::Pcg::default();
+    let mut trainer = Trainer::new(build_small_network(&mut rng));
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let hook_events = events.clone();
+    trainer.enable_gradient_monitoring(move |event| hook_events.borrow_mut().push(event));
+
+    // A huge learning rate sends the weights to infinity and the gradients to NaN within a few steps
+    for _ in 0..10 {
+        trainer.train_one_example(&[0.5, -0.3], &[1.], 1e30);
+        if !events.borrow().is_empty() {
+            break;
+        }
+    }
+
+    let network = &trainer.network;
+    let reported = events.borrow().clone();
+    assert!(!reported.is_empty());
+    for event in &reported {
+        assert!(!event.gradient.is_finite());
+        let gradient = if event.layer_ix == network.hidden_layers.len() {
+            network.outputs.neuron_gradients[event.neuron_ix]
+        } else {
+            network.hidden_layers[event.layer_ix].neuron_gradients[event.neuron_ix]
+        };
+        assert!(!gradient.is_finite());
+    }
+
+    trainer.disable_gradient_monitoring();
+    events.borrow_mut().clear();
+    trainer.train_one_example(&[0.5, -0.3], &[1.], 1e30);
+    assert!(events.borrow().is_empty());
+}
+
+#[test]
+fn test_mixture_density_gradients_match_finite_differences() {
+    let mdn = MixtureDensityOutput::new(2);
+    let outputs = [0.3, -0.2, 0.5, -1., -0.4, 0.2];
+    let target = 0.1;
+    let mut gradients = [0.; 6];
+    mdn.compute_output_gradients(&outputs, target, &mut gradients);
+
+    let epsilon = 1e-2;
+    for ix in 0..outputs.len() {
+        let mut plus = outputs;
+        plus[ix] += epsilon;
+        let mut minus = outputs;
+        minus[ix] -= epsilon;
+        let numeric = -(mdn.negative_log_likelihood(&plus, target) - mdn.negative_log_likelihood(&minus, target))
+            / (2. * epsilon);
+        assert!(
+            (numeric - gradients[ix]).abs() < 1e-2,
+            "Output {}: analytic {} numeric {}",
+            ix,
+            gradients[ix],
+            numeric
+        );
+    }
+}
+
+#[test]
+fn test_mixture_density_network_fits_1d_regression() {
+    let mut rng = pcg::Pcg::default();
+    let mdn = MixtureDensityOutput::new(2);
+    let mut network = Network::builder()
+        .input_size(1)
+        .hidden_layer(8, &TANH)
+        .output_size(mdn.output_count())
+        .weight_init(WeightInit::Uniform { min: -0.5, max: 0.5 })
+        .learning_rate(0.01)
+        .build()
+        .unwrap();
+
+    // y = x with a little deterministic noise
+    let data: Vec<(Weight, Weight)> = (0..40)
+        .map(|i| {
+            let x = i as Weight / 20. - 1.;
+            (x, x + if i % 2 == 0 { 0.1 } else { -0.1 })
+        })
+        .collect();
+    let mean_nll = |network: &mut Network| {
+        data.iter()
+            .map(|&(x, y)| mdn.negative_log_likelihood(network.compute(&[x]), y))
+            .sum::<Weight>()
+            / data.len() as Weight
+    };
+
+    let nll_before = mean_nll(&mut network);
+    for _ in 0..500 {
+        for &(x, y) in &data {
+            mdn.train_one_example(&mut network, &[x], y, 0.01);
+        }
+    }
+    let nll_after = mean_nll(&mut network);
+    assert!(
+        nll_after < nll_before - 1.,
+        "NLL went from {} to {}",
+        nll_before,
+        nll_after
+    );
+
+    let outputs = network.compute(&[0.5]).to_owned();
+    let params = mdn.parameters(&outputs);
+    assert!((params.pis.iter().sum::<Weight>() - 1.).abs() < 1e-5);
+    assert!((params.mean() - 0.5).abs() < 0.2);
+
+    let samples: Vec<Weight> = (0..500).map(|_| mdn.sample(&outputs, &mut rng)).collect();
+    let sample_mean = samples.iter().sum::<Weight>() / samples.len() as Weight;
+    assert!((sample_mean - 0.5).abs() < 0.25);
+    assert!(samples.iter().all(|&sample| sample > -1. && sample < 2.));
+}
+
+/// Sums the probability of every path through `probs` that collapses to `targets`
+fn brute_force_ctc_likelihood(probs: &[Vec<Weight>], targets: &[usize], blank_label: usize) -> Weight {
+    let label_count = probs[0].len();
+    let path_count = label_count.pow(probs.len() as u32);
+    let mut likelihood = 0.;
+    for path_ix in 0..path_count {
+        let mut remaining = path_ix;
+        let mut prob = 1.;
+        let mut collapsed = Vec::new();
+        let mut prev_label = None;
+        for step_probs in probs {
+            let label = remaining % label_count;
+            remaining /= label_count;
+            prob *= step_probs[label];
+            if label != blank_label && prev_label != Some(label) {
+                collapsed.push(label);
+            }
+            prev_label = Some(label);
+        }
+        if collapsed == targets {
+            likelihood += prob;
+        }
+    }
+    likelihood
+}
+
+#[test]
+fn test_ctc_loss_matches_brute_force() {
+    let probs: Vec<Vec<Weight>> = vec![vec![0.5, 0.3, 0.2], vec![0.2, 0.5, 0.3], vec![0.4, 0.2, 0.4], vec![
+        0.1, 0.3, 0.6,
+    ]];
+    let log_probs: Vec<Vec<Weight>> = probs.iter().map(|step| step.iter().map(|p| p.ln()).collect()).collect();
+
+    for targets in [vec![1, 2], vec![1, 1], vec![2], vec![]] {
+        let (loss, gradients) = CtcLoss::compute(&log_probs, &targets, 0);
+        let expected_loss = -brute_force_ctc_likelihood(&probs, &targets, 0).ln();
+        assert!(
+            (loss - expected_loss).abs() < 1e-4,
+            "{:?}: {} vs {}",
+            targets,
+            loss,
+            expected_loss
+        );
+
+        let epsilon = 1e-3;
+        for t in 0..log_probs.len() {
+            for label_ix in 0..3 {
+                let mut perturbed = log_probs.clone();
+                perturbed[t][label_ix] += epsilon;
+                let (loss_plus, _) = CtcLoss::compute(&perturbed, &targets, 0);
+                perturbed[t][label_ix] -= 2. * epsilon;
+                let (loss_minus, _) = CtcLoss::compute(&perturbed, &targets, 0);
+                let numeric = (loss_plus - loss_minus) / (2. * epsilon);
+                assert!((numeric - gradients[t][label_ix]).abs() < 1e-2);
+            }
+        }
+    }
+
+    // Two repeated labels need a blank between them, which doesn't fit in two steps
+    let (loss, gradients) = CtcLoss::compute(&log_probs[..2], &[1, 1], 0);
+    assert_eq!(loss, Weight::INFINITY);
+    assert!(gradients.iter().flatten().all(|&gradient| gradient == 0.));
+}
+
+#[test]
+fn test_weight_sensitivity_flags_zeroed_weight() {
+    let mut rng = pcg::Pcg::default();
+    let mut network = build_small_network(&mut rng);
+    let examples: Vec<Vec<Weight>> = (0..10).map(|i| vec![i as Weight / 5. - 1., 0.3]).collect();
+    let expected: Vec<Vec<Weight>> = examples.iter().map(|example| vec![0.8 * example[0]]).collect();
+    for _ in 0..300 {
+        for (example, expected) in examples.iter().zip(expected.iter()) {
+            network.train_one_example(example, expected, 0.1);
+        }
+    }
+
+    // Knock out the hidden weight carrying the most signal from the input that matters
+    let neuron_ix = (0..3)
+        .max_by(|&a, &b| {
+            let strength = |ix: usize| (network.hidden_layers[0].weights[ix][0] * network.outputs.weights[0][ix]).abs();
+            strength(a).total_cmp(&strength(b))
+        })
+        .unwrap();
+    network.hidden_layers[0].weights[neuron_ix][0] = 0.;
+    let weights_before = all_weights(&network);
+
+    let report = network.weight_sensitivity(&examples, &expected, 0.01);
+    assert_eq!(all_weights(&network), weights_before);
+    assert_eq!(report.weights.len(), 3 * 2 + 3);
+    assert!(report
+        .weights
+        .windows(2)
+        .all(|pair| pair[0].sensitivity >= pair[1].sensitivity));
+    let top = report.weights[0];
+    assert_eq!((top.layer_ix, top.neuron_ix, top.weight_ix), (0, neuron_ix, 0));
+
+    assert_eq!(report.layers.len(), 2);
+    assert_eq!(report.layers[0].max_sensitivity, top.sensitivity);
+    assert!(report.layers[1].mean_sensitivity <= report.layers[1].max_sensitivity);
+}
+
+struct Cube;
+static CUBE: Cube = Cube;
+
+impl ActivationFunction for Cube {
+    fn name(&self) -> &'static str { "cube" }
+
+    fn get_output(&self, x: Weight) -> Weight { x * x * x }
+
+    fn derivative(&self, x: Weight) -> Weight { 3. * x * x }
+}
+
+fn round_trip(network: &Network) -> Network {
+    let mut saved = Vec::new();
+    network.save(&mut saved).unwrap();
+    Network::load(&mut saved.as_slice()).unwrap()
+}
+
+#[test]
+fn test_activation_functions_round_trip_through_save_and_load() {
+    let builtins: [&'static dyn ActivationFunction; 14] = [
+        &SIGMOID,
+        &STABLE_SIGMOID,
+        &TANH,
+        &IDENTITY,
+        &RELU,
+        &LEAKY_RELU,
+        &GCU,
+        &GAUSSIAN,
+        &SWISH,
+        &GELU,
+        &GELU_TANH,
+        &ELU,
+        &SELU,
+        &AMEO,
+    ];
+    let inputs = [0.7, -1.3];
+    for activation_fn in builtins {
+        let mut network = Network::builder()
+            .input_size(2)
+            .hidden_layer(3, activation_fn)
+            .output_size(2)
+            .output_activation(activation_fn)
+            .learning_rate(0.05)
+            .build()
+            .unwrap();
+        network.hidden_layers[0].biases = vec![0.1, -0.2, 0.3];
+
+        let mut loaded = round_trip(&network);
+        assert_eq!(loaded.hidden_layers[0].activation_fn.name(), activation_fn.name());
+        assert_eq!(loaded.outputs.activation_fn.name(), activation_fn.name());
+        assert_eq!(all_weights(&loaded), all_weights(&network));
+        assert_eq!(loaded.learning_rate, 0.05);
+        assert_eq!(loaded.compute(&inputs), network.compute(&inputs));
+    }
+
+    let network = Network::builder()
+        .input_size(2)
+        .hidden_layer(2, &CUBE)
+        .output_size(1)
+        .build()
+        .unwrap();
+    let mut saved = Vec::new();
+    network.save(&mut saved).unwrap();
+    assert!(Network::load(&mut saved.as_slice()).is_err());
+
+    register_activation_fn("cube", &CUBE);
+    let loaded = round_trip(&network);
+    assert_eq!(loaded.hidden_layers[0].activation_fn.name(), "cube");
+    assert!(ActivationFunctionRegistry::lookup("not registered").is_none());
+}
+
+struct AbsoluteError;
+static ABSOLUTE_ERROR: AbsoluteError = AbsoluteError;
+
+impl CostFunction for AbsoluteError {
+    fn name(&self) -> &'static str { "absolute_error" }
+
+    fn get_cost(&self, error: Weight) -> Weight { error.abs() }
+
+    fn derivative(&self, error: Weight) -> Weight { error.signum() }
+}
+
+#[test]
+fn test_cost_functions_round_trip_through_save_and_load() {
+    let mut network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &TANH)
+        .output_size(2)
+        .build()
+        .unwrap();
+    let mut loaded = round_trip(&network);
+    assert_eq!(loaded.outputs.cost_fn.name(), "mean_squared_error");
+
+    let examples = vec![vec![0.3, -0.8]];
+    let expected = vec![vec![0.5, 0.1]];
+    assert_eq!(loaded.compute(&examples[0]), network.compute(&examples[0]));
+    assert_eq!(
+        loaded.evaluate(&examples, &expected),
+        network.evaluate(&examples, &expected)
+    );
+
+    network.outputs.cost_fn = &ABSOLUTE_ERROR;
+    let mut saved = Vec::new();
+    network.save(&mut saved).unwrap();
+    assert!(Network::load(&mut saved.as_slice()).is_err());
+
+    CostFunctionRegistry::register("absolute_error", &ABSOLUTE_ERROR);
+    let mut loaded = round_trip(&network);
+    assert_eq!(loaded.outputs.cost_fn.name(), "absolute_error");
+    assert_eq!(
+        loaded.evaluate(&examples, &expected),
+        network.evaluate(&examples, &expected)
+    );
+}
+
+#[test]
+fn test_profiling_network_records_timings() {
+    let network = Network::builder()
+        .input_size(64)
+        .hidden_layer(128, &TANH)
+        .hidden_layer(128, &TANH)
+        .output_size(16)
+        .build()
+        .unwrap();
+    let mut reference = network.clone();
+    let mut profiled = ProfilingNetwork::new(network);
+    assert_eq!(*profiled.get_profile(), Profile::default());
+
+    let example: Vec<Weight> = (0..64).map(|i| (i as Weight * 0.1).sin()).collect();
+    let expected = vec![0.5; 16];
+    for _ in 0..5 {
+        let cost = profiled.train_one_example(&example, &expected, 0.01);
+        assert_eq!(cost, reference.train_one_example(&example, &expected, 0.01));
+    }
+    let profile = profiled.get_profile().clone();
+    assert!(profile.forward_time_ns > 0);
+    assert!(profile.backward_time_ns > 0);
+    assert!(profile.weight_update_time_ns > 0);
+
+    profiled.compute(&example);
+    assert!(profiled.get_profile().forward_time_ns > profile.forward_time_ns);
+    assert_eq!(profiled.get_profile().backward_time_ns, profile.backward_time_ns);
+
+    profiled.reset_profile();
+    assert_eq!(*profiled.get_profile(), Profile::default());
+}
+
+#[test]
+fn test_loss_weight_scales_gradients() {
+    let mut rng = pcg::Pcg::default();
+    let mut network = build_small_network(&mut rng);
+    let example = [0.4, -0.7];
+    let expected = [0.9];
+
+    let gradients = |network: &Network| {
+        let mut gradients: Vec<Weight> = network.outputs.neuron_gradients.clone();
+        for layer in &network.hidden_layers {
+            gradients.extend(layer.neuron_gradients.iter());
+        }
+        gradients
+    };
+
+    network.compute_gradients(&example, &expected);
+    let unweighted = gradients(&network);
+    network.compute_weighted_gradients(&example, &expected, LossWeight::default());
+    assert_eq!(gradients(&network), unweighted);
+
+    network.compute_weighted_gradients(&example, &expected, LossWeight(2.));
+    let doubled = gradients(&network);
+    for (doubled, unweighted) in doubled.iter().zip(unweighted.iter()) {
+        assert!((doubled - 2. * unweighted).abs() < 1e-6);
+    }
+
+    network.compute_weighted_gradients(&example, &expected, LossWeight(0.));
+    assert!(gradients(&network).iter().all(|&gradient| gradient == 0.));
+
+    // An ignored example leaves every weight where it was
+    let weights_before = all_weights(&network);
+    network.train_one_example_weighted(&example, &expected, LossWeight(0.), 0.1);
+    assert_eq!(all_weights(&network), weights_before);
+}
+
+#[test]
+fn test_bucketed_data_loader() {
+    // Sequence lengths 1 through 12, each labeled with its own length so it can be identified later
+    let data: Vec<(Vec<Weight>, Vec<Weight>)> = (1..=12).map(|len| (vec![0.5; len], vec![len as Weight])).collect();
+    let mut loader = BucketedDataLoader::new(vec![4, 8], data);
+    assert_eq!(loader.len(), 12);
+    assert_eq!(loader.bucket_ix_for_len(3), 0);
+    assert_eq!(loader.bucket_ix_for_len(4), 1);
+    assert_eq!(loader.bucket_ix_for_len(100), 2);
+
+    for _ in 0..2 {
+        let mut seen = Vec::new();
+        loop {
+            let batch = loader.next_batch(2);
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 2);
+            let bucket_ix = loader.bucket_ix_for_len(batch[0].0.len());
+            for (sequence, expected) in &batch {
+                assert_eq!(loader.bucket_ix_for_len(sequence.len()), bucket_ix);
+                assert_eq!(expected[0] as usize, sequence.len());
+                seen.push(sequence.len());
+            }
+        }
+
+        seen.sort_unstable();
+        assert_eq!(seen, (1..=12).collect::<Vec<_>>());
+        loader.reset();
+    }
+}
+
+fn cosine_similarity(a: &[Weight], b: &[Weight]) -> Weight {
+    let dot: Weight = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum();
+    let norm = |v: &[Weight]| v.iter().map(|x| x * x).sum::<Weight>().sqrt();
+    dot / (norm(a) * norm(b))
+}
+
+#[test]
+fn test_sinusoidal_positional_encoding() {
+    let encoding = SinusoidalPositionalEncoding::new(16, 64);
+    assert_eq!(encoding.encoding(0)[..4], [0., 1., 0., 1.]);
+    assert!((encoding.encoding(1)[0] - (1. as Weight).sin()).abs() < 1e-6);
+
+    for position in [5, 20, 40] {
+        let adjacent = cosine_similarity(encoding.encoding(position), encoding.encoding(position + 1));
+        let distant = cosine_similarity(encoding.encoding(position), encoding.encoding(position + 20));
+        assert!(adjacent > distant, "{}: {} <= {}", position, adjacent, distant);
+    }
+
+    let encoded = encoding.encode(3, &[1.; 16]);
+    assert_eq!(encoded[0], 1. + encoding.encoding(3)[0]);
+}
+
+#[test]
+fn test_learned_positional_encoding_trains() {
+    let mut rng = pcg::Pcg::default();
+    let mut encoding = LearnedPositionalEncoding::new(4, 2, &mut |_, _| 0.);
+    let mut network = build_small_network(&mut rng);
+    // Every position sees the same input, so the network can only tell them apart using the encodings
+    let inputs = [0.2, 0.2];
+    let targets = [-0.6, -0.2, 0.2, 0.6];
+
+    let total_cost = |network: &mut Network, encoding: &LearnedPositionalEncoding| {
+        (0..4)
+            .map(|position| {
+                let output = network.compute(&encoding.encode(position, &inputs))[0];
+                (targets[position] - output).powi(2)
+            })
+            .sum::<Weight>()
+    };
+    let cost_before = total_cost(&mut network, &encoding);
+
+    let mut input_gradients = [0.; 2];
+    for _ in 0..300 {
+        for (position, &target) in targets.iter().enumerate() {
+            let encoded = encoding.encode(position, &inputs);
+            network.compute_gradients(&encoded, &[target]);
+            network.compute_input_gradients(&mut input_gradients);
+            encoding.accumulate_gradients(position, &input_gradients);
+            network.update_weights(&encoded, 0.1);
+            encoding.update_weights(0.1);
+        }
+    }
+
+    assert!(total_cost(&mut network, &encoding) < cost_before / 10.);
+    assert!(encoding.gradients.iter().flatten().all(|&gradient| gradient == 0.));
+    assert_ne!(encoding.encoding(0), encoding.encoding(3));
+}
+
+#[test]
+fn test_layer_norm() {
+    let x = [1., 4., -2., 0.5, 3.];
+    let mut layer_norm = LayerNorm::new(5, 1e-5);
+    layer_norm.gamma = vec![1.5, 0.5, -1., 2., 1.];
+    layer_norm.beta = vec![0.1, -0.2, 0.3, 0., 0.5];
+    let outputs = layer_norm.normalize(&x);
+
+    let mean = layer_norm.normalized.iter().sum::<Weight>() / 5.;
+    let variance = layer_norm.normalized.iter().map(|n| (n - mean).powi(2)).sum::<Weight>() / 5.;
+    assert!(mean.abs() < 1e-5);
+    assert!((variance - 1.).abs() < 1e-3);
+    for (feature_ix, &output) in outputs.iter().enumerate() {
+        let expected = layer_norm.gamma[feature_ix] * layer_norm.normalized[feature_ix] + layer_norm.beta[feature_ix];
+        assert!((output - expected).abs() < 1e-6);
+    }
+
+    // With a cost of sum(c * y), the negated output gradients are -c
+    let c = [0.3, -1.2, 0.7, 0.2, -0.4];
+    let output_gradients: Vec<Weight> = c.iter().map(|c| -c).collect();
+    let mut input_gradients = [0.; 5];
+    layer_norm.compute_gradients(&output_gradients, &mut input_gradients);
+
+    let cost = |layer_norm: &mut LayerNorm, x: &[Weight]| -> Weight {
+        layer_norm.normalize(x).iter().zip(c.iter()).map(|(y, c)| y * c).sum()
+    };
+    let epsilon = 1e-2;
+    for feature_ix in 0..5 {
+        let mut plus = x;
+        plus[feature_ix] += epsilon;
+        let mut minus = x;
+        minus[feature_ix] -= epsilon;
+        let mut probe = layer_norm.clone();
+        let numeric = (cost(&mut probe, &plus) - cost(&mut probe, &minus)) / (2. * epsilon);
+        assert!((numeric + input_gradients[feature_ix]).abs() < 1e-2);
+    }
+
+    let normalized = layer_norm.normalized.clone();
+    let gamma_before = layer_norm.gamma.clone();
+    let beta_before = layer_norm.beta.clone();
+    layer_norm.update_weights(0.1);
+    for feature_ix in 0..5 {
+        assert!(
+            (layer_norm.gamma[feature_ix] - (gamma_before[feature_ix] - 0.1 * c[feature_ix] * normalized[feature_ix]))
+                .abs()
+                < 1e-6
+        );
+        assert!((layer_norm.beta[feature_ix] - (beta_before[feature_ix] - 0.1 * c[feature_ix])).abs() < 1e-6);
+    }
+    assert!(layer_norm
+        .gamma_gradients
+        .iter()
+        .chain(layer_norm.beta_gradients.iter())
+        .all(|&g| g == 0.));
+}
+
+/// Xorshift generator for tests that check the statistics of random values, which `pcg::Pcg` isn't uniform enough for
+struct XorShiftRng(u64);
+
+impl rand::RngCore for XorShiftRng {
+    fn next_u32(&mut self) -> u32 { (self.next_u64() >> 32) as u32 }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn mean_and_std_dev(vals: &[Weight]) -> (Weight, Weight) {
+    let mean = vals.iter().sum::<Weight>() / vals.len() as Weight;
+    let variance = vals.iter().map(|v| (v - mean).powi(2)).sum::<Weight>() / vals.len() as Weight;
+    (mean, variance.sqrt())
+}
+
+#[test]
+fn test_noise_layers() {
+    let mut rng = XorShiftRng(0x2545_f491_4f6c_dd1d);
+    let inputs = vec![1.5; 20_000];
+
+    let mut gaussian = GaussianNoiseLayer::new(0.3);
+    let noisy = gaussian.forward_propagate(&inputs, &mut rng);
+    let (mean, std_dev) = mean_and_std_dev(&noisy);
+    assert!((mean - 1.5).abs() < 0.01);
+    assert!((std_dev - 0.3).abs() < 0.01);
+
+    let mut uniform = UniformNoiseLayer::new(-0.5, 0.25);
+    let noisy = uniform.forward_propagate(&inputs, &mut rng);
+    assert!(noisy.iter().all(|x| (1. ..1.75).contains(x)));
+    let (mean, std_dev) = mean_and_std_dev(&noisy);
+    assert!((mean - 1.375).abs() < 0.01);
+    // The standard deviation of a uniform distribution is its width over sqrt(12)
+    assert!((std_dev - 0.75 / (12. as Weight).sqrt()).abs() < 0.01);
+
+    gaussian.training = false;
+    uniform.training = false;
+    assert_eq!(gaussian.forward_propagate(&inputs, &mut rng), inputs);
+    assert_eq!(uniform.forward_propagate(&inputs, &mut rng), inputs);
+}
+
+#[test]
+fn test_bag_of_words_layer() {
+    let embedding = EmbeddingLayer::new(5, 2, &mut |token_ix, dim_ix| (token_ix * 10 + dim_ix) as Weight);
+    let mut bag = BagOfWordsLayer::new(embedding, BagOfWordsReduction::Mean);
+    assert_eq!(bag.forward_propagate(&[1, 3]), vec![20., 21.]);
+    let mean = bag.forward_propagate(&[2, 2, 4]);
+    assert!((mean[0] - 80. / 3.).abs() < 1e-5 && (mean[1] - 83. / 3.).abs() < 1e-5);
+    assert_eq!(bag.forward_propagate(&[]), vec![0., 0.]);
+
+    bag.reduction = BagOfWordsReduction::Sum;
+    assert_eq!(bag.forward_propagate(&[1, 3]), vec![40., 42.]);
+
+    bag.reduction = BagOfWordsReduction::Mean;
+    bag.accumulate_gradients(&[1, 3, 3, 0], &[1., -2.]);
+    assert_eq!(bag.embedding.gradients[0], vec![0.25, -0.5]);
+    assert_eq!(bag.embedding.gradients[1], vec![0.25, -0.5]);
+    assert_eq!(bag.embedding.gradients[3], vec![0.5, -1.]);
+    for token_ix in [2, 4] {
+        assert!(bag.embedding.gradients[token_ix].iter().all(|&gradient| gradient == 0.));
+    }
+
+    bag.update_weights(1.);
+    assert_eq!(bag.embedding.embeddings[3], vec![30.5, 30.]);
+    assert_eq!(bag.embedding.embeddings[4], vec![40., 41.]);
+}
+
+/// Hand-builds a version 1.0 .npy file, padding the header like NumPy does
+fn npy_bytes(descr: &str, fortran_order: bool, shape: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}",
+        descr,
+        if fortran_order { "True" } else { "False" },
+        shape
+    );
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn test_load_dense_layer_weights_from_npy() {
+    let path = std::env::temp_dir().join(format!("libnn_weights_{}.npy", std::process::id()));
+    let mut layer = DenseLayer::new(2, 3, &mut |_, _| 0., &mut |_| 0.5, &IDENTITY);
+
+    // C order f4: rows are neurons
+    let data: Vec<u8> = [1f32, 2., 3., 4., 5., 6.]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    std::fs::write(&path, npy_bytes("<f4", false, "(2, 3)", &data)).unwrap();
+    layer.load_weights_from_npy(&path).unwrap();
+    assert_eq!(layer.weights, vec![vec![1., 2., 3.], vec![4., 5., 6.]]);
+    assert_eq!(layer.biases, vec![0.5, 0.5]);
+
+    // Fortran order f8 stores the same matrix column by column
+    let data: Vec<u8> = [1f64, 4., 2., 5., 3., 6.]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    std::fs::write(&path, npy_bytes("<f8", true, "(2, 3)", &data)).unwrap();
+    layer.weights = vec![vec![0.; 3]; 2];
+    layer.load_weights_from_npy(&path).unwrap();
+    assert_eq!(layer.weights, vec![vec![1., 2., 3.], vec![4., 5., 6.]]);
+
+    let array = NpyArray::read(&mut npy_bytes("<f4", false, "(6,)", &[0; 24]).as_slice()).unwrap();
+    assert_eq!(array.shape, vec![6]);
+
+    std::fs::write(&path, npy_bytes("<f4", false, "(3, 2)", &[0; 24])).unwrap();
+    assert!(matches!(
+        layer.load_weights_from_npy(&path),
+        Err(NpyError::ShapeMismatch { .. })
+    ));
+    std::fs::write(&path, npy_bytes("<i4", false, "(2, 3)", &[0; 24])).unwrap();
+    assert!(matches!(
+        layer.load_weights_from_npy(&path),
+        Err(NpyError::UnsupportedDtype(_))
+    ));
+    std::fs::write(&path, b"not numpy").unwrap();
+    assert!(matches!(
+        layer.load_weights_from_npy(&path),
+        Err(NpyError::InvalidMagic)
+    ));
+
+    // Shapes describing more data than the file holds fail once it runs out, and ones too big to count fail up front
+    let huge = NpyArray::read(&mut npy_bytes("<f4", false, "(1000000000, 1000000000)", &[0; 24]).as_slice());
+    assert!(matches!(huge, Err(NpyError::Io(_))));
+    let overflowing = format!("({}, 2)", usize::MAX);
+    let overflowing = NpyArray::read(&mut npy_bytes("<f4", false, &overflowing, &[0; 24]).as_slice());
+    assert!(matches!(overflowing, Err(NpyError::InvalidHeader(_))));
+    let mut truncated_header = npy_bytes("<f4", false, "(2, 3)", &[]);
+    truncated_header[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert!(matches!(
+        NpyArray::read(&mut truncated_header.as_slice()),
+        Err(NpyError::Io(_))
+    ));
+
+    let mut no_inputs = DenseLayer::new(2, 0, &mut |_, _| 0., &mut |_| 0.5, &IDENTITY);
+    std::fs::write(&path, npy_bytes("<f4", false, "(2, 0)", &[])).unwrap();
+    no_inputs.load_weights_from_npy(&path).unwrap();
+    assert_eq!(no_inputs.weights, vec![Vec::<Weight>::new(); 2]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sparse_embedding_updates_match_dense() {
+    let mut rng = pcg::Pcg::default();
+    let mut embedding = EmbeddingLayer::new(1000, 4, &mut |_, _| rng.gen_range(-1., 1.));
+    let mut dense = embedding.clone();
+
+    let batch = [3, 17, 3, 999, 42];
+    for (step, &token_ix) in batch.iter().enumerate() {
+        let gradients: Vec<Weight> = (0..4).map(|dim_ix| (step * 4 + dim_ix) as Weight * 0.1).collect();
+        embedding.accumulate_gradients(token_ix, &gradients);
+        dense.accumulate_gradients(token_ix, &gradients);
+    }
+    let mut dirty_rows: Vec<usize> = embedding.dirty_rows.iter().copied().collect();
+    dirty_rows.sort_unstable();
+    assert_eq!(dirty_rows, vec![3, 17, 42, 999]);
+
+    embedding.update_weights(0.5);
+    for (row, gradients) in dense.embeddings.iter_mut().zip(dense.gradients.iter_mut()) {
+        for (weight, gradient) in row.iter_mut().zip(gradients.iter_mut()) {
+            *weight += 0.5 * *gradient;
+            *gradient = 0.;
+        }
+    }
+    assert_eq!(embedding.embeddings, dense.embeddings);
+    assert_eq!(embedding.gradients, dense.gradients);
+    assert!(embedding.dirty_rows.is_empty());
+}
+
+#[test]
+fn test_reservoir_spectral_radius() {
+    // Eigenvalues of +-0.5i, which power iteration alone can't find, and a non-normal triangular matrix
+    let rotation = vec![vec![0., -0.5], vec![0.5, 0.]];
+    assert!((estimate_spectral_radius(&rotation) - 0.5).abs() < 1e-3);
+    let triangular = vec![vec![0.3, 5.], vec![0., -0.7]];
+    assert!((estimate_spectral_radius(&triangular) - 0.7).abs() < 1e-2);
+
+    let mut input_rng = XorShiftRng(0x9e37_79b9_7f4a_7c15);
+    let mut reservoir_rng = XorShiftRng(0x2545_f491_4f6c_dd1d);
+    let mut reservoir = ReservoirLayer::new(3, 50, 0.9, &mut |_, _| input_rng.gen_range(-1., 1.), &mut |_, _| {
+        reservoir_rng.gen_range(-1., 1.)
+    });
+    let radius = estimate_spectral_radius(&reservoir.reservoir_weights);
+    assert!((radius - 0.9).abs() < 0.02, "spectral radius was {}", radius);
+
+    reservoir.set_spectral_radius(0.5);
+    let radius = estimate_spectral_radius(&reservoir.reservoir_weights);
+    assert!((radius - 0.5).abs() < 0.01, "spectral radius was {}", radius);
+    assert_eq!(reservoir.spectral_radius, 0.5);
+
+    let input_weights = reservoir.input_weights.clone();
+    let states = reservoir.collect_states(&[vec![1., 0., -1.], vec![0.; 3], vec![0.; 3]]);
+    assert_eq!(states.len(), 3);
+    assert!(states.iter().flatten().all(|s| s.abs() < 1.));
+    assert_eq!(states.last().unwrap(), &reservoir.state);
+    assert_eq!(reservoir.input_weights, input_weights);
+    reservoir.reset_state();
+    assert!(reservoir.state.iter().all(|&s| s == 0.));
+}
+
+#[test]
+fn test_quantization_aware_training() {
+    let mut rng = XorShiftRng(0x1234_5678_9abc_def1);
+    let layer = DenseLayer::new(2, 3, &mut |_, _| rng.gen_range(-0.5, 0.5), &mut |_| 0., &IDENTITY);
+    let mut quantized = QuantizedDenseLayer::new(layer, QuantizationConfig::new(4, true));
+    let target = |x: &[Weight]| vec![0.8 * x[0] - 0.4 * x[1] + 0.1 * x[2] + 0.2, -0.6 * x[0] + 0.3 * x[2]];
+    let examples: Vec<Vec<Weight>> = (0..64)
+        .map(|_| (0..3).map(|_| rng.gen_range(-1., 1.)).collect())
+        .collect();
+
+    let mean_cost = |quantized: &mut QuantizedDenseLayer| {
+        let mut total = 0.;
+        for example in &examples {
+            quantized.forward_propagate(example);
+            total += quantized
+                .outputs()
+                .iter()
+                .zip(target(example))
+                .map(|(output, expected)| (expected - output).powi(2))
+                .sum::<Weight>();
+        }
+        total / examples.len() as Weight
+    };
+    let initial_cost = mean_cost(&mut quantized);
+    for _ in 0..200 {
+        for example in &examples {
+            quantized.forward_propagate(example);
+            let errors: Vec<Weight> = target(example)
+                .iter()
+                .zip(quantized.outputs())
+                .map(|(expected, output)| expected - output)
+                .collect();
+            quantized.layer.neuron_gradients.copy_from_slice(&errors);
+            quantized.update_weights(example, 0.01);
+            quantized.update_biases(0.01);
+        }
+    }
+    let final_cost = mean_cost(&mut quantized);
+    assert!(final_cost < initial_cost / 10., "{} -> {}", initial_cost, final_cost);
+    assert!(final_cost < 0.02, "final cost was {}", final_cost);
+
+    // At 4 bits each neuron's weights are one of 15 multiples of the neuron's scale
+    for (row, full_precision) in quantized
+        .layer
+        .weights
+        .iter()
+        .zip(quantized.full_precision_weights.iter())
+    {
+        let scale = full_precision.iter().fold(0., |acc: Weight, w| acc.max(w.abs())) / 7.;
+        for &weight in row {
+            let level = weight / scale;
+            assert!((level - level.round()).abs() < 1e-4 && level.abs() <= 7. + 1e-4);
+        }
+    }
+
+    let inference = quantized.to_quantized_inference_layer();
+    assert!(inference.weights.iter().flatten().all(|&w| (-7..=7).contains(&w)));
+    for example in &examples {
+        quantized.forward_propagate(example);
+        let outputs = inference.forward_propagate(example);
+        for (&fake_quantized, &integer) in quantized.outputs().iter().zip(outputs.iter()) {
+            assert!(
+                (fake_quantized - integer).abs() < 0.02,
+                "{} vs {}",
+                fake_quantized,
+                integer
+            );
+        }
+    }
+}
+
+#[test]
+fn test_network_to_dot() {
+    let network = Network::builder()
+        .input_size(4)
+        .hidden_layer(8, &RELU)
+        .hidden_layer(5, &TANH)
+        .output_size(2)
+        .output_activation(&SIGMOID)
+        .cost_function(&MeanSquaredError)
+        .build()
+        .unwrap();
+    let dot = network.to_dot();
+
+    assert!(dot.starts_with("digraph network {") && dot.trim_end().ends_with('}'));
+    let edges: Vec<&str> = dot.lines().filter(|line| line.contains("->")).collect();
+    let nodes = dot
+        .lines()
+        .filter(|line| line.contains("[label=") && !line.contains("->"))
+        .count();
+    assert_eq!(nodes, 4);
+    assert_eq!(edges, vec![
+        "    input -> hidden_0 [label=\"[4]\"];",
+        "    hidden_0 -> hidden_1 [label=\"[8]\"];",
+        "    hidden_1 -> output [label=\"[5]\"];",
+    ]);
+    assert!(dot.contains("Dense relu\\n8 units"));
+    assert!(dot.contains("Output sigmoid\\n2 units\\nmean_squared_error"));
+    assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+
+    let network = build_small_network(&mut pcg::Pcg::default());
+    assert_eq!(network.to_dot().matches("->").count(), 2);
+}
+
+#[test]
+fn test_ensemble_averages_members() {
+    let build = |seed: u64| {
+        let mut rng = XorShiftRng(seed | 1);
+        let mut init_weights = |_, _| rng.gen_range(-1., 1.);
+        Network {
+            hidden_layers: vec![DenseLayer::new(4, 2, &mut init_weights, &mut |_| 0.1, &TANH)],
+            outputs: Box::new(OutputLayer::new(&SIGMOID, &MeanSquaredError, &mut init_weights, 4, 3)),
+            learning_rate: 0.1,
+        }
+    };
+    let examples = vec![vec![0.2, -0.7], vec![1., 0.5], vec![-0.3, 0.]];
+
+    let mut ensemble = Ensemble::from_seeds(build, &[1, 2, 3]);
+    let predictions = ensemble.predict(&examples);
+    assert_eq!(predictions.len(), examples.len());
+    for (example, prediction) in examples.iter().zip(predictions.iter()) {
+        let mut expected = [0.; 3];
+        for seed in [1, 2, 3] {
+            for (sum, &output) in expected.iter_mut().zip(build(seed).compute(example)) {
+                *sum += output / 3.;
+            }
+        }
+        for (&actual, &expected) in prediction.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-6);
+        }
+    }
+
+    let mut identical = Ensemble::from_seeds(build, &[7, 7, 7, 7]);
+    let mut member = build(7);
+    for example in &examples {
+        let single = member.compute(example).to_owned();
+        let averaged = identical.compute(example);
+        for (&a, &b) in single.iter().zip(averaged.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_gradient_reversal_confuses_domain_classifier() {
+    let mut rng = XorShiftRng(0x5851_f42d_4c95_7f2d);
+    let mut init_weights = |_, _| rng.gen_range(-1., 1.);
+    let feature_extractor = Network {
+        hidden_layers: vec![DenseLayer::new(4, 2, &mut init_weights, &mut |_| 0., &TANH)],
+        outputs: Box::new(OutputLayer::new(&TANH, &MeanSquaredError, &mut init_weights, 4, 3)),
+        learning_rate: 0.05,
+    };
+    let mut domain_classifier = Network {
+        hidden_layers: vec![DenseLayer::new(4, 3, &mut init_weights, &mut |_| 0., &TANH)],
+        outputs: Box::new(OutputLayer::new(&SIGMOID, &MeanSquaredError, &mut init_weights, 4, 1)),
+        learning_rate: 0.05,
+    };
+    let (example, domain) = ([0.6, -0.4], [1.]);
+    let mut domain_cost = |feature_extractor: &mut Network| {
+        let features = feature_extractor.compute(&example).to_owned();
+        domain_classifier.compute_gradients(&features, &domain);
+        let cost = domain_classifier.mean_cost();
+        let mut feature_gradients = vec![0.; features.len()];
+        domain_classifier.compute_input_gradients(&mut feature_gradients);
+        (cost, feature_gradients)
+    };
+
+    let train_features = |feature_extractor: &mut Network, grl: Option<GradientReversalLayer>, gradients: &[Weight]| {
+        let mut gradients = gradients.to_owned();
+        if let Some(grl) = grl {
+            let output_gradients = gradients.clone();
+            grl.compute_gradients(&output_gradients, &mut gradients);
+        }
+        feature_extractor.forward_propagate(&example);
+        feature_extractor.backpropagate(&gradients);
+        feature_extractor.update_weights(&example, 0.05);
+    };
+
+    let (initial_cost, gradients) = domain_cost(&mut feature_extractor.clone());
+    let grl = GradientReversalLayer::new(1.);
+    assert_eq!(grl.forward_propagate(&[1., -2.]), vec![1., -2.]);
+
+    let mut reversed = feature_extractor.clone();
+    train_features(&mut reversed, Some(grl), &gradients);
+    let (reversed_cost, _) = domain_cost(&mut reversed);
+    assert!(reversed_cost > initial_cost, "{} -> {}", initial_cost, reversed_cost);
+
+    let mut plain = feature_extractor.clone();
+    train_features(&mut plain, None, &gradients);
+    let (plain_cost, _) = domain_cost(&mut plain);
+    assert!(plain_cost < initial_cost, "{} -> {}", initial_cost, plain_cost);
+
+    let mut dst = [0.; 2];
+    GradientReversalLayer::new(0.5).compute_gradients(&[2., -4.], &mut dst);
+    assert_eq!(dst, [-1., 2.]);
+}
+
+#[test]
+fn test_swish_and_gelu_derivatives() {
+    let epsilon = 1e-3;
+    for activation_fn in [&SWISH as &dyn ActivationFunction, &GELU, &GELU_TANH] {
+        for &x in &[-4., -1.5, -0.3, 0., 0.2, 1., 2.5, 5.] {
+            let numerical =
+                (activation_fn.get_output(x + epsilon) - activation_fn.get_output(x - epsilon)) / (2. * epsilon);
+            let analytic = activation_fn.derivative(x);
+            assert!(
+                (analytic - numerical).abs() < 2e-3,
+                "{} at {}: analytic {} numerical {}",
+                activation_fn.name(),
+                x,
+                analytic,
+                numerical
+            );
+        }
+    }
+
+    assert!((SWISH.get_output(1.) - 1. * sigmoid(1.)).abs() < 1e-6);
+    assert_eq!(SWISH.derivative(-200.), 0.);
+    assert!((GELU.get_output(1.) - 0.841_344_7).abs() < 1e-5);
+    assert!((GELU.get_output(-1.) + 0.158_655_3).abs() < 1e-5);
+    for &x in &[-3., -1., 0.5, 2.] {
+        assert!((GELU.get_output(x) - GELU_TANH.get_output(x)).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_swish_network_converges() {
+    let mut network = Network::builder()
+        .input_size(1)
+        .hidden_layer(4, &SWISH)
+        .output_size(1)
+        .weight_init(WeightInit::Uniform { min: -0.5, max: 0.5 })
+        .learning_rate(0.05)
+        .build()
+        .unwrap();
+
+    let mut rng = pcg::Pcg::default();
+    for _ in 0..5_000 {
+        let x = rng.gen_range(-1.0, 1.0);
+        network.train_one_example(&[x], &[0.5 * x], 0.05);
+    }
+    assert!((network.compute(&[0.5])[0] - 0.25).abs() < 0.05);
+}
+
+#[test]
+fn test_prelu_learns_alpha() {
+    let inputs = [-2., -0.5, 0.3, 1.5, -1.];
+    let expected: Vec<Weight> = inputs.iter().map(|&x| if x >= 0. { x } else { 0.25 * x }).collect();
+    let mut prelu = PRelu::new(0.);
+
+    let mut dst = [0.; 5];
+    let relu_outputs: Vec<Weight> = inputs.iter().map(|&x| RELU.get_output(x)).collect();
+    assert_eq!(prelu.forward_propagate(&inputs), relu_outputs);
+    let output_gradients = [0.3, -1., 2., 0.5, 0.7];
+    prelu.compute_gradients(&inputs, &output_gradients, &mut dst);
+    for ((&gradient, &output_gradient), &x) in dst.iter().zip(output_gradients.iter()).zip(inputs.iter()) {
+        assert_eq!(gradient, output_gradient * RELU.derivative(x));
+    }
+
+    for _ in 0..100 {
+        let outputs = prelu.forward_propagate(&inputs);
+        let errors: Vec<Weight> = expected.iter().zip(outputs.iter()).map(|(e, o)| e - o).collect();
+        let alpha_gradient = prelu.compute_gradients(&inputs, &errors, &mut dst);
+        prelu.update_alpha(0.1, alpha_gradient);
+    }
+    assert!((prelu.alpha - 0.25).abs() < 1e-3, "alpha was {}", prelu.alpha);
+}
+
+#[test]
+fn test_darts_activation_selects_best_candidate() {
+    let mut darts = DartsActivationLayer::new(vec![&RELU, &TANH, &SIGMOID, &IDENTITY]);
+    let inputs: Vec<Weight> = (0..20).map(|i| i as Weight / 5. - 2.).collect();
+    let mean_of_candidates: Vec<Weight> = inputs
+        .iter()
+        .map(|&x| (RELU.get_output(x) + x.tanh() + SIGMOID.get_output(x) + x) / 4.)
+        .collect();
+    for (&output, &expected) in darts.forward_propagate(&inputs).iter().zip(mean_of_candidates.iter()) {
+        assert!((output - expected).abs() < 1e-6);
+    }
+
+    // Check the gradients against finite differences of `sum(output_gradients * outputs)`
+    darts.alphas = vec![0.3, -0.2, 0.5, 0.1];
+    let output_gradients: Vec<Weight> = inputs.iter().map(|x| 0.5 - 0.2 * x).collect();
+    let objective = |darts: &DartsActivationLayer, inputs: &[Weight]| -> Weight {
+        darts
+            .forward_propagate(inputs)
+            .iter()
+            .zip(output_gradients.iter())
+            .map(|(output, gradient)| output * gradient)
+            .sum()
+    };
+    let mut input_gradients = vec![0.; inputs.len()];
+    let alpha_gradients = darts.compute_gradients(&inputs, &output_gradients, &mut input_gradients);
+    let epsilon = 1e-2;
+    for (ix, &alpha_gradient) in alpha_gradients.iter().enumerate() {
+        let mut shifted = DartsActivationLayer::new(darts.candidates.clone());
+        shifted.alphas = darts.alphas.clone();
+        shifted.alphas[ix] += epsilon;
+        let plus = objective(&shifted, &inputs);
+        shifted.alphas[ix] -= 2. * epsilon;
+        let minus = objective(&shifted, &inputs);
+        assert!((alpha_gradient - (plus - minus) / (2. * epsilon)).abs() < 1e-2);
+    }
+    let x = [0.7];
+    let mut dst = [0.];
+    darts.compute_gradients(&x, &[1.], &mut dst);
+    let numeric = (darts.forward_propagate(&[0.7 + 1e-2])[0] - darts.forward_propagate(&[0.7 - 1e-2])[0]) / 2e-2;
+    assert!((dst[0] - numeric).abs() < 1e-2);
+
+    darts.alphas = vec![0.; 4];
+    let expected: Vec<Weight> = inputs.iter().map(|x| x.tanh()).collect();
+    for _ in 0..500 {
+        let outputs = darts.forward_propagate(&inputs);
+        let errors: Vec<Weight> = expected.iter().zip(outputs.iter()).map(|(e, o)| e - o).collect();
+        let alpha_gradients = darts.compute_gradients(&inputs, &errors, &mut input_gradients);
+        darts.update_alphas(0.5, &alpha_gradients);
+    }
+    println!(
+        "alphas: {:?}, mixing weights: {:?}",
+        darts.alphas,
+        darts.mixing_weights()
+    );
+    assert_eq!(darts.discretize().name(), TANH.name());
+    assert!(darts.mixing_weights()[1] > 0.5);
+}
+
+#[test]
+fn test_maxout_approximates_relu_and_quadratic() {
+    let train = |target: &dyn Fn(Weight) -> Weight, k: usize| {
+        let mut rng = XorShiftRng(0x0123_4567_89ab_cdef);
+        let mut layer = MaxoutDenseLayer::new(1, 1, k, &mut |_, _| rng.gen_range(-1., 1.), &mut |_| 0.);
+        let xs: Vec<Weight> = (0..41).map(|i| i as Weight / 20. - 1.).collect();
+        for _ in 0..2_000 {
+            for &x in &xs {
+                layer.forward_propagate(&[x]);
+                layer.neuron_gradients[0] = target(x) - layer.outputs[0];
+                layer.update_weights(&[x], 0.05);
+                layer.update_biases(0.05);
+            }
+        }
+        let mean_squared_error = xs
+            .iter()
+            .map(|&x| {
+                layer.forward_propagate(&[x]);
+                (target(x) - layer.outputs[0]).powi(2)
+            })
+            .sum::<Weight>()
+            / xs.len() as Weight;
+        (layer, mean_squared_error)
+    };
+
+    let (_, relu_error) = train(&|x| x.max(0.), 2);
+    assert!(relu_error < 1e-4, "ReLU error was {}", relu_error);
+    let (mut layer, quadratic_error) = train(&|x| x * x, 4);
+    assert!(quadratic_error < 5e-3, "quadratic error was {}", quadratic_error);
+
+    // Gradients only reach the inputs through the piece that won
+    layer.forward_propagate(&[0.9]);
+    layer.compute_gradients(&[vec![2.]], &[0.5]);
+    let mut dst = [0.];
+    layer.compute_input_gradients(&mut dst);
+    assert_eq!(dst[0], layer.weights[layer.max_indices[0]][0]);
+}
+
+#[test]
+fn test_elu_and_selu() {
+    static HALF_ELU: Elu = Elu { alpha: 0.5 };
+    for elu in [&ELU, &HALF_ELU] {
+        for &x in &[-50., -3., -0.5, 0., 0.5, 3.] {
+            assert!(elu.get_output(x) >= -elu.alpha);
+        }
+        assert!((elu.get_output(1e-4) - elu.get_output(-1e-4)).abs() < 1e-3);
+        assert!((elu.get_output(-1.) - elu.alpha * ((-1. as Weight).exp() - 1.)).abs() < 1e-6);
+    }
+    // The slopes only meet at zero for the default alpha
+    assert!((ELU.derivative(1e-4) - ELU.derivative(-1e-4)).abs() < 1e-3);
+    assert_eq!(ELU.derivative(2.), 1.);
+    assert_eq!(HALF_ELU.name(), "elu(0.5)");
+
+    // Custom alphas survive saving and loading without having to be registered
+    let mut network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &HALF_ELU)
+        .output_size(1)
+        .output_activation(&ELU)
+        .build()
+        .unwrap();
+    let mut loaded = round_trip(&network);
+    assert_eq!(loaded.hidden_layers[0].activation_fn.name(), "elu(0.5)");
+    assert_eq!(loaded.outputs.activation_fn.name(), "elu");
+    assert_eq!(loaded.compute(&[-1.5, 0.5]), network.compute(&[-1.5, 0.5]));
+    assert!(ActivationFunctionRegistry::lookup("elu(0.5,1)").is_none());
+
+    assert!((SELU_ALPHA as f64 - 1.673_263_242_354_377_3).abs() < 1e-6);
+    assert!((SELU_SCALE as f64 - 1.050_700_987_355_480_5).abs() < 1e-6);
+    assert!((SELU.get_output(2.) - SELU_SCALE * 2.).abs() < 1e-6);
+    assert!((SELU.get_output(-1e3) + SELU_SCALE * SELU_ALPHA).abs() < 1e-5);
+}
+
+#[test]
+fn test_mean_absolute_error() {
+    assert_eq!(MEAN_ABSOLUTE_ERROR.get_cost(-1.5), 1.5);
+    assert_eq!(MEAN_ABSOLUTE_ERROR.get_cost(0.25), 0.25);
+    assert_eq!(MEAN_ABSOLUTE_ERROR.derivative(-1.5), -1.);
+    assert_eq!(MEAN_ABSOLUTE_ERROR.derivative(0.25), 1.);
+    assert_eq!(MEAN_ABSOLUTE_ERROR.derivative(0.), 0.);
+    assert_eq!(
+        CostFunctionRegistry::lookup("mean_absolute_error").map(|f| f.name()),
+        Some("mean_absolute_error")
+    );
+}
+
+#[test]
+fn test_mean_absolute_error_is_robust_to_outliers() {
+    let target = |x: Weight| 0.8 * x + 0.3;
+    let data: Vec<(Weight, Weight)> = (0..50)
+        .map(|i| {
+            let x = i as Weight / 25. - 1.;
+            let outlier = if i % 10 == 3 { 8. } else { 0. };
+            (x, target(x) + outlier)
+        })
+        .collect();
+
+    let fit = |cost_fn: &dyn CostFunction| {
+        let mut layer = DenseLayer::new(1, 1, &mut |_, _| 0., &mut |_| 0., &Identity);
+        for _ in 0..500 {
+            for &(x, y) in &data {
+                layer.forward_propagate(&[x]);
+                layer.compute_gradients(&[vec![1.]], &[cost_fn.derivative(y - layer.outputs[0])]);
+                layer.update_weights(&[x], 0.005);
+                layer.update_biases(0.005);
+            }
+        }
+        // Mean distance from the line the inliers lie on
+        (0..20)
+            .map(|i| {
+                let x = i as Weight / 10. - 1.;
+                layer.forward_propagate(&[x]);
+                (layer.outputs[0] - target(x)).abs()
+            })
+            .sum::<Weight>()
+            / 20.
+    };
+
+    let mae_error = fit(&MEAN_ABSOLUTE_ERROR);
+    let mse_error = fit(&MEAN_SQUARED_ERROR);
+    assert!(mae_error < 0.1, "MAE fit was off by {}", mae_error);
+    assert!(mae_error * 5. < mse_error, "MAE {} vs MSE {}", mae_error, mse_error);
+}
+
+#[test]
+fn test_hinge_loss_margin() {
+    let mut costs = [0.; 3];
+    let mut gradients = [0.; 3];
+    let outputs = [0.5, -2., 1.5];
+    let errors = [1. - 0.5, 1. + 2., -1. - 1.5];
+    HINGE_LOSS.compute_costs(&outputs, &errors, &mut costs);
+    HINGE_LOSS.compute_error_gradients(&outputs, &errors, &mut gradients);
+    assert_eq!(costs, [0.5, 3., 2.5]);
+    assert_eq!(gradients, [1., 1., -1.]);
+    // A lone output of 0 with a positive label is inside the margin
+    assert_eq!(HINGE_LOSS.get_cost(1.), 1.);
+    assert_eq!(HINGE_LOSS.derivative(1.), 1.);
+    // A label of 0 is the negative class, the same as -1
+    let outputs = [0.5, 0.5];
+    let errors = [0. - 0.5, -1. - 0.5];
+    let mut costs = [0.; 2];
+    let mut gradients = [0.; 2];
+    HINGE_LOSS.compute_costs(&outputs, &errors, &mut costs);
+    HINGE_LOSS.compute_error_gradients(&outputs, &errors, &mut gradients);
+    assert_eq!(costs, [1.5, 1.5]);
+    assert_eq!(gradients, [-1., -1.]);
+    assert_eq!(HINGE_LOSS.derivative(0.), -1.);
+
+    // Separable by the line x0 = x1, with the closest points 0.4 from it
+    let examples: Vec<Vec<Weight>> = vec![
+        vec![1., -1.],
+        vec![0.6, 0.],
+        vec![2., 1.],
+        vec![-1., 1.],
+        vec![0., 0.6],
+        vec![-0.5, 1.5],
+    ];
+    let labels = [1., 1., 1., -1., -1., -1.];
+    let mut output_layer = OutputLayer::new(&IDENTITY, &HINGE_LOSS, &mut |_, _| 0., 2, 1);
+    for _ in 0..500 {
+        for (example, &label) in examples.iter().zip(labels.iter()) {
+            output_layer.forward_propagate(example);
+            output_layer.compute_costs(&[label]);
+            output_layer.compute_gradients();
+            output_layer.update_weights(example, 0.05);
+        }
+    }
+    for (example, &label) in examples.iter().zip(labels.iter()) {
+        output_layer.forward_propagate(example);
+        assert!(
+            label * output_layer.outputs[0] >= 0.95,
+            "margin {}",
+            label * output_layer.outputs[0]
+        );
+    }
+    // Once every margin is reached, training stops moving the boundary
+    let weights = output_layer.weights.clone();
+    output_layer.forward_propagate(&examples[0]);
+    output_layer.compute_costs(&[labels[0]]);
+    if output_layer.costs[0] == 0. {
+        output_layer.compute_gradients();
+        output_layer.update_weights(&examples[0], 0.05);
+        assert_eq!(output_layer.weights, weights);
+    }
+}
+
+#[test]
+fn test_multiclass_hinge_loss() {
+    let outputs = [2., 1.5, -1.];
+    let errors = [-2., 1. - 1.5, 1.];
+    let mut costs = [0.; 3];
+    let mut gradients = [0.; 3];
+    MULTICLASS_HINGE_LOSS.compute_costs(&outputs, &errors, &mut costs);
+    MULTICLASS_HINGE_LOSS.compute_error_gradients(&outputs, &errors, &mut gradients);
+    assert_eq!(costs, [1.5, 0., 0.]);
+    assert_eq!(gradients, [-1., 1., 0.]);
+
+    let centers = [[1., 0.], [-1., 1.], [-1., -1.]];
+    let mut output_layer = OutputLayer::new(&IDENTITY, &MULTICLASS_HINGE_LOSS, &mut |_, _| 0., 2, 3);
+    for _ in 0..200 {
+        for (class_ix, center) in centers.iter().enumerate() {
+            let mut expected = [0.; 3];
+            expected[class_ix] = 1.;
+            output_layer.forward_propagate(center);
+            output_layer.compute_costs(&expected);
+            output_layer.compute_gradients();
+            output_layer.update_weights(center, 0.05);
+        }
+    }
+    for (class_ix, center) in centers.iter().enumerate() {
+        output_layer.forward_propagate(center);
+        assert_eq!(predicted_class(&output_layer.outputs), class_ix);
+        let mut expected = [0.; 3];
+        expected[class_ix] = 1.;
+        output_layer.compute_costs(&expected);
+        assert!(output_layer.costs.iter().all(|&cost| cost < 0.05));
+    }
+
+    // The layer's gradients come from the slice-based gradients over every output
+    output_layer.forward_propagate(&[0.2, 0.3]);
+    output_layer.compute_costs(&[0., 0., 1.]);
+    output_layer.compute_gradients();
+    let mut gradients = [0.; 3];
+    MULTICLASS_HINGE_LOSS.compute_error_gradients(&output_layer.outputs, &output_layer.errors, &mut gradients);
+    assert_eq!(output_layer.neuron_gradients, gradients);
+    assert!(gradients.iter().any(|&gradient| gradient != 0.));
+    assert!(MULTICLASS_HINGE_LOSS.get_cost(0.5).is_finite());
+}
+
+#[test]
+fn test_focal_loss() {
+    let cross_entropy = FocalLoss { alpha: 1., gamma: 0. };
+    let cases: [(Weight, Weight); 4] = [(1., 0.9), (1., 0.2), (0., 0.3), (0., 0.95)];
+    for &(target, p) in &cases {
+        let error = target - p;
+        let expected_cost = -(target * p.ln() + (1. - target) * (1. - p).ln());
+        assert!((cross_entropy.get_cost(error) - expected_cost).abs() < 1e-5);
+        // Negated derivative of cross-entropy with respect to p
+        let expected_gradient = target / p - (1. - target) / (1. - p);
+        assert!((cross_entropy.derivative(error) - expected_gradient).abs() < 1e-3);
+    }
+
+    let focal = FocalLoss { alpha: 0.25, gamma: 2. };
+    let epsilon = 1e-3;
+    for &(target, p) in &[(1., 0.6), (0., 0.7), (1., 0.1)] {
+        let numerical =
+            -(focal.get_cost(target - (p + epsilon)) - focal.get_cost(target - (p - epsilon))) / (2. * epsilon);
+        assert!((focal.derivative(target - p) - numerical).abs() < 1e-3);
+    }
+
+    // Confident, correct predictions barely move compared to cross-entropy
+    let confident_error = 1. - 0.99;
+    assert!(focal.derivative(confident_error).abs() < 1e-3);
+    assert!(focal.derivative(confident_error).abs() < cross_entropy.derivative(confident_error) * 1e-3);
+    assert_eq!(focal.derivative(0.), 0.);
+    assert!(focal.get_cost(1.).is_finite());
+}
+
+#[test]
+fn test_kl_divergence_loss() {
+    let kl_divergence = |target: &[Weight], outputs: &[Weight]| {
+        let errors: Vec<Weight> = target.iter().zip(outputs.iter()).map(|(t, o)| t - o).collect();
+        let mut costs = vec![0.; outputs.len()];
+        KL_DIVERGENCE_LOSS.compute_costs(outputs, &errors, &mut costs);
+        let mut gradients = vec![0.; outputs.len()];
+        KL_DIVERGENCE_LOSS.compute_error_gradients(outputs, &errors, &mut gradients);
+        (costs.iter().sum::<Weight>(), gradients)
+    };
+
+    let target = [0.7, 0.2, 0.1, 0.];
+    assert!(kl_divergence(&target, &target).0.abs() < 1e-6);
+    let (cost, gradients) = kl_divergence(&target, &[0.25, 0.25, 0.25, 0.25]);
+    assert!(cost > 0.);
+    assert!(kl_divergence(&target, &[0.5, 0.5, 0., 0.]).0.is_finite());
+    assert!(KL_DIVERGENCE_LOSS.get_cost(0.5).is_finite());
+    assert!(KL_DIVERGENCE_LOSS.derivative(0.5).is_finite());
+
+    let outputs = [0.25, 0.25, 0.25, 0.25];
+    let epsilon = 1e-3;
+    for (ix, &gradient) in gradients.iter().enumerate() {
+        let mut plus = outputs;
+        plus[ix] += epsilon;
+        let mut minus = outputs;
+        minus[ix] -= epsilon;
+        let numerical = -(kl_divergence(&target, &plus).0 - kl_divergence(&target, &minus).0) / (2. * epsilon);
+        assert!(
+            (gradient - numerical).abs() < 1e-2,
+            "{}: {} vs {}",
+            ix,
+            gradient,
+            numerical
+        );
+    }
+}
+
+#[test]
+fn test_contrastive_loss_separates_pairs() {
+    let loss = ContrastiveLoss::new(1.);
+    let (cost, first, second) = loss.compute(&[0., 0.], &[0.6, 0.8], true);
+    assert!((cost - 1.).abs() < 1e-6);
+    assert_eq!(first, vec![1.2, 1.6]);
+    assert_eq!(second, vec![-1.2, -1.6]);
+    let (cost, first, _) = loss.compute(&[0., 0.], &[0.3, 0.4], false);
+    assert!((cost - 0.25).abs() < 1e-6);
+    assert!(first[0] < 0. && first[1] < 0.);
+    assert_eq!(loss.compute(&[0., 0.], &[3., 4.], false).0, 0.);
+
+    let mut rng = XorShiftRng(0x6a09_e667_f3bc_c909);
+    let mut init_weights = |_, _| rng.gen_range(-1., 1.);
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new(6, 2, &mut init_weights, &mut |_| 0., &TANH)],
+        outputs: Box::new(OutputLayer::new(&IDENTITY, &MeanSquaredError, &mut init_weights, 6, 2)),
+        learning_rate: 0.02,
+    };
+    let class_a = [[0.2, 0.9], [0.4, 0.7], [0.1, 0.6]];
+    let class_b = [[0.5, 0.1], [0.8, 0.3], [0.6, -0.1]];
+    let mut pairs = Vec::new();
+    for (class, other) in [(&class_a, &class_b), (&class_b, &class_a)] {
+        for (ix, first) in class.iter().enumerate() {
+            pairs.push((first, &class[(ix + 1) % 3], true));
+            pairs.push((first, &other[ix], false));
+        }
+    }
+
+    let mean_distances = |network: &mut Network| {
+        let (mut similar, mut dissimilar) = (0., 0.);
+        for &(first, second, is_similar) in &pairs {
+            let first = network.compute(first).to_owned();
+            let distance = metric_learning::euclidean_distance(&first, network.compute(second));
+            if is_similar {
+                similar += distance / 6.;
+            } else {
+                dissimilar += distance / 6.;
+            }
+        }
+        (similar, dissimilar)
+    };
+    let (initial_similar, initial_dissimilar) = mean_distances(&mut network);
+
+    let mut output = PairOutputLayer::new(loss);
+    for _ in 0..2_000 {
+        for &(first, second, similar) in &pairs {
+            let first_embedding = network.compute(first).to_owned();
+            let second_embedding = network.compute(second).to_owned();
+            output.compute_costs(&first_embedding, &second_embedding, similar);
+
+            network.forward_propagate(first);
+            network.backpropagate(&output.first_gradients);
+            network.update_weights(first, 0.02);
+            network.forward_propagate(second);
+            network.backpropagate(&output.second_gradients);
+            network.update_weights(second, 0.02);
+        }
+    }
+    let (similar, dissimilar) = mean_distances(&mut network);
+    assert!(
+        similar < initial_similar * 0.2,
+        "similar {} -> {}",
+        initial_similar,
+        similar
+    );
+    assert!(
+        dissimilar > initial_dissimilar && dissimilar > 0.9,
+        "dissimilar {} -> {}",
+        initial_dissimilar,
+        dissimilar
+    );
+}
+
+#[test]
+fn test_triplet_loss_with_online_mining() {
+    let loss = TripletLoss::new(0.5);
+    let (cost, anchor, positive, negative) = loss.compute(&[0., 0.], &[1., 0.], &[0., 1.2]);
+    assert!((cost - 0.3).abs() < 1e-6);
+    assert_eq!(positive, vec![-1., 0.]);
+    assert_eq!(negative, vec![0., 1.]);
+    assert_eq!(anchor, vec![1., -1.]);
+    assert_eq!(loss.compute(&[0., 0.], &[0.1, 0.], &[2., 0.]).0, 0.);
+
+    let embeddings = vec![vec![0., 0.], vec![0.2, 0.], vec![1., 0.], vec![0.1, 0.1]];
+    let labels = [0, 0, 1, 1];
+    let triplets = online_triplet_mining(&embeddings, &labels);
+    assert_eq!(triplets, vec![(0, 1, 3), (1, 0, 3), (2, 3, 1), (3, 2, 0)]);
+
+    let mut rng = XorShiftRng(0xbb67_ae85_84ca_a73b);
+    let mut init_weights = |_, _| rng.gen_range(-1., 1.);
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new(6, 2, &mut init_weights, &mut |_| 0., &TANH)],
+        outputs: Box::new(OutputLayer::new(&IDENTITY, &MeanSquaredError, &mut init_weights, 6, 2)),
+        learning_rate: 0.02,
+    };
+    let examples = [
+        [0.2, 0.9],
+        [0.4, 0.7],
+        [0.1, 0.6],
+        [0.5, 0.1],
+        [0.8, 0.3],
+        [0.6, -0.1],
+        [-0.5, 0.],
+        [-0.7, 0.3],
+    ];
+    let labels = [0, 0, 0, 1, 1, 1, 2, 2];
+    let embed = |network: &mut Network| -> Vec<Vec<Weight>> {
+        examples
+            .iter()
+            .map(|example| network.compute(example).to_owned())
+            .collect()
+    };
+
+    for _ in 0..500 {
+        let embeddings = embed(&mut network);
+        for (anchor_ix, positive_ix, negative_ix) in online_triplet_mining(&embeddings, &labels) {
+            let (cost, anchor, positive, negative) = loss.compute(
+                &embeddings[anchor_ix],
+                &embeddings[positive_ix],
+                &embeddings[negative_ix],
+            );
+            if cost == 0. {
+                continue;
+            }
+            for (example_ix, gradients) in [(anchor_ix, anchor), (positive_ix, positive), (negative_ix, negative)] {
+                network.forward_propagate(&examples[example_ix]);
+                network.backpropagate(&gradients);
+                network.update_weights(&examples[example_ix], 0.02);
+            }
+        }
+    }
+
+    let embeddings = embed(&mut network);
+    for (anchor_ix, anchor) in embeddings.iter().enumerate() {
+        for (positive_ix, positive) in embeddings.iter().enumerate() {
+            if positive_ix == anchor_ix || labels[positive_ix] != labels[anchor_ix] {
+                continue;
+            }
+            for (negative_ix, negative) in embeddings.iter().enumerate() {
+                if labels[negative_ix] == labels[anchor_ix] {
+                    continue;
+                }
+                let positive_distance = metric_learning::euclidean_distance(anchor, positive);
+                let negative_distance = metric_learning::euclidean_distance(anchor, negative);
+                assert!(
+                    positive_distance + 0.45 <= negative_distance,
+                    "({}, {}, {}): {} vs {}",
+                    anchor_ix,
+                    positive_ix,
+                    negative_ix,
+                    positive_distance,
+                    negative_distance
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mutual_information_critic() {
+    let mut rng = XorShiftRng(0x3c6e_f372_fe94_f82b);
+    let states: Vec<Vec<Weight>> = (0..32).map(|_| vec![rng.gen_range(-1., 1.)]).collect();
+    let correlated: Vec<Vec<Weight>> = states.iter().map(|state| vec![state[0]]).collect();
+    let independent: Vec<Vec<Weight>> = (0..32).map(|_| vec![rng.gen_range(-1., 1.)]).collect();
+
+    let mut build_regularizer = || {
+        let mut init_weights = |_, _| rng.gen_range(-0.5, 0.5);
+        MutualInformationRegularizer::new(0.1, Network {
+            hidden_layers: vec![DenseLayer::new(8, 2, &mut init_weights, &mut |_| 0., &TANH)],
+            outputs: Box::new(OutputLayer::new(&IDENTITY, &MeanSquaredError, &mut init_weights, 8, 1)),
+            learning_rate: 0.05,
+        })
+    };
+    let mut regularizer = build_regularizer();
+    let mut baseline = build_regularizer();
+    for _ in 0..1_000 {
+        regularizer.train_critic(&states, &correlated, 0.5);
+        baseline.train_critic(&states, &independent, 0.5);
+    }
+
+    let mean_score = |regularizer: &mut MutualInformationRegularizer, outputs: &[Vec<Weight>], shift: usize| {
+        let count = states.len();
+        (0..count)
+            .map(|ix| {
+                regularizer
+                    .critic
+                    .compute(&[states[ix][0], outputs[(ix + shift) % count][0]])[0]
+            })
+            .sum::<Weight>()
+            / count as Weight
+    };
+    let joint_score = mean_score(&mut regularizer, &correlated, 0);
+    let shuffled_score = mean_score(&mut regularizer, &correlated, 7);
+    assert!(
+        joint_score > shuffled_score + 0.5,
+        "{} vs {}",
+        joint_score,
+        shuffled_score
+    );
+
+    let estimate = regularizer.estimate(&states, &correlated);
+    let baseline_estimate = baseline.estimate(&states, &independent);
+    assert!(estimate > 0.5, "estimate was {}", estimate);
+    assert!(
+        estimate > baseline_estimate + 0.3,
+        "{} vs {}",
+        estimate,
+        baseline_estimate
+    );
+
+    let state_gradients = regularizer.state_gradients(&states, &correlated);
+    assert_eq!(state_gradients.len(), states.len());
+    assert!(state_gradients
+        .iter()
+        .all(|gradients| gradients.len() == 1 && gradients[0].is_finite()));
+    regularizer.beta = 0.;
+    assert!(regularizer
+        .state_gradients(&states, &correlated)
+        .iter()
+        .flatten()
+        .all(|&gradient| gradient == 0.));
+}
+
+#[test]
+fn test_curriculum_data_loader() {
+    // Difficulty is the length of the sequence
+    let data: Vec<LabeledSequence> = [7, 2, 9, 4, 1, 6, 3, 10, 5, 8]
+        .iter()
+        .map(|&len| (vec![0.5; len], vec![len as Weight]))
+        .collect();
+    let schedule = CurriculumSchedule::Linear {
+        initial_fraction: 0.3,
+        full_at_epoch: 4,
+    };
+    let mut loader = CurriculumDataLoader::new(data, Box::new(|sequence| sequence.len() as f32), schedule);
+    assert_eq!(loader.len(), 10);
+
+    let mut first_epoch = Vec::new();
+    loop {
+        let batch = loader.next_batch(2);
+        if batch.is_empty() {
+            break;
+        }
+        first_epoch.extend(batch.into_iter().map(|(sequence, _)| sequence.len()));
+    }
+    assert_eq!(first_epoch, vec![1, 2, 3]);
+
+    let mut previous_count = loader.available().len();
+    for epoch in 1..=4 {
+        loader.set_epoch(epoch);
+        let count = loader.available().len();
+        assert!(count >= previous_count);
+        let max_difficulty = loader
+            .available()
+            .iter()
+            .map(|(sequence, _)| sequence.len())
+            .max()
+            .unwrap();
+        assert_eq!(max_difficulty, count);
+        previous_count = count;
+    }
+    assert_eq!(loader.available().len(), 10);
+    assert_eq!(loader.next_batch(100).len(), 10);
+
+    let root = CurriculumSchedule::Root {
+        initial_fraction: 0.2,
+        full_at_epoch: 4,
+    };
+    assert_eq!(root.fraction(0), 0.2);
+    assert!((root.fraction(1) - 0.6).abs() < 1e-6);
+    assert_eq!(root.fraction(10), 1.);
+    assert!(root.fraction(1) > schedule.fraction(1));
+}
+
+#[test]
+fn test_nce_loss() {
+    let noise_distribution = vec![0.4, 0.3, 0.15, 0.1, 0.05];
+    let logits: [Weight; 5] = [1., -0.5, 0.3, 2., 0.];
+    let log_total = log_sum_exp(&logits);
+    let log_probs: Vec<Weight> = logits.iter().map(|logit| logit - log_total).collect();
+    let target = 2;
+    let mut rng = XorShiftRng(0xa54f_f53a_5f1d_36f1);
+
+    let mean_gradients = |nce: &NceLoss, draws: usize, rng: &mut XorShiftRng| {
+        let mut mean = [0.; 5];
+        for _ in 0..draws {
+            let noise_words = nce.sample_noise(rng);
+            let (loss, gradients) = nce.compute(|word_ix| log_probs[word_ix], target, &noise_words);
+            assert!(loss.is_finite() && loss > 0.);
+            for (word_ix, gradient) in gradients {
+                mean[word_ix] += gradient / draws as Weight;
+            }
+        }
+        mean
+    };
+
+    // The sampled gradient averages out to its expectation over the noise distribution
+    let nce = NceLoss::new(3, noise_distribution.clone());
+    let sampled = mean_gradients(&nce, 20_000, &mut rng);
+    for (word_ix, &sampled) in sampled.iter().enumerate() {
+        let log_odds = log_probs[word_ix] - (3. * noise_distribution[word_ix]).ln();
+        let target_term = if word_ix == target { 1. - sigmoid(log_odds) } else { 0. };
+        let expected = target_term - 3. * noise_distribution[word_ix] * sigmoid(log_odds);
+        assert!(
+            (sampled - expected).abs() < 0.02,
+            "{}: {} vs {}",
+            word_ix,
+            sampled,
+            expected
+        );
+    }
+
+    // With more noise samples the gradient approaches that of softmax cross-entropy, `one_hot(target) - softmax`
+    let cross_entropy_gradients: Vec<Weight> = log_probs
+        .iter()
+        .enumerate()
+        .map(|(word_ix, log_prob)| if word_ix == target { 1. } else { 0. } - log_prob.exp())
+        .collect();
+    let mut errors = Vec::new();
+    for &(noise_samples, draws) in &[(1, 20_000), (10, 5_000), (1_000, 200)] {
+        let nce = NceLoss::new(noise_samples, noise_distribution.clone());
+        let sampled = mean_gradients(&nce, draws, &mut rng);
+        let error = sampled
+            .iter()
+            .zip(cross_entropy_gradients.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0., Weight::max);
+        errors.push(error);
+    }
+    assert!(errors[0] > errors[1] && errors[1] > errors[2], "{:?}", errors);
+    assert!(errors[2] < 0.02, "{:?}", errors);
+}
+
+#[test]
+fn test_beam_search() {
+    // Token 3 ends the sequence.  The most likely first token leads to a less likely sequence overall.
+    let transitions: [[Weight; 4]; 5] = [
+        [0.5, 0.4, 0.05, 0.05],
+        [0.3, 0.3, 0.2, 0.2],
+        [0.02, 0.03, 0.05, 0.9],
+        [0.1, 0.1, 0.1, 0.7],
+        [0.25, 0.25, 0.25, 0.25],
+    ];
+    let model = |tokens: &[usize]| -> Vec<Weight> {
+        let row = tokens.last().map(|&token| token + 1).unwrap_or(0);
+        transitions[row].iter().map(|p| p.ln()).collect()
+    };
+
+    let greedy = {
+        let mut tokens = Vec::new();
+        let mut score = 0.;
+        while tokens.len() < 5 && tokens.last() != Some(&3) {
+            let log_probs = model(&tokens);
+            let token = predicted_class(&log_probs);
+            score += log_probs[token];
+            tokens.push(token);
+        }
+        Hypothesis { tokens, score }
+    };
+    let decoded = BeamSearchDecoder::new(1, 5, 3).decode(model);
+    assert_eq!(decoded.tokens, greedy.tokens);
+    assert!((decoded.score - greedy.score).abs() < 1e-5);
+
+    let decoded = BeamSearchDecoder::new(2, 5, 3).decode(model);
+    assert_eq!(decoded.tokens, vec![1, 3]);
+    assert!((decoded.score - (0.4 as Weight * 0.9).ln()).abs() < 1e-5);
+    assert!(decoded.score > greedy.score);
+
+    let unfinished = BeamSearchDecoder::new(2, 1, 3).decode(model);
+    assert_eq!(unfinished.tokens, vec![0]);
+}
+
+#[test]
+fn test_temperature_scaling() {
+    let logits = [2., 0.5, -1.];
+    let base = softmax_with_temperature(&logits, 1.);
+    let flat = softmax_with_temperature(&logits, 3.);
+    let sharp = softmax_with_temperature(&logits, 0.3);
+    assert!((flat.iter().sum::<Weight>() - 1.).abs() < 1e-5);
+    assert!(flat[0] < base[0] && base[0] < sharp[0]);
+    assert!(flat[2] > base[2] && base[2] > sharp[2]);
+
+    // The true log odds are the inputs, but the network multiplies them by 4 and so is overconfident
+    let identity = |i: usize, j: usize| if i == j { 1. } else { 0. };
+    let mut network = Network {
+        hidden_layers: vec![DenseLayer::new(
+            3,
+            3,
+            &mut |i, j| identity(i, j),
+            &mut |_| 0.,
+            &IDENTITY,
+        )],
+        outputs: Box::new(OutputLayer::new(
+            &IDENTITY,
+            &MeanSquaredError,
+            &mut |i, j| 4. * identity(i, j),
+            3,
+            3,
+        )),
+        learning_rate: 0.1,
+    };
+    let mut rng = XorShiftRng(0x510e_527f_ade6_82d1);
+    let mut examples = Vec::new();
+    let mut expected = Vec::new();
+    let mut labels = Vec::new();
+    for _ in 0..2_000 {
+        let example: Vec<Weight> = (0..3).map(|_| rng.gen_range(-1.5, 1.5)).collect();
+        let probabilities = softmax_with_temperature(&example, 1.);
+        let sample = rng.gen::<Weight>();
+        let mut cumulative = 0.;
+        let label = probabilities
+            .iter()
+            .position(|p| {
+                cumulative += p;
+                sample < cumulative
+            })
+            .unwrap_or(2);
+        let mut one_hot = vec![0.; 3];
+        one_hot[label] = 1.;
+        examples.push(example);
+        expected.push(one_hot);
+        labels.push(label);
+    }
+
+    let temperature = network.calibrate_temperature(&examples, &expected);
+    assert!(
+        (temperature - 4.).abs() < 0.8,
+        "calibrated temperature was {}",
+        temperature
+    );
+
+    let mut calibration_error = |temperature: f32| {
+        let probabilities: Vec<Vec<Weight>> = examples
+            .iter()
+            .map(|example| network.predict_with_temperature(example, temperature))
+            .collect();
+        expected_calibration_error(&probabilities, &labels, 10)
+    };
+    let uncalibrated = calibration_error(1.);
+    let calibrated = calibration_error(temperature);
+    assert!(calibrated < uncalibrated, "{} vs {}", calibrated, uncalibrated);
+}
+
+#[test]
+fn test_activation_stats() {
+    let weights = [1., -1., -2., 3.];
+    let mut layer = DenseLayer::new(4, 1, &mut |neuron_ix, _| weights[neuron_ix], &mut |_| 0., &RELU);
+    layer.forward_propagate(&[1.]);
+    let stats = layer.compute_activation_stats();
+    assert_eq!(stats.mean, 1.);
+    assert!((stats.std - (1.5 as Weight).sqrt()).abs() < 1e-6);
+    assert_eq!(stats.fraction_dead, 0.5);
+    assert_eq!(stats.fraction_saturated, 0.5);
+
+    // Flipping the sign of the input kills the other half
+    layer.forward_propagate(&[-1.]);
+    let stats = layer.compute_activation_stats();
+    assert_eq!(stats.mean, 0.75);
+    assert_eq!(stats.fraction_dead, 0.5);
+    assert_eq!((layer.outputs[0], layer.outputs[3]), (0., 0.));
+
+    let mut layer = DenseLayer::new(2, 1, &mut |neuron_ix, _| [10., 0.][neuron_ix], &mut |_| 0., &SIGMOID);
+    layer.forward_propagate(&[1.]);
+    let stats = layer.compute_activation_stats();
+    assert_eq!(stats.fraction_dead, 0.);
+    assert_eq!(stats.fraction_saturated, 0.5);
+}
+
+/// Splits a protobuf message into its field numbers and raw payloads.  Varints are returned as little endian `u64`s.
+fn read_proto_fields(mut buf: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    fn read_varint(buf: &mut &[u8]) -> u64 {
+        let mut val = 0;
+        let mut shift = 0;
+        loop {
+            let byte = buf[0];
+            *buf = &buf[1..];
+            val |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return val;
+            }
+            shift += 7;
+        }
+    }
+
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf);
+        let len = match key & 7 {
+            0 => {
+                fields.push((key >> 3, read_varint(&mut buf).to_le_bytes().to_vec()));
+                continue;
+            },
+            1 => 8,
+            2 => read_varint(&mut buf) as usize,
+            5 => 4,
+            wire_type => panic!("Unexpected wire type {}", wire_type),
+        };
+        fields.push((key >> 3, buf[..len].to_vec()));
+        buf = &buf[len..];
+    }
+    fields
+}
+
+#[test]
+fn test_tensorboard_logger() {
+    assert_eq!(tensorboard::crc32c(b"123456789"), 0xe306_9283);
+
+    let log_dir = std::env::temp_dir().join(format!("libnn_tensorboard_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&log_dir);
+    let weights = [[0.5, -1.], [2., 0.25], [0., 1.5]];
+    let layer = DenseLayer::new(
+        3,
+        2,
+        &mut |neuron_ix, input_ix| weights[neuron_ix][input_ix],
+        &mut |ix| ix as Weight,
+        &RELU,
+    );
+    let path = {
+        let mut logger = TensorBoardLogger::new(&log_dir).unwrap();
+        logger.log_scalar(3, "loss", 0.75).unwrap();
+        logger.log_weights(4, "dense", &layer).unwrap();
+        logger.path.clone()
+    };
+    assert!(path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("events.out.tfevents."));
+
+    let bytes = std::fs::read(&path).unwrap();
+    let mut records = Vec::new();
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        let (length, tail) = rest.split_at(8);
+        let (length_crc, tail) = tail.split_at(4);
+        assert_eq!(
+            u32::from_le_bytes(length_crc.try_into().unwrap()),
+            tensorboard::masked_crc32c(length)
+        );
+        let length = u64::from_le_bytes(length.try_into().unwrap()) as usize;
+        let (data, tail) = tail.split_at(length);
+        let (data_crc, tail) = tail.split_at(4);
+        assert_eq!(
+            u32::from_le_bytes(data_crc.try_into().unwrap()),
+            tensorboard::masked_crc32c(data)
+        );
+        records.push(read_proto_fields(data));
+        rest = tail;
+    }
+    assert_eq!(records.len(), 3);
+
+    let field = |fields: &[(u64, Vec<u8>)], number: u64| -> Vec<u8> {
+        fields
+            .iter()
+            .find(|(field_number, _)| *field_number == number)
+            .unwrap()
+            .1
+            .clone()
+    };
+    assert_eq!(field(&records[0], 3), b"brain.Event:2");
+
+    assert_eq!(field(&records[1], 2)[0], 3);
+    let scalar = read_proto_fields(&field(&read_proto_fields(&field(&records[1], 5)), 1));
+    assert_eq!(field(&scalar, 1), b"loss");
+    assert_eq!(f32::from_le_bytes(field(&scalar, 2).try_into().unwrap()), 0.75);
+
+    assert_eq!(field(&records[2], 2)[0], 4);
+    let values: Vec<Vec<(u64, Vec<u8>)>> = read_proto_fields(&field(&records[2], 5))
+        .iter()
+        .map(|(_, value)| read_proto_fields(value))
+        .collect();
+    assert_eq!(values.len(), 2);
+    assert_eq!(field(&values[0], 1), b"dense/weights");
+    assert_eq!(field(&values[1], 1), b"dense/biases");
+    let histogram = read_proto_fields(&field(&values[0], 5));
+    let double = |number: u64| f64::from_le_bytes(field(&histogram, number).try_into().unwrap());
+    assert_eq!((double(1), double(2), double(3), double(4)), (-1., 2., 6., 3.25));
+    let doubles = |number: u64| -> Vec<f64> {
+        field(&histogram, number)
+            .chunks(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    };
+    let bucket_limits = doubles(6);
+    let buckets = doubles(7);
+    assert_eq!(bucket_limits.len(), buckets.len());
+    assert_eq!(*bucket_limits.last().unwrap(), 2.);
+    assert_eq!(buckets.iter().sum::<f64>(), 6.);
+    assert_eq!(buckets[0], 1.);
+
+    std::fs::remove_dir_all(&log_dir).unwrap();
+}
+
+#[test]
+fn test_integrated_gradients_completeness() {
+    let mut network = Network::builder()
+        .input_size(3)
+        .hidden_layer(5, &TANH)
+        .output_size(2)
+        .output_activation(&SIGMOID)
+        .weight_init(WeightInit::Uniform { min: -1.5, max: 1.5 })
+        .build()
+        .unwrap();
+    let inputs = [0.8, -0.5, 1.2];
+    let baseline = [0., -0.5, -0.3];
+
+    for output_ix in 0..2 {
+        let attributions = InputAttribution::shap_values(&mut network, &inputs, &baseline, output_ix, 200);
+        assert_eq!(attributions.len(), 3);
+        // Inputs that match the baseline get no credit
+        assert_eq!(attributions[1], 0.);
+
+        let difference = network.compute(&inputs)[output_ix] - network.compute(&baseline)[output_ix];
+        let total: Weight = attributions.iter().sum();
+        assert!((total - difference).abs() < 1e-4, "{} vs {}", total, difference);
+    }
+}
+
+#[test]
+fn test_spectral_normalization() {
+    // W^T W has eigenvalues 7 +- sqrt(13)
+    let weights = vec![vec![3., 1.], vec![0., 2.]];
+    let mut left_vector = vec![1., 0.];
+    let mut sigma = 0.;
+    for _ in 0..50 {
+        sigma = spectral_norm_step(&weights, &mut left_vector);
+    }
+    assert!((sigma as f64 - (7. + 13f64.sqrt()).sqrt()).abs() < 1e-4, "{}", sigma);
+
+    let spectral_norm = |weights: &[Vec<Weight>]| {
+        let mut left_vector = vec![1.; weights.len()];
+        (0..200)
+            .map(|_| spectral_norm_step(weights, &mut left_vector))
+            .last()
+            .unwrap()
+    };
+    let mut init = XorShiftRng(0x243f_6a88_85a3_08d3);
+    let mut layer = DenseLayer::new(4, 3, &mut |_, _| init.gen_range(-2., 2.), &mut |_| 0., &TANH);
+    assert!(spectral_norm(&layer.weights) > 1.5);
+
+    let normalized_spectral_norm = |layer: &DenseLayer| spectral_norm(&layer.weights) * layer.spectral_norm_scale();
+    let raw_weights = layer.weights.clone();
+    layer.enable_spectral_normalization();
+    layer.forward_propagate(&[0.5, -1., 0.25]);
+    assert!(normalized_spectral_norm(&layer) <= 1. + 1e-3);
+    assert_eq!(layer.weights, raw_weights);
+    let outputs = layer.outputs.clone();
+    for (neuron_ix, row) in raw_weights.iter().enumerate() {
+        let weight_sum: Weight = row
+            .iter()
+            .zip([0.5, -1., 0.25])
+            .map(|(weight, input)| weight * input)
+            .sum();
+        assert!((outputs[neuron_ix] - (weight_sum * layer.spectral_norm_scale()).tanh()).abs() < 1e-3);
+    }
+
+    // The estimate keeps up as training moves the weights
+    for step in 0..20 {
+        for (neuron_ix, row) in layer.weights.iter_mut().enumerate() {
+            row[step % 3] += 0.05 * (neuron_ix as Weight - 1.5);
+        }
+        layer.after_weight_update();
+        assert!(normalized_spectral_norm(&layer) <= 1. + 1e-2);
+    }
+
+    // Running examples through a network leaves the weights alone, and training still lowers the cost
+    let mut network = Network::builder()
+        .input_size(3)
+        .hidden_layer(4, &TANH)
+        .output_size(1)
+        .build()
+        .unwrap();
+    network.hidden_layers[0].weights = raw_weights.clone();
+    network.hidden_layers[0].enable_spectral_normalization();
+    let spectral_norm_state = network.hidden_layers[0].spectral_norm.clone();
+    let first = network.compute(&[0.5, -1., 0.25]).to_owned();
+    let second = network.compute(&[0.5, -1., 0.25]).to_owned();
+    assert_eq!(first, second);
+    let untrained = network.evaluate(&[vec![0.5, -1., 0.25]], &[vec![0.8]]).total_loss;
+    assert_eq!(network.hidden_layers[0].weights, raw_weights);
+    assert_eq!(network.hidden_layers[0].spectral_norm, spectral_norm_state);
+
+    // Training through a `Trainer` takes the same steps as training the network directly
+    network.learning_rate = 0.1;
+    let mut trainer = Trainer::new(network.clone());
+    for _ in 0..100 {
+        network.train_one_example(&[0.5, -1., 0.25], &[0.8], 0.1);
+        trainer.train_one_example(&[0.5, -1., 0.25], &[0.8], 0.1);
+    }
+    assert!(network.hidden_layers[0].spectral_norm != spectral_norm_state);
+    for (direct, trained) in all_weights(&network).iter().zip(all_weights(&trainer.network).iter()) {
+        assert!((direct - trained).abs() < 1e-4, "{} vs {}", direct, trained);
+    }
+    let trained = network.evaluate(&[vec![0.5, -1., 0.25]], &[vec![0.8]]).total_loss;
+    assert!(trained < untrained * 0.1, "{} vs {}", trained, untrained);
+
+    layer.disable_spectral_normalization();
+    assert_eq!(layer.apply_spectral_normalization(), None);
+}
+
+#[test]
+fn test_orthogonal_regularizer() {
+    let regularizer = OrthogonalRegularizer::new(0.5);
+    let rotation = vec![vec![0.6, -0.8], vec![0.8, 0.6]];
+    assert!(regularizer.penalty(&rotation).abs() < 1e-6);
+
+    let mut weights = vec![vec![0.9, -0.3, 0.2], vec![0.4, 1.1, -0.5]];
+    let gradients = regularizer.gradients(&weights);
+    let epsilon = 1e-3;
+    for row_ix in 0..2 {
+        for col_ix in 0..3 {
+            let original = weights[row_ix][col_ix];
+            weights[row_ix][col_ix] = original + epsilon;
+            let penalty_up = regularizer.penalty(&weights);
+            weights[row_ix][col_ix] = original - epsilon;
+            let penalty_down = regularizer.penalty(&weights);
+            weights[row_ix][col_ix] = original;
+            let numerical = -(penalty_up - penalty_down) / (2. * epsilon);
+            assert!((numerical - gradients[row_ix][col_ix]).abs() < 1e-2);
+        }
+    }
+
+    let examples = [[0., 0., 1., 0.], [1., 0., 0., 1.], [0., 1., 1., 1.], [1., 1., 0., 0.]];
+    let expected = [[0.], [1.], [1.], [0.]];
+    let train = |regularizer: Option<OrthogonalRegularizer>| {
+        let mut network = Network::builder()
+            .input_size(4)
+            .hidden_layer(4, &TANH)
+            .output_size(1)
+            .learning_rate(0.05)
+            .build()
+            .unwrap();
+        for _ in 0..300 {
+            for (example, expected) in examples.iter().zip(expected.iter()) {
+                network.train_one_example(example, expected, 0.05);
+                if let Some(regularizer) = regularizer {
+                    regularizer.apply(&mut network.hidden_layers[0], 0.05);
+                }
+            }
+        }
+        OrthogonalRegularizer::new(1.).penalty(&network.hidden_layers[0].weights)
+    };
+    let unregularized = train(None);
+    let regularized = train(Some(OrthogonalRegularizer::new(0.1)));
+    assert!(
+        regularized < 0.2 * unregularized,
+        "{} vs {}",
+        regularized,
+        unregularized
+    );
+}
+
+#[test]
+fn test_gradient_penalty() {
+    let build = || {
+        Network::builder()
+            .input_size(2)
+            .hidden_layer(4, &TANH)
+            .hidden_layer(3, &STABLE_SIGMOID)
+            .output_size(1)
+            .learning_rate(0.05)
+            .build()
+            .unwrap()
+    };
+    let inputs = [0.7, -0.4];
+    // Spectral normalization changes the weights the penalty sees, with the spectral norm held fixed
+    for (penalty_type, spectral_norm) in [
+        (PenaltyType::L1, false),
+        (PenaltyType::L2, false),
+        (PenaltyType::LInf, false),
+        (PenaltyType::L2, true),
+    ] {
+        let penalty = GradientPenalty::new(0.5, penalty_type);
+        let mut network = build();
+        if spectral_norm {
+            network.hidden_layers[0].enable_spectral_normalization();
+            assert!(network.hidden_layers[0].spectral_norm_scale() < 1.);
+        }
+        network.forward_propagate(&inputs);
+        let (value, weight_gradients, bias_gradients) = penalty.penalty_gradients(&network, &inputs);
+        assert!((value - penalty.compute(&mut network, &inputs)).abs() < 1e-6);
+
+        let epsilon = 1e-2;
+        let numerical = |network: &mut Network, set: &dyn Fn(&mut Network, Weight)| {
+            set(network, epsilon);
+            let up = penalty.compute(network, &inputs);
+            set(network, -2. * epsilon);
+            let down = penalty.compute(network, &inputs);
+            set(network, epsilon);
+            (up - down) / (2. * epsilon)
+        };
+        let hidden = numerical(&mut network, &|network, delta| {
+            network.hidden_layers[0].weights[2][1] += delta
+        });
+        assert!(
+            (hidden - weight_gradients[0][2][1]).abs() < 2e-3,
+            "{:?}: {} vs {}",
+            penalty_type,
+            hidden,
+            weight_gradients[0][2][1]
+        );
+        let bias = numerical(&mut network, &|network, delta| {
+            network.hidden_layers[1].biases[1] += delta
+        });
+        assert!(
+            (bias - bias_gradients[1][1]).abs() < 2e-3,
+            "{:?}: {} vs {}",
+            penalty_type,
+            bias,
+            bias_gradients[1][1]
+        );
+        let output = numerical(&mut network, &|network, delta| network.outputs.weights[0][2] += delta);
+        assert!(
+            (output - weight_gradients[2][0][2]).abs() < 2e-3,
+            "{:?}: {} vs {}",
+            penalty_type,
+            output,
+            weight_gradients[2][0][2]
+        );
+    }
+
+    // Fit a step, which pulls the network towards steep slopes, and measure the steepest slope along the way
+    let examples: Vec<[Weight; 2]> = (0..20).map(|ix| [ix as Weight / 10. - 1., 0.]).collect();
+    let train = |penalty: Option<GradientPenalty>| {
+        let mut network = build();
+        for _ in 0..300 {
+            for example in &examples {
+                let expected = if example[0] > 0. { 1. } else { -1. };
+                network.train_one_example(example, &[expected], 0.05);
+                if let Some(penalty) = penalty {
+                    penalty.apply(&mut network, example, 0.05);
+                }
+            }
+        }
+        examples
+            .iter()
+            .map(|example| GradientPenalty::input_gradients(&mut network, example)[0].abs())
+            .fold(0., Weight::max)
+    };
+    let unpenalized = train(None);
+    let penalized = train(Some(GradientPenalty::new(1., PenaltyType::L2)));
+    assert!(penalized < 0.5 * unpenalized, "{} vs {}", penalized, unpenalized);
+}
+
+#[test]
+fn test_parameterized_costs_round_trip_through_save_and_load() {
+    static FOCAL_CROSS_ENTROPY: FocalLoss = FocalLoss { alpha: 1., gamma: 0. };
+    static SOFT_NDCG_LOSS: NdcgLoss = NdcgLoss { temperature: 0.3 };
+    let mut network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &TANH)
+        .output_size(2)
+        .build()
+        .unwrap();
+    let examples = vec![vec![0.3, -0.8]];
+    let expected = vec![vec![1., 0.]];
+    for cost_fn in [
+        &FOCAL_LOSS as &'static dyn CostFunction,
+        &FOCAL_CROSS_ENTROPY,
+        &NDCG_LOSS,
+        &SOFT_NDCG_LOSS,
+    ] {
+        network.outputs.cost_fn = cost_fn;
+        let mut loaded = round_trip(&network);
+        assert_eq!(loaded.outputs.cost_fn.name(), cost_fn.name());
+        assert_eq!(
+            loaded.evaluate(&examples, &expected),
+            network.evaluate(&examples, &expected)
+        );
+    }
+    assert_eq!(FOCAL_LOSS.name(), "focal");
+    assert_eq!(FOCAL_CROSS_ENTROPY.name(), "focal(1,0)");
+    assert_eq!(SOFT_NDCG_LOSS.name(), "ndcg(0.3)");
+    assert!(CostFunctionRegistry::lookup("ndcg(0.3,1)").is_none());
+    assert!(CostFunctionRegistry::lookup("focal(one,0)").is_none());
+}
+
+#[test]
+fn test_ranking_losses() {
+    static SHARP_NDCG_LOSS: NdcgLoss = NdcgLoss { temperature: 0.5 };
+    assert_eq!(ndcg(&[3., 2., 1.], &[2., 1., 0.]), 1.);
+    assert!(ndcg(&[1., 2., 3.], &[2., 1., 0.]) < 0.7);
+
+    let outputs = [0.3, -0.2, 1.1, 0.6];
+    let relevance = [2., 0., 1., 3.];
+    let total_cost = |cost_fn: &dyn CostFunction, outputs: &[Weight]| {
+        let errors: Vec<Weight> = relevance.iter().zip(outputs.iter()).map(|(r, o)| r - o).collect();
+        let mut costs = [0.; 4];
+        cost_fn.compute_costs(outputs, &errors, &mut costs);
+        costs.iter().sum::<Weight>()
+    };
+    assert!(
+        (total_cost(&SHARP_NDCG_LOSS, &[0., -1., 1., 2.]) - (1. - ndcg(&[0., -1., 1., 2.], &relevance))).abs() < 0.1
+    );
+    for cost_fn in [&LISTNET_LOSS as &dyn CostFunction, &SHARP_NDCG_LOSS] {
+        let errors: Vec<Weight> = relevance.iter().zip(outputs.iter()).map(|(r, o)| r - o).collect();
+        let mut gradients = [0.; 4];
+        cost_fn.compute_error_gradients(&outputs, &errors, &mut gradients);
+        for ix in 0..4 {
+            let epsilon = 1e-2;
+            let mut shifted = outputs;
+            shifted[ix] += epsilon;
+            let up = total_cost(cost_fn, &shifted);
+            shifted[ix] -= 2. * epsilon;
+            let down = total_cost(cost_fn, &shifted);
+            // `relevance` is fixed here, whereas shifting an output alone would also shift the recovered target
+            let numerical = -(up - down) / (2. * epsilon);
+            assert!(
+                (numerical - gradients[ix]).abs() < 1e-3,
+                "{}: {} vs {}",
+                ix,
+                numerical,
+                gradients[ix]
+            );
+        }
+    }
+
+    // Each list is 4 items with one feature each, and the relevance grade only depends on the feature
+    let mut rng = XorShiftRng(0x1319_8a2e_0370_7344);
+    let lists: Vec<(Vec<Weight>, Vec<Weight>)> = (0..60)
+        .map(|_| {
+            let features: Vec<Weight> = (0..4).map(|_| rng.gen_range(-1., 1.)).collect();
+            let relevance = features
+                .iter()
+                .map(|&feature| (1.5 - 2. * feature).round().clamp(0., 3.))
+                .collect();
+            (features, relevance)
+        })
+        .collect();
+    let (train, test) = lists.split_at(40);
+    for cost_fn in [&LISTNET_LOSS as &'static dyn CostFunction, &SHARP_NDCG_LOSS] {
+        let mut network = Network::builder()
+            .input_size(4)
+            .hidden_layer(8, &TANH)
+            .output_size(4)
+            .cost_function(cost_fn)
+            .learning_rate(0.05)
+            .build()
+            .unwrap();
+        let mean_ndcg = |network: &mut Network| {
+            test.iter()
+                .map(|(features, relevance)| ndcg(network.compute(features), relevance))
+                .sum::<Weight>()
+                / test.len() as Weight
+        };
+        let before = mean_ndcg(&mut network);
+        for _ in 0..200 {
+            for (features, relevance) in train {
+                network.train_one_example(features, relevance, 0.05);
+            }
+        }
+        let after = mean_ndcg(&mut network);
+        assert!(
+            after > before + 0.1 && after > 0.9,
+            "{}: {} -> {}",
+            cost_fn.name(),
+            before,
+            after
+        );
+    }
+}
+
+#[test]
+fn test_pruned_weights_stay_zero_through_training() {
+    let mut network = Network::builder()
+        .input_size(3)
+        .hidden_layer(6, &TANH)
+        .output_size(2)
+        .learning_rate(0.05)
+        .build()
+        .unwrap();
+    let inputs = [0.4, -0.7, 0.9];
+    let outputs = network.compute(&inputs).to_owned();
+    let unpruned = network.hidden_layers[0].weights.clone();
+    assert_eq!(network.prune_by_magnitude(0.), PruneReport {
+        per_layer: vec![0],
+        total: 0
+    });
+    assert_eq!(network.compute(&inputs), outputs.as_slice());
+    assert_eq!(network.hidden_layers[0].weights, unpruned);
+
+    let threshold = 0.5;
+    let below_threshold = unpruned
+        .iter()
+        .flatten()
+        .filter(|weight| weight.abs() < threshold)
+        .count();
+    assert!(below_threshold > 0 && below_threshold < 18);
+    assert_eq!(
+        network.hidden_layers[0].prune_by_magnitude(threshold).total,
+        below_threshold
+    );
+    let pruned: Vec<bool> = unpruned
+        .iter()
+        .flatten()
+        .map(|weight| weight.abs() < threshold)
+        .collect();
+    let is_pruned = |network: &Network| -> Vec<bool> {
+        network.hidden_layers[0]
+            .weights
+            .iter()
+            .flatten()
+            .map(|&weight| weight == 0.)
+            .collect()
+    };
+    assert_eq!(is_pruned(&network), pruned);
+    let mask: Vec<bool> = network.hidden_layers[0]
+        .prune_mask
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|&kept| !kept)
+        .collect();
+    assert_eq!(mask, pruned);
+
+    let mut optimizer = NesterovSgd::new(0.9);
+    for step in 0..50 {
+        network.train_one_example(&inputs, &[0.5, -0.5], 0.05);
+        network.compute_gradients(&inputs, &[0.5, -0.5]);
+        network.hidden_layers[0].update_weights_with_optimizer(&inputs, 0.05, &mut optimizer);
+        assert_eq!(is_pruned(&network), pruned, "step {}", step);
+    }
+    assert!(network.compute(&inputs) != outputs.as_slice());
+
+    // Accumulated gradients are applied by the trainer rather than the layer, and still respect the mask
+    let mut trainer = Trainer::new(network.clone());
+    trainer.set_accumulation_steps(3);
+    for step in 0..12 {
+        trainer.train_one_example(&inputs, &[0.5, -0.5], 0.05);
+        assert_eq!(is_pruned(&trainer.network), pruned, "step {}", step);
+    }
+
+    // Weights already pruned aren't counted again
+    assert_eq!(network.prune_by_magnitude(threshold).per_layer, vec![0]);
+
+    // Without the mask applied during updates, pruned weights start at zero but train back
+    network.hidden_layers[0].apply_mask_during_update = false;
+    network.train_one_example(&inputs, &[0.5, -0.5], 0.05);
+    assert!(is_pruned(&network) != pruned);
+    network.hidden_layers[0].apply_prune_mask();
+    assert_eq!(is_pruned(&network), pruned);
+}
+
+#[test]
+fn test_svd_compression() {
+    let frobenius_distance = |a: &[Vec<Weight>], b: &[Vec<Weight>]| -> Weight {
+        a.iter()
+            .flatten()
+            .zip(b.iter().flatten())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<Weight>()
+            .sqrt()
+    };
+
+    let mut init = XorShiftRng(0xa409_3822_299f_31d0);
+    for (neurons, inputs) in [(5, 3), (3, 6), (4, 4)] {
+        let layer = DenseLayer::new(
+            neurons,
+            inputs,
+            &mut |_, _| init.gen_range(-1., 1.),
+            &mut |ix| ix as Weight,
+            &TANH,
+        );
+        let (u, s, vt) = svd(&layer.weights);
+        let rank = neurons.min(inputs);
+        assert_eq!((u.len(), s.len(), vt.len()), (neurons, rank, rank));
+        assert!(s.windows(2).all(|pair| pair[0] >= pair[1]));
+        for a in 0..rank {
+            for b in 0..rank {
+                let dot: Weight = u.iter().map(|row| row[a] * row[b]).sum();
+                assert!((dot - if a == b { 1. } else { 0. }).abs() < 1e-5);
+            }
+        }
+
+        let lossless = layer.compress_weights(rank);
+        assert!(frobenius_distance(&lossless.weights(), &layer.weights) < 1e-5);
+
+        // Eckart-Young: the error of the best rank-1 approximation is the norm of the discarded singular values
+        let rank_one = layer.compress_weights(1);
+        assert_eq!(rank_one.parameter_count(), neurons + inputs + neurons);
+        let expected_error = s[1..].iter().map(|sigma| sigma * sigma).sum::<Weight>().sqrt();
+        assert!((frobenius_distance(&rank_one.weights(), &layer.weights) - expected_error).abs() < 1e-4);
+    }
+
+    let mut layer = DenseLayer::new(
+        4,
+        3,
+        &mut |_, _| init.gen_range(-1., 1.),
+        &mut |ix| 0.1 * ix as Weight,
+        &TANH,
+    );
+    let mut factored = layer.compress_weights(3);
+    let inputs = [0.4, -0.9, 0.2];
+    layer.forward_propagate(&inputs);
+    factored.forward_propagate(&inputs);
+    for (a, b) in layer.outputs.iter().zip(factored.outputs.iter()) {
+        assert!((a - b).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_randomized_svd() {
+    let reconstruction_error = |matrix: &[Vec<Weight>], (u, s, vt): &SvdFactors| -> Weight {
+        let mut error = 0.;
+        for (row_ix, row) in matrix.iter().enumerate() {
+            for (col_ix, &val) in row.iter().enumerate() {
+                let approx: Weight = (0..s.len()).map(|ix| u[row_ix][ix] * s[ix] * vt[ix][col_ix]).sum();
+                error += (val - approx).powi(2);
+            }
+        }
+        error.sqrt()
+    };
+
+    // A 30x20 matrix of rank 3
+    let mut rng = XorShiftRng(0x082e_fa98_ec4e_6c89);
+    let mut factor = |rows: usize| -> Vec<Vec<Weight>> {
+        (0..rows)
+            .map(|_| (0..3).map(|_| rng.gen_range(-1., 1.)).collect())
+            .collect()
+    };
+    let (left, right) = (factor(30), factor(20));
+    let low_rank: Vec<Vec<Weight>> = left
+        .iter()
+        .map(|l| {
+            right
+                .iter()
+                .map(|r| l.iter().zip(r.iter()).map(|(a, b)| a * b).sum())
+                .collect()
+        })
+        .collect();
+    let randomized = randomized_svd(&low_rank, 3, 5, 1);
+    assert_eq!((randomized.0.len(), randomized.1.len(), randomized.2.len()), (30, 3, 3));
+    let (_, exact_s, _) = svd(&low_rank);
+    for (approx, exact) in randomized.1.iter().zip(exact_s.iter()) {
+        assert!((approx - exact).abs() < 1e-3 * exact);
+    }
+    assert!(reconstruction_error(&low_rank, &randomized) < 1e-3);
+
+    // Without any structure to find, the power iterations are what keep the approximation close to optimal
+    let random: Vec<Vec<Weight>> = (0..30)
+        .map(|_| (0..20).map(|_| rng.gen_range(-1., 1.)).collect())
+        .collect();
+    let (u, s, vt) = svd(&random);
+    let exact = (
+        u.iter().map(|row| row[..5].to_vec()).collect(),
+        s[..5].to_vec(),
+        vt[..5].to_vec(),
+    );
+    let optimal_error = reconstruction_error(&random, &exact);
+    let with_power_iterations = reconstruction_error(&random, &randomized_svd(&random, 5, 5, 3));
+    let without_power_iterations = reconstruction_error(&random, &randomized_svd(&random, 5, 0, 0));
+    assert!(
+        with_power_iterations < 1.05 * optimal_error,
+        "{} vs {}",
+        with_power_iterations,
+        optimal_error
+    );
+    assert!(with_power_iterations < without_power_iterations);
+}
+
+#[test]
+fn test_clip_weights() {
+    let weights = [[-0.5, 0.01, 0.3], [0.01, -0.01, 0.], [0.02, -2., -0.01]];
+    let mut layer = DenseLayer::new(3, 3, &mut |i, j| weights[i][j], &mut |_| 5., &RELU);
+    layer.clip_weights(-0.01, 0.01);
+    let expected = [[-0.01, 0.01, 0.01], [0.01, -0.01, 0.], [0.01, -0.01, -0.01]];
+    for (row, expected_row) in layer.weights.iter().zip(expected.iter()) {
+        assert_eq!(row.as_slice(), expected_row.as_slice());
+    }
+    assert_eq!(layer.biases, vec![5.; 3]);
+
+    let mut frozen = DenseLayer::new(3, 3, &mut |i, j| weights[i][j], &mut |_| 0., &RELU);
+    frozen.freeze();
+    frozen.clip_weights(-0.01, 0.01);
+    assert_eq!(frozen.weights[2][1], -2.);
+
+    let mut network = Network::builder()
+        .input_size(3)
+        .hidden_layer(4, &RELU)
+        .hidden_layer(4, &RELU)
+        .output_size(2)
+        .build()
+        .unwrap();
+    network.clip_all_weights(-0.25, 0.25);
+    let all_weights = network
+        .hidden_layers
+        .iter()
+        .flat_map(|layer| layer.weights.iter())
+        .chain(network.outputs.weights.iter())
+        .flatten();
+    assert!(all_weights.clone().all(|weight| (-0.25..=0.25).contains(weight)));
+    assert!(all_weights.clone().any(|weight| weight.abs() == 0.25));
+    assert!(all_weights.clone().any(|weight| weight.abs() < 0.25));
+}
+
+#[test]
+fn test_tape_gradients() {
+    fn f(tape: &Tape, x: Weight, y: Weight, z: Weight) -> (Var<'_>, [usize; 3]) {
+        let (x, y, z) = (tape.var(x), tape.var(y), tape.var(z));
+        // Reusing a variable accumulates its gradient along every path
+        let smooth = ((x * y).exp() + (z * z).max(y * 2.)).ln();
+        let output = smooth * x + (z * -0.5).exp() * y + 3.;
+        (output, [x.index, y.index, z.index])
+    }
+
+    let epsilon = 1e-3;
+    for point in [[0.3, -0.8, 1.2], [1.1, 0.9, -0.4], [-0.6, 0.2, 0.1]] {
+        let tape = Tape::new();
+        let (output, indices) = f(&tape, point[0], point[1], point[2]);
+        let gradients = tape.gradients(output);
+        for (var_ix, &index) in indices.iter().enumerate() {
+            let mut up = point;
+            up[var_ix] += epsilon;
+            let mut down = point;
+            down[var_ix] -= epsilon;
+            let numerical = (f(&Tape::new(), up[0], up[1], up[2]).0.value
+                - f(&Tape::new(), down[0], down[1], down[2]).0.value)
+                / (2. * epsilon);
+            assert!(
+                (numerical - gradients[index]).abs() < 5e-3,
+                "{:?}: {} vs {}",
+                point,
+                numerical,
+                gradients[index]
+            );
+        }
+    }
+
+    // Softplus built from the supported operations has a sigmoid for its derivative
+    for x in [-3., -0.5, 0., 2.] {
+        let tape = Tape::new();
+        let input = tape.var(x);
+        let softplus = (input.exp() + 1.).ln();
+        assert!((softplus.value - (1. + x.exp()).ln()).abs() < 1e-6);
+        assert!((tape.gradients(softplus)[input.index] - sigmoid(x)).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_static_output_layer_matches_output_layer() {
+    let mut init = |i: usize, j: usize| ((i * 3 + j) as Weight * 0.7).sin();
+    let mut dynamic = OutputLayer::new(&SIGMOID, &MEAN_SQUARED_ERROR, &mut init, 3, 2);
+    let mut fixed = StaticOutputLayer::<3, 2>::new(&SIGMOID, &MEAN_SQUARED_ERROR, &mut init);
+    let inputs = [0.5, -1.25, 0.75];
+    let expected = [1., 0.];
+
+    for _ in 0..3 {
+        dynamic.compute(&inputs);
+        fixed.compute(&inputs);
+        assert_eq!(dynamic.outputs.as_slice(), fixed.outputs.as_slice());
+        dynamic.compute_costs(&expected);
+        fixed.compute_costs(&expected);
+        assert_eq!(dynamic.costs.as_slice(), fixed.costs.as_slice());
+        dynamic.compute_gradients();
+        fixed.compute_gradients();
+        assert_eq!(dynamic.neuron_gradients.as_slice(), fixed.neuron_gradients.as_slice());
+
+        let mut dynamic_input_gradients = [0.; 3];
+        let mut fixed_input_gradients = [0.; 3];
+        dynamic.compute_input_gradients(&mut dynamic_input_gradients);
+        fixed.compute_input_gradients(&mut fixed_input_gradients);
+        assert_eq!(dynamic_input_gradients, fixed_input_gradients);
+
+        dynamic.update_weights(&inputs, 0.5);
+        fixed.update_weights(&inputs, 0.5);
+        for (dynamic_row, fixed_row) in dynamic.weights.iter().zip(fixed.weights.iter()) {
+            assert_eq!(dynamic_row.as_slice(), fixed_row.as_slice());
+        }
+    }
+}
+
+#[test]
+fn test_typed_dense_layers() {
+    let first = TypedDenseLayer::<3, 2>::new(&mut |i, j| (i + j) as Weight * 0.25, &mut |_| 0.1, &RELU);
+    let second = TypedDenseLayer::<2, 1>::new(&mut |_, j| if j == 0 { 1. } else { -1. }, &mut |_| 0., &IDENTITY);
+    let mut connected = connect(first, second);
+    let outputs = *connected.forward_propagate(&[1., 2., -1.]);
+
+    let mut first = DenseLayer::new(2, 3, &mut |i, j| (i + j) as Weight * 0.25, &mut |_| 0.1, &RELU);
+    let mut second = DenseLayer::new(1, 2, &mut |_, j| if j == 0 { 1. } else { -1. }, &mut |_| 0., &IDENTITY);
+    first.forward_propagate(&[1., 2., -1.]);
+    second.forward_propagate(&first.outputs);
+    assert_eq!(outputs, [second.outputs[0]]);
+
+    assert!(TypedDenseLayer::<3, 2>::from_dynamic(first).is_some());
+    assert!(TypedDenseLayer::<2, 2>::from_dynamic(second).is_none());
+}
+
+/// A single linear neuron trained with the delta rule, standing in for a network type other than `Network`
+struct LinearModel {
+    weights: Vec<Weight>,
+    output: [Weight; 1],
+}
+
+impl Model for LinearModel {
+    fn forward_propagate(&mut self, inputs: &[Weight]) -> &[Weight] {
+        self.output[0] = self.weights.iter().zip(inputs.iter()).map(|(w, x)| w * x).sum();
+        &self.output
+    }
+
+    fn train_one_sample(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+        let error = expected[0] - self.forward_propagate(example)[0];
+        for (weight, &input) in self.weights.iter_mut().zip(example.iter()) {
+            *weight += learning_rate * 2. * error * input;
+        }
+        error * error
+    }
+
+    fn accumulate_gradients(&mut self, example: &[Weight], expected: &[Weight], gradients: &mut Vec<Weight>) -> Weight {
+        let error = expected[0] - self.forward_propagate(example)[0];
+        gradients.resize(self.weights.len(), 0.);
+        for (gradient, &input) in gradients.iter_mut().zip(example.iter()) {
+            *gradient += 2. * error * input;
+        }
+        error * error
+    }
+
+    fn apply_gradients(&mut self, gradients: &[Weight], scale: Weight) {
+        for (weight, &gradient) in self.weights.iter_mut().zip(gradients.iter()) {
+            *weight += scale * gradient;
+        }
+    }
+
+    fn param_count(&self) -> usize { self.weights.len() }
+
+    fn save(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        for weight in &self.weights {
+            writer.write_all(&weight.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn load(reader: &mut impl std::io::Read) -> std::io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let weights = bytes
+            .chunks(4)
+            .map(|chunk| Weight::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(LinearModel { weights, output: [0.] })
+    }
+}
+
+#[test]
+fn test_model_trait() {
+    fn fit<N: Model>(model: &mut N, examples: &[Vec<Weight>], expected: &[Vec<Weight>], epochs: usize) -> Weight {
+        let losses = fit_model(model, (examples, expected), epochs, 0.05);
+        assert_eq!(losses.len(), epochs);
+        losses[epochs - 1]
+    }
+    fn round_trip<N: Model>(model: &mut N, inputs: &[Weight]) {
+        let mut bytes = Vec::new();
+        model.save(&mut bytes).unwrap();
+        let mut loaded = N::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.param_count(), model.param_count());
+        assert_eq!(loaded.predict(inputs), model.predict(inputs));
+    }
+
+    let examples = vec![vec![1., 0.], vec![0., 1.], vec![1., 1.], vec![0.5, -1.]];
+    let expected = vec![vec![0.5], vec![-0.25], vec![0.25], vec![0.5]];
+
+    let mut network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &TANH)
+        .output_size(1)
+        .learning_rate(0.05)
+        .build()
+        .unwrap();
+    assert_eq!(network.param_count(), 2 * 3 + 3 + 3);
+    let initial_loss = fit(&mut network, &examples, &expected, 1);
+    assert!(fit(&mut network, &examples, &expected, 300) < 0.1 * initial_loss);
+    round_trip(&mut network, &examples[0]);
+
+    let mut linear = LinearModel {
+        weights: vec![0., 0.],
+        output: [0.],
+    };
+    assert!(fit(&mut linear, &examples, &expected, 300) < 1e-4);
+    assert!((linear.weights[0] - 0.5).abs() < 1e-2 && (linear.weights[1] + 0.25).abs() < 1e-2);
+    round_trip(&mut linear, &examples[0]);
+
+    let mut ensemble = Ensemble::new(vec![
+        LinearModel {
+            weights: vec![1., 0.],
+            output: [0.],
+        },
+        LinearModel {
+            weights: vec![0., 3.],
+            output: [0.],
+        },
+    ]);
+    assert_eq!(ensemble.compute(&[2., 1.]), vec![2.5]);
+    let losses = ensemble.fit((&examples, &expected), 300, 0.05);
+    assert!(losses[299] < 1e-4 && losses[299] < losses[0]);
+    assert!((ensemble.compute(&[2., 1.])[0] - 0.75).abs() < 1e-2);
+}
+
+#[test]
+fn test_trainer_is_generic_over_models() {
+    /// Trains for `epochs` epochs with gradients accumulated over two examples and returns the last epoch's mean loss
+    fn train<N: Model>(model: N, examples: &[Vec<Weight>], expected: &[Vec<Weight>], epochs: usize) -> (N, Weight) {
+        let mut trainer = Trainer::new(model);
+        trainer.set_accumulation_steps(2);
+        let mut loss = 0.;
+        for _ in 0..epochs {
+            loss = examples
+                .iter()
+                .zip(expected.iter())
+                .map(|(example, expected)| trainer.train_one_example(example, expected, 0.05))
+                .sum::<Weight>()
+                / examples.len() as Weight;
+        }
+        assert_eq!(trainer.accumulated_steps, 0);
+        assert_eq!(trainer.gradients.len(), trainer.network.param_count());
+        (trainer.network, loss)
+    }
+
+    let examples = vec![vec![1., 0.], vec![0., 1.], vec![1., 1.], vec![0.5, -1.]];
+    let expected = vec![vec![0.5], vec![-0.25], vec![0.25], vec![0.5]];
+
+    let network = Network::builder()
+        .input_size(2)
+        .hidden_layer(3, &TANH)
+        .output_size(1)
+        .learning_rate(0.05)
+        .build()
+        .unwrap();
+    let (_, initial_loss) = train(network.clone(), &examples, &expected, 1);
+    let (_, trained_loss) = train(network, &examples, &expected, 500);
+    assert!(trained_loss < 0.1 * initial_loss);
+
+    // Each update averages the gradients of both examples in the pair
+    let linear = LinearModel {
+        weights: vec![0., 0.],
+        output: [0.],
+    };
+    let (linear, _) = train(linear, &examples[..2], &expected[..2], 1);
+    assert!((linear.weights[0] - 0.05 * 0.5).abs() < 1e-6);
+    assert!((linear.weights[1] - 0.05 * -0.25).abs() < 1e-6);
+
+    let linear = LinearModel {
+        weights: vec![0., 0.],
+        output: [0.],
+    };
+    let (linear, loss) = train(linear, &examples, &expected, 500);
+    assert!(loss < 1e-4);
+    assert!((linear.weights[0] - 0.5).abs() < 1e-2 && (linear.weights[1] + 0.25).abs() < 1e-2);
+}
+
+#[test]
+fn test_population_based_training() {
+    let mut rng = XorShiftRng(11);
+    let target = |x: &[Weight]| vec![(x[0] - 0.5 * x[1]).tanh()];
+    let mut make_data = |count: usize| -> (Vec<Vec<Weight>>, Vec<Vec<Weight>>) {
+        let examples: Vec<Vec<Weight>> = (0..count)
+            .map(|_| vec![rng.gen_range(-1., 1.), rng.gen_range(-1., 1.)])
+            .collect();
+        let expected = examples.iter().map(|x| target(x)).collect();
+        (examples, expected)
+    };
+    let (train_examples, train_expected) = make_data(60);
+    let (val_examples, val_expected) = make_data(30);
+
+    let learning_rates = [0.0003, 0.001, 0.003, 0.01, 0.03, 0.1, 0.0001, 0.0005];
+    let population = (0..learning_rates.len() as u64)
+        .map(|seed| {
+            let mut rng = XorShiftRng(seed * 2 + 1);
+            let mut init_weights = |_, _| rng.gen_range(-1., 1.);
+            Network {
+                hidden_layers: vec![DenseLayer::new(6, 2, &mut init_weights, &mut |_| 0., &TANH)],
+                outputs: Box::new(OutputLayer::new(&Identity, &MeanSquaredError, &mut init_weights, 6, 1)),
+                learning_rate: 0.,
+            }
+        })
+        .collect();
+    let hyperparams = learning_rates
+        .iter()
+        .map(|&learning_rate| HyperParams {
+            learning_rate,
+            weight_decay: 1e-4,
+        })
+        .collect();
+    let mut pbt = PopulationBasedTrainer::new(population, hyperparams);
+
+    let mut losses = Vec::new();
+    for _ in 0..15 {
+        losses.push(pbt.step(
+            (&train_examples, &train_expected),
+            (&val_examples, &val_expected),
+            60,
+            &mut rng,
+        ));
+    }
+    println!("mean validation losses: {:?}", losses);
+    println!("hyperparams: {:?}", pbt.hyperparams);
+
+    for pair in losses.windows(2) {
+        assert!(pair[1] <= pair[0]);
+    }
+    assert!(losses[losses.len() - 1] < losses[0] / 4.);
+    // The slowest learners have been replaced by faster ones
+    assert!(pbt
+        .hyperparams
+        .iter()
+        .all(|hyperparams| hyperparams.learning_rate > 0.0001));
+    let best = pbt.best_ix();
+    assert_eq!(
+        pbt.validation_losses[best],
+        pbt.validation_losses
+            .iter()
+            .cloned()
+            .fold(Weight::INFINITY, Weight::min)
+    );
+}
+
+fn make_regression_data(rng: &mut impl Rng, count: usize) -> (Vec<Vec<Weight>>, Vec<Vec<Weight>>) {
+    let examples: Vec<Vec<Weight>> = (0..count)
+        .map(|_| vec![rng.gen_range(-1., 1.), rng.gen_range(-1., 1.)])
+        .collect();
+    let expected = examples.iter().map(|x| vec![(2. * x[0] * x[1]).tanh()]).collect();
+    (examples, expected)
+}
+
+fn build_for_config(config: &HyperParamConfig) -> Network {
+    let mut builder = Network::builder()
+        .input_size(2)
+        .output_size(1)
+        .learning_rate(config.learning_rate);
+    for _ in 0..config.hidden_layer_count {
+        builder = builder.hidden_layer(config.hidden_size, &TANH);
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn test_random_search() {
+    let mut rng = XorShiftRng(5);
+    let (train_examples, train_expected) = make_regression_data(&mut rng, 40);
+    let (val_examples, val_expected) = make_regression_data(&mut rng, 20);
+    let param_space = ParamSpace {
+        learning_rate: (1e-4, 0.3),
+        hidden_sizes: vec![2, 4, 8],
+        hidden_layer_counts: vec![1, 2],
+    };
+
+    let built = std::cell::RefCell::new(Vec::new());
+    let mut search = RandomSearch::new(param_space.clone(), 12, 20, 3);
+    let best = search.run(
+        |config| {
+            built.borrow_mut().push(*config);
+            build_for_config(config)
+        },
+        (&train_examples, &train_expected),
+        (&val_examples, &val_expected),
+    );
+
+    let built = built.into_inner();
+    assert_eq!(built.len(), 12);
+    assert_eq!(search.trials.len(), 12);
+    for (trial, config) in search.trials.iter().zip(built.iter()) {
+        assert_eq!(trial.config, *config);
+        assert!(config.learning_rate >= 1e-4 && config.learning_rate <= 0.3);
+        assert!(param_space.hidden_sizes.contains(&config.hidden_size));
+        assert!(param_space.hidden_layer_counts.contains(&config.hidden_layer_count));
+    }
+    // Trials are random, so they shouldn't all land on the same configuration
+    assert!(built.iter().any(|config| config.hidden_size != built[0].hidden_size));
+
+    let best_loss = search
+        .trials
+        .iter()
+        .find(|trial| trial.config == best)
+        .unwrap()
+        .validation_loss;
+    assert!(search.trials.iter().all(|trial| best_loss <= trial.validation_loss));
+    assert!(search.trials.iter().any(|trial| trial.validation_loss > best_loss));
+}
+
+#[test]
+fn test_gaussian_process_interpolates_observations() {
+    let mut gp = GaussianProcess::new(0.3, 1., 1e-6);
+    let inputs = vec![vec![0.1], vec![0.4], vec![0.5], vec![0.9]];
+    let targets = [2., -1., 0.5, 3.];
+    gp.fit(&inputs, &targets);
+    assert_eq!(gp.observation_count(), 4);
+
+    for (input, &target) in inputs.iter().zip(targets.iter()) {
+        let (mean, variance) = gp.predict(input);
+        assert!((mean - target).abs() < 1e-2, "{} != {}", mean, target);
+        assert!(variance < 1e-3);
+    }
+    // Far from the data the posterior falls back to the prior, centred on the mean of the targets
+    let (mean, variance) = gp.predict(&[5.]);
+    assert!((mean - 1.125).abs() < 1e-3);
+    assert!(variance > gp.predict(&[0.7]).1);
+
+    let ei = AcquisitionFunction::ExpectedImprovement { xi: 0. };
+    assert_eq!(ei.evaluate(1., 0., 0.5), 0.);
+    assert_eq!(ei.evaluate(0.2, 0., 0.5), 0.3);
+    assert!(ei.evaluate(1., 1., 0.5) > ei.evaluate(1., 0.1, 0.5));
+    assert!(ei.evaluate(0., 0.1, 0.5) > ei.evaluate(0.4, 0.1, 0.5));
+    let ucb = AcquisitionFunction::UpperConfidenceBound { kappa: 2. };
+    assert_eq!(ucb.evaluate(1., 0.25, 0.), 0.);
+}
+
+#[test]
+fn test_bayesian_optimizer() {
+    let param_space = ParamSpace {
+        learning_rate: (1e-4, 1.),
+        hidden_sizes: vec![2, 4, 8, 16],
+        hidden_layer_counts: vec![1, 2, 3],
+    };
+    // A cheap stand-in for training a network, best at a learning rate of 0.01 with 2 hidden layers of 8 neurons
+    let objective = |config: &HyperParamConfig| {
+        (config.learning_rate.log10() + 2.).powi(2)
+            + if config.hidden_size == 8 { 0. } else { 0.5 }
+            + if config.hidden_layer_count == 2 { 0. } else { 0.5 }
+    };
+
+    let config = param_space.sample(&mut XorShiftRng(1));
+    let point = param_space.to_unit_cube(&config);
+    let round_trip = param_space.from_unit_cube(&point);
+    assert!((round_trip.learning_rate - config.learning_rate).abs() < 1e-4 * config.learning_rate);
+    assert_eq!(
+        (round_trip.hidden_size, round_trip.hidden_layer_count),
+        (config.hidden_size, config.hidden_layer_count)
+    );
+
+    let best = |acquisition: AcquisitionFunction| {
+        let mut optimizer = BayesianOptimizer::new(param_space.clone(), acquisition, 25, 0, 3);
+        for _ in 0..optimizer.n_trials {
+            let config = optimizer.suggest();
+            optimizer.observe(config, objective(&config));
+        }
+        assert_eq!(optimizer.gp.observation_count(), 25);
+        let losses: Vec<Weight> = optimizer.trials.iter().map(|trial| trial.validation_loss).collect();
+        losses.iter().cloned().fold(Weight::INFINITY, Weight::min)
+    };
+    let mut random = RandomSearch::new(param_space.clone(), 25, 0, 3);
+    let random_best = (0..25)
+        .map(|_| objective(&random.param_space.sample(&mut random.rng)))
+        .fold(Weight::INFINITY, Weight::min);
+    let ei_best = best(AcquisitionFunction::ExpectedImprovement { xi: 0.01 });
+    let ucb_best = best(AcquisitionFunction::UpperConfidenceBound { kappa: 2. });
+    println!(
+        "random best={}, ei best={}, ucb best={}",
+        random_best, ei_best, ucb_best
+    );
+
+    assert!(ei_best < random_best);
+    assert!(ucb_best < random_best);
+    assert!(ei_best < 0.1);
+}
+
+#[test]
+fn test_swa_averages_snapshots() {
+    let mut rng = XorShiftRng(21);
+    let mut make_data = |count: usize, noise: Weight| -> (Vec<Vec<Weight>>, Vec<Vec<Weight>>) {
+        let examples: Vec<Vec<Weight>> = (0..count)
+            .map(|_| vec![rng.gen_range(-1., 1.), rng.gen_range(-1., 1.)])
+            .collect();
+        let expected = examples
+            .iter()
+            .map(|x| vec![0.7 * x[0] - 0.4 * x[1] + noise * rng.gen_range(-1., 1.)])
+            .collect();
+        (examples, expected)
+    };
+    let (train_examples, train_expected) = make_data(50, 0.5);
+    let (val_examples, val_expected) = make_data(100, 0.);
+
+    let mut network = build_small_network(&mut pcg::Pcg::default());
+    let mut averager = SwaAverager::new();
+    for epoch in 0..40 {
+        for (example, expected) in train_examples.iter().zip(train_expected.iter()) {
+            network.train_one_example(example, expected, 0.1);
+        }
+        if epoch >= 20 {
+            averager.update_average(&network);
+        }
+    }
+    assert_eq!(averager.num_models, 20);
+
+    let last_loss = network.evaluate(&val_examples, &val_expected).mean_loss_per_example;
+    let mut averaged = network.clone();
+    averager.apply_to(&mut averaged);
+    let averaged_loss = averaged.evaluate(&val_examples, &val_expected).mean_loss_per_example;
+    println!("last checkpoint loss={}, swa loss={}", last_loss, averaged_loss);
+    assert!(averaged_loss < last_loss);
+
+    // Averaging a single network and applying it is a no-op
+    let mut single = SwaAverager::new();
+    single.update_average(&network);
+    let mut copy = build_small_network(&mut pcg::Pcg::new(3, 0));
+    single.apply_to(&mut copy);
+    assert_eq!(all_weights(&copy), all_weights(&network));
+}
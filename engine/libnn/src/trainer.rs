@@ -0,0 +1,166 @@
+use crate::{Callback, GradientEvent, GradientMonitor, Model, Network, Weight};
+
+/// A set of examples alongside the expected output for each
+pub type LabeledExamples<'a> = (&'a [Vec<Weight>], &'a [Vec<Weight>]);
+
+/// Trains any `Model` on every example in order for `epochs` epochs, taking one step per example, and returns the mean
+/// training loss of each epoch.  `Trainer` adds gradient accumulation and gradient monitoring on top of this.
+pub fn fit_model(
+    model: &mut impl Model,
+    (examples, expected): LabeledExamples,
+    epochs: usize,
+    learning_rate: Weight,
+) -> Vec<Weight> {
+    assert_eq!(examples.len(), expected.len());
+    (0..epochs)
+        .map(|_| {
+            let total_loss: Weight = examples
+                .iter()
+                .zip(expected.iter())
+                .map(|(example, expected)| model.train_one_sample(example, expected, learning_rate))
+                .sum();
+            if examples.is_empty() {
+                0.
+            } else {
+                total_loss / examples.len() as Weight
+            }
+        })
+        .collect()
+}
+
+/// Drives training of any `Model`.  `fit` additionally needs a `Network`, since callbacks are given the network to
+/// inspect and adjust.
+///
+/// With `accumulation_steps` greater than 1, gradients from that many examples are summed before a single weight
+/// update is applied using their average.  This simulates training with a batch that many times larger without needing
+/// to hold the whole batch at once.
+pub struct Trainer<N: Model = Network> {
+    pub network: N,
+    pub accumulation_steps: usize,
+    /// How many examples have had their gradients accumulated since the last weight update
+    pub accumulated_steps: usize,
+    /// Accumulated gradients of every parameter, laid out as by `Model::accumulate_gradients`.  Empty until the first
+    /// example is seen.
+    pub gradients: Vec<Weight>,
+    /// Checks gradients after every backward pass when set
+    pub gradient_monitor: Option<GradientMonitor>,
+}
+
+impl<N: Model> Trainer<N> {
+    pub fn new(network: N) -> Self {
+        Trainer {
+            network,
+            accumulation_steps: 1,
+            accumulated_steps: 0,
+            gradients: Vec::new(),
+            gradient_monitor: None,
+        }
+    }
+
+    /// Calls `hook` for every NaN or infinite neuron gradient found after each backward pass.
+    pub fn enable_gradient_monitoring(&mut self, hook: impl Fn(GradientEvent) + 'static) {
+        self.gradient_monitor = Some(GradientMonitor::new(hook));
+    }
+
+    pub fn disable_gradient_monitoring(&mut self) { self.gradient_monitor = None; }
+
+    /// Sets how many examples have their gradients accumulated before each weight update.  Any gradients accumulated
+    /// so far are kept and count towards the new total.
+    pub fn set_accumulation_steps(&mut self, n: usize) {
+        assert!(n > 0, "Must accumulate gradients over at least one step");
+        self.accumulation_steps = n;
+    }
+
+    /// Accumulates gradients for `example` and updates weights once `accumulation_steps` examples have been seen.
+    /// Returns the average cost of the output before any weights were updated.
+    pub fn train_one_example(&mut self, example: &[Weight], expected: &[Weight], learning_rate: Weight) -> Weight {
+        let cost = self
+            .network
+            .accumulate_gradients(example, expected, &mut self.gradients);
+        if let Some(monitor) = &self.gradient_monitor {
+            monitor.check(&self.network);
+        }
+        self.accumulated_steps += 1;
+        if self.accumulated_steps >= self.accumulation_steps {
+            self.apply_accumulated_gradients(learning_rate);
+        }
+
+        cost
+    }
+
+    /// Updates weights and biases using the average of all gradients accumulated so far, then clears them.
+    pub fn apply_accumulated_gradients(&mut self, learning_rate: Weight) {
+        if self.accumulated_steps == 0 {
+            return;
+        }
+        let scale = learning_rate / self.accumulated_steps as Weight;
+        self.network.apply_gradients(&self.gradients, scale);
+        self.gradients.fill(0.);
+
+        self.accumulated_steps = 0;
+    }
+}
+
+impl Trainer<Network> {
+    /// Trains on every example in order for `epochs` epochs, using the network's current learning rate for each update
+    /// so that callbacks can adjust it between epochs.  Any gradients still accumulated at the end of an epoch are
+    /// applied so that every epoch ends with a weight update.
+    pub fn fit(
+        &mut self,
+        examples: &[Vec<Weight>],
+        expected: &[Vec<Weight>],
+        validation: Option<LabeledExamples>,
+        epochs: usize,
+        callbacks: &mut [&mut dyn Callback],
+    ) {
+        assert_eq!(examples.len(), expected.len());
+
+        let mut batch_ix = 0;
+        for epoch in 0..epochs {
+            for callback in callbacks.iter_mut() {
+                callback.on_epoch_start(epoch, &mut self.network);
+            }
+
+            let mut total_loss = 0.;
+            let mut batch_loss = 0.;
+            for (example, expected) in examples.iter().zip(expected.iter()) {
+                let learning_rate = self.network.learning_rate;
+                let loss = self.train_one_example(example, expected, learning_rate);
+                total_loss += loss;
+                batch_loss += loss;
+
+                if self.accumulated_steps == 0 {
+                    for callback in callbacks.iter_mut() {
+                        callback.on_batch_end(batch_ix, batch_loss / self.accumulation_steps as Weight);
+                    }
+                    batch_ix += 1;
+                    batch_loss = 0.;
+                }
+            }
+            if self.accumulated_steps > 0 {
+                let leftover_steps = self.accumulated_steps;
+                self.apply_accumulated_gradients(self.network.learning_rate);
+                for callback in callbacks.iter_mut() {
+                    callback.on_batch_end(batch_ix, batch_loss / leftover_steps as Weight);
+                }
+                batch_ix += 1;
+            }
+
+            let train_loss = if examples.is_empty() {
+                0.
+            } else {
+                total_loss / examples.len() as Weight
+            };
+            let val_loss = validation.map(|(val_examples, val_expected)| {
+                self.network.evaluate(val_examples, val_expected).mean_loss_per_example
+            });
+            for callback in callbacks.iter_mut() {
+                callback.on_epoch_end(epoch, train_loss, val_loss, &self.network);
+            }
+        }
+
+        for callback in callbacks.iter_mut() {
+            callback.on_training_end(&self.network);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::marker::PhantomData;
+
+use crate::{ActivationFunction, DenseLayer, Weight};
+
+/// Marks a layer dimension in a type, so that layers with mismatched sizes can't be connected
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LayerDim<const N: usize>;
+
+/// A `DenseLayer` with `IN` inputs and `OUT` neurons known at compile time.  It delegates to the dynamic layer, so
+/// the dimensions only exist in the type and cost nothing at runtime.
+pub struct TypedDenseLayer<const IN: usize, const OUT: usize> {
+    pub layer: DenseLayer,
+    dims: PhantomData<(LayerDim<IN>, LayerDim<OUT>)>,
+}
+
+impl<const IN: usize, const OUT: usize> TypedDenseLayer<IN, OUT> {
+    pub fn new(
+        init_weights: &mut impl FnMut(usize, usize) -> Weight,
+        init_biases: &mut impl FnMut(usize) -> Weight,
+        activation_fn: &'static dyn ActivationFunction,
+    ) -> Self {
+        TypedDenseLayer {
+            layer: DenseLayer::new(OUT, IN, init_weights, init_biases, activation_fn),
+            dims: PhantomData,
+        }
+    }
+
+    /// Wraps an existing layer, returning `None` if its sizes don't match `IN` and `OUT`.
+    pub fn from_dynamic(layer: DenseLayer) -> Option<Self> {
+        let matches = layer.weights.len() == OUT && layer.weights.iter().all(|row| row.len() == IN);
+        matches.then_some(TypedDenseLayer {
+            layer,
+            dims: PhantomData,
+        })
+    }
+
+    pub fn into_dynamic(self) -> DenseLayer { self.layer }
+
+    pub fn forward_propagate(&mut self, inputs: &[Weight; IN]) -> &[Weight; OUT] {
+        self.layer.forward_propagate(inputs);
+        self.layer
+            .outputs
+            .as_slice()
+            .try_into()
+            .expect("Layer size changed after construction")
+    }
+}
+
+/// Two layers where the outputs of `first` feed the inputs of `second`, built by `connect`
+pub struct ConnectedLayers<const IN: usize, const MID: usize, const OUT: usize> {
+    pub first: TypedDenseLayer<IN, MID>,
+    pub second: TypedDenseLayer<MID, OUT>,
+}
+
+impl<const IN: usize, const MID: usize, const OUT: usize> ConnectedLayers<IN, MID, OUT> {
+    pub fn forward_propagate(&mut self, inputs: &[Weight; IN]) -> &[Weight; OUT] {
+        let hidden = *self.first.forward_propagate(inputs);
+        self.second.forward_propagate(&hidden)
+    }
+}
+
+/// Feeds `first` into `second`.  The number of outputs of `first` has to match the number of inputs of `second`, so
+/// this doesn't compile:
+///
+/// ```compile_fail
+/// use libnn::{connect, TypedDenseLayer, RELU};
+///
+/// let first = TypedDenseLayer::<4, 3>::new(&mut |_, _| 0.5, &mut |_| 0., &RELU);
+/// let second = TypedDenseLayer::<2, 1>::new(&mut |_, _| 0.5, &mut |_| 0., &RELU);
+/// connect(first, second);
+/// ```
+pub fn connect<const IN: usize, const MID: usize, const OUT: usize>(
+    first: TypedDenseLayer<IN, MID>,
+    second: TypedDenseLayer<MID, OUT>,
+) -> ConnectedLayers<IN, MID, OUT> {
+    ConnectedLayers { first, second }
+}